@@ -291,6 +291,23 @@ pub fn generate(call: &CallExpression, state: &mut State) -> String {
         FunctionData::GeneratedFunc { name, args } => {
             format!("{}({})", name, args.gen(state))
         }
+        // Legacy dict functions are passed the dict as `self`, which the
+        // runtime decides on when it looks the function up
+        FunctionData::ExprFunc {
+            caller: Expression::DictAccess(access),
+            args,
+        } => {
+            let args = match args.is_empty() {
+                true => String::new(),
+                false => format!(", {}", args.gen(state)),
+            };
+
+            format!(
+                "NVIM9.dict_call({}, '{}'{args})",
+                access.container.gen(state),
+                access.index.gen(state)
+            )
+        }
         FunctionData::ExprFunc { caller, args } => {
             format!("{}({})", caller.gen(state), args.gen(state))
         }
@@ -1,20 +1,27 @@
 #![feature(iter_intersperse)]
 
-use std::path::Path;
+use std::{collections::HashSet, path::Path};
 
 use lexer::Lexer;
 use parser::{
-    self, new_parser, ArrayLiteral, AssignStatement, AugroupCommand,
-    AutocmdCommand, Body, BreakCommand, CallCommand, CallExpression,
-    ContinueCommand, DeclCommand, DefCommand, DeferCommand, DictAccess,
-    DictLiteral, EchoCommand, ExecuteCommand, ElseCommand, ElseIfCommand, ExCommand,
-    Expandable, ExportCommand, Expression, ForCommand, GroupedExpression,
-    Heredoc, Identifier, IfCommand, ImportCommand, IndexExpression, IndexType,
-    InfixExpression, InnerType, Lambda, Literal, MethodCall, MutationStatement,
-    PrefixExpression, RawIdentifier, Register, ReturnCommand, ScopedIdentifier,
-    SharedCommand, Signature, StatementCommand, Ternary, TryCommand, Type,
-    UnpackIdentifier, UserCommand, VarCommand, Vim9ScriptCommand, VimBoolean,
-    VimKey, VimNumber, VimOption, VimScope, VimString, WhileCommand,
+    self, new_parser, Address, ArrayLiteral, AssignStatement, AugroupCommand,
+    AugroupDeleteCommand, AutocmdBlock, AutocmdBuffer, AutocmdCommand,
+    AutocmdPattern, Body, BreakCommand, CallCommand, CallExpression,
+    ClassCommand, ClassMember, ContinueCommand, DeclCommand, DefCommand,
+    DeferCommand, Diagnostic, DictAccess, DictLiteral, EchoCommand,
+    EchoErrCommand, ElseCommand, ElseIfCommand, EnumCommand, ErrorCommand,
+    ExCommand, ExecuteCommand, Expandable, ExportCommand, Expression,
+    ForCommand, FunctionCommand, GroupedExpression, Heredoc, Identifier,
+    IfCommand, ImportCommand, IndexExpression, IndexType, InfixExpression,
+    InnerType, InterfaceCommand, InterpolatedPart, InterpolatedString, Lambda,
+    LetCommand, LineNumber, Literal, MapCommand, MapKind, MapRhs, MethodCall,
+    ModifiedCommand, ModifierKind, MutationStatement, PrefixExpression, Range,
+    RawIdentifier, Register, ReturnCommand, ScopedIdentifier, SetAction,
+    SetCommand, SetScope, SharedCommand, Signature, StatementCommand,
+    StringBytes, Ternary, ThrowCommand, TryCommand, Type, TypeAliasCommand,
+    UnletCommand, UnpackIdentifier, UserCommand, VarCommand, Vim9ScriptCommand,
+    VimBoolean, VimKey, VimNumber, VimOption, VimScope, VimString,
+    WhileCommand,
 };
 
 // this word is missspelled
@@ -29,6 +36,15 @@ pub struct State {
     pub command_depth: i32,
     pub method_depth: i32,
 
+    /// The class and the names of its static variables, while generating
+    /// its methods. The methods can use those by their bare name.
+    pub class_statics: Option<(String, Vec<String>)>,
+
+    /// The variables of the legacy function that is being generated. They
+    /// are named `l_name`, and its arguments `a_name`, so that neither
+    /// clashes with the script variable `s:name`.
+    pub legacy_locals: Option<Vec<String>>,
+
     // TODO: We could modify the state as we are generating code.
     //  As we generate the code and notice certain identifiers are certain
     //  types, we can use that to do *some* optimizations
@@ -81,25 +97,20 @@ macro_rules! find_scope {
     }};
 }
 
-macro_rules! scope_or_empty {
-    ($state:ident, $m:pat) => {{
-        use ScopeKind::*;
-
-        let scope = $state.find_relevant_scope(|s| matches!(s.kind, $m));
-        match scope {
-            Some(scope) => scope,
-            None => &$state.scopes[0],
-        }
-    }};
-}
-
 #[derive(PartialEq, Eq, Debug)]
 pub enum ScopeKind {
     TopLevel,
     Function,
-    While { has_continue: bool },
-    For { has_continue: bool },
+    While {
+        has_continue: bool,
+    },
+    For {
+        has_continue: bool,
+    },
     If,
+    /// The body, catches and finally of a `try`, which are all generated as
+    /// closures that return how they were left
+    Try,
 }
 
 #[derive(Debug)]
@@ -118,6 +129,7 @@ impl Scope {
         match self.kind {
             ScopeKind::While { has_continue } => has_continue,
             ScopeKind::For { has_continue } => has_continue,
+            ScopeKind::Try => true,
             _ => false,
         }
     }
@@ -133,11 +145,18 @@ impl Generate for ExCommand {
             ExCommand::Vim9Script(cmd) => cmd.gen(state),
             ExCommand::Var(cmd) => cmd.gen(state),
             ExCommand::Echo(cmd) => cmd.gen(state),
+            ExCommand::EchoErr(cmd) => cmd.gen(state),
             ExCommand::Statement(cmd) => cmd.gen(state),
             ExCommand::Return(cmd) => cmd.gen(state),
             ExCommand::Def(cmd) => cmd.gen(state),
+            ExCommand::Function(cmd) => cmd.gen(state),
+            ExCommand::Let(cmd) => cmd.gen(state),
+            ExCommand::Class(cmd) => cmd.gen(state),
+            ExCommand::Interface(cmd) => cmd.gen(state),
+            ExCommand::Enum(cmd) => cmd.gen(state),
             ExCommand::If(cmd) => cmd.gen(state),
             ExCommand::Augroup(cmd) => cmd.gen(state),
+            ExCommand::AugroupDelete(cmd) => cmd.gen(state),
             ExCommand::Autocmd(cmd) => cmd.gen(state),
             ExCommand::Call(cmd) => cmd.gen(state),
             ExCommand::Decl(cmd) => cmd.gen(state),
@@ -151,6 +170,8 @@ impl Generate for ExCommand {
             }
             ExCommand::Heredoc(heredoc) => heredoc.gen(state),
             ExCommand::UserCommand(usercmd) => usercmd.gen(state),
+            ExCommand::Map(map) => map.gen(state),
+            ExCommand::Set(set) => set.gen(state),
             ExCommand::Eval(eval) => format!("{};", eval.expr.gen(state)),
             ExCommand::SharedCommand(shared) => shared.gen(state),
             ExCommand::ExportCommand(export) => export.gen(state),
@@ -158,15 +179,111 @@ impl Generate for ExCommand {
             ExCommand::Finish(_) => format!("return __VIM9_MODULE"),
             ExCommand::For(f) => f.gen(state),
             ExCommand::Try(t) => t.gen(state),
+            ExCommand::Throw(t) => t.gen(state),
             ExCommand::While(w) => w.gen(state),
             ExCommand::Break(b) => b.gen(state),
             ExCommand::Continue(c) => c.gen(state),
             ExCommand::Defer(defer) => defer.gen(state),
+            ExCommand::Unlet(unlet) => unlet.gen(state),
+            ExCommand::TypeAlias(alias) => alias.gen(state),
+            ExCommand::Error(err) => err.gen(state),
+            ExCommand::Modified(modified) => modified.gen(state),
             _ => todo!("Have not yet handled: {:?}", self),
         }
     }
 }
 
+/// Aliases are resolved by the parser, there is nothing left of them at
+/// runtime
+impl Generate for TypeAliasCommand {
+    fn gen(&self, _: &mut State) -> String {
+        String::new()
+    }
+}
+
+impl Generate for UnletCommand {
+    fn gen(&self, state: &mut State) -> String {
+        self.targets
+            .iter()
+            .map(|target| gen_unlet(state, target, self.bang))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Removes `target`, which fails when it does not exist unless `force` is set
+fn gen_unlet(state: &mut State, target: &Expression, force: bool) -> String {
+    let (table, key, name) = match target {
+        Expression::Identifier(Identifier::Scope(scoped)) => {
+            let key = gen_accessor(state, &scoped.accessor);
+            let (table, prefix) = match scoped.scope {
+                VimScope::Global => ("vim.g", "g"),
+                VimScope::VimVar => ("vim.v", "v"),
+                VimScope::Tab => ("vim.t", "t"),
+                VimScope::Window => ("vim.w", "w"),
+                VimScope::Buffer => ("vim.b", "b"),
+                VimScope::Script | VimScope::Local | VimScope::Argument => {
+                    return format!("{} = nil", scoped.gen(state));
+                }
+            };
+
+            (table, format!("\"{key}\""), format!("{prefix}:{key}"))
+        }
+        Expression::String(VimString::EnvironmentVariable(env)) => {
+            ("vim.env", lua_string(env.as_bytes()), format!("${env}"))
+        }
+        Expression::Identifier(ident) => {
+            return format!("{} = nil", ident.gen(state));
+        }
+        Expression::DictAccess(access) => {
+            return format!(
+                "NVIM9.unlet_item({}, \"{}\", {force})",
+                access.container.gen(state),
+                access.index.gen(state)
+            );
+        }
+        Expression::Index(index) => {
+            let container = index.container.gen(state);
+            return match index.index.as_ref() {
+                IndexType::Item(item) => format!(
+                    "NVIM9.unlet_item({container}, {}, {force})",
+                    item.gen(state)
+                ),
+                IndexType::Slice(slice) => format!(
+                    "NVIM9.unlet_slice({container}, {}, {})",
+                    slice
+                        .start
+                        .as_ref()
+                        .map_or("nil".to_string(), |item| item.gen(state)),
+                    slice
+                        .finish
+                        .as_ref()
+                        .map_or("nil".to_string(), |item| item.gen(state)),
+                ),
+            };
+        }
+        _ => unreachable!("cannot unlet: {:?}", target),
+    };
+
+    if force {
+        format!("{table}[{key}] = nil")
+    } else {
+        format!("NVIM9.unlet_var({table}, {key}, \"{name}\")")
+    }
+}
+
+impl Generate for ErrorCommand {
+    fn gen(&self, _: &mut State) -> String {
+        // Fail loudly when the broken code would have run,
+        // the same way vim reports the error when it reaches the line.
+        format!(
+            "error([==[vim9jit: {}: {}]==])",
+            self.span.start_row + 1,
+            self.diagnostic.message
+        )
+    }
+}
+
 impl Generate for DeferCommand {
     fn gen(&self, state: &mut State) -> String {
         state.push_defer();
@@ -180,7 +297,8 @@ impl Generate for DeferCommand {
 
 impl Generate for ContinueCommand {
     fn gen(&self, state: &mut State) -> String {
-        let scope = find_scope!(state, Function | While { .. } | For { .. });
+        let scope =
+            find_scope!(state, Function | While { .. } | For { .. } | Try);
         assert!(scope.kind != ScopeKind::Function, "continue: While/For");
         assert!(scope.has_continue(), "must have continue...");
 
@@ -190,19 +308,22 @@ impl Generate for ContinueCommand {
 
 impl Generate for BreakCommand {
     fn gen(&self, state: &mut State) -> String {
-        let scope = find_scope!(state, Function | While { .. } | For { .. });
-        assert!(
-            scope.kind != ScopeKind::Function,
-            "continue: While/For {:#?} // {:?}",
-            self,
-            scope
-        );
+        gen_break(state)
+    }
+}
 
-        if scope.has_continue() {
-            format!("return NVIM9.ITER_BREAK")
-        } else {
-            format!("break")
-        }
+fn gen_break(state: &State) -> String {
+    let scope = find_scope!(state, Function | While { .. } | For { .. } | Try);
+    assert!(
+        scope.kind != ScopeKind::Function,
+        "continue: While/For {:?}",
+        scope
+    );
+
+    if scope.has_continue() {
+        format!("return NVIM9.ITER_BREAK")
+    } else {
+        format!("break")
     }
 }
 
@@ -216,6 +337,7 @@ impl Generate for WhileCommand {
 
         if has_continue {
             let condition = self.condition.gen(state);
+            let ret = gen_return(state, Some("nvim9_ret".to_string()));
             return format!(
                 r#"
                     local body = function()
@@ -229,7 +351,7 @@ impl Generate for WhileCommand {
                         if nvim9_status == 2 then
                             break
                         elseif nvim9_status == 3 then
-                            return nvim9_ret
+                            {ret}
                         end
                     end
                 "#
@@ -242,19 +364,109 @@ impl Generate for WhileCommand {
 
 impl Generate for TryCommand {
     fn gen(&self, state: &mut State) -> String {
-        // TODO: try and catch LUL
-        self.body.gen(state)
+        let (body, _) = state.with_scope(ScopeKind::Try, |s| self.body.gen(s));
+        let catches = self
+            .catches
+            .iter()
+            .map(|catch| {
+                let (body, _) =
+                    state.with_scope(ScopeKind::Try, |s| catch.body.gen(s));
+                let pattern = match &catch.pattern {
+                    Some(pattern) => {
+                        format!("pattern = {},", lua_string(pattern.as_bytes()))
+                    }
+                    None => String::new(),
+                };
+
+                format!("{{ {pattern} body = function() {body} end }},")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let finally = match &self.finally {
+            Some(finally) => {
+                let (body, _) =
+                    state.with_scope(ScopeKind::Try, |s| finally.body.gen(s));
+                format!("function() {body} end")
+            }
+            None => "nil".to_string(),
+        };
+
+        // Leave the try the same way that its body, catch or finally did
+        let mut exits = vec![format!(
+            "if nvim9_status == NVIM9.ITER_RETURN then {}",
+            gen_return(state, Some("nvim9_ret".to_string()))
+        )];
+        let in_loop = state
+            .find_relevant_scope(|s| {
+                matches!(
+                    s.kind,
+                    ScopeKind::Function
+                        | ScopeKind::While { .. }
+                        | ScopeKind::For { .. }
+                        | ScopeKind::Try
+                )
+            })
+            .map_or(false, |s| s.kind != ScopeKind::Function);
+        if in_loop {
+            exits.push(format!(
+                "elseif nvim9_status == NVIM9.ITER_BREAK then {}",
+                gen_break(state)
+            ));
+
+            if try_has_continue(self) {
+                exits.push(
+                    "elseif nvim9_status == NVIM9.ITER_CONTINUE then return NVIM9.ITER_CONTINUE"
+                        .to_string(),
+                );
+            }
+        }
+
+        format!(
+            r#"
+            do
+                local nvim9_status, nvim9_ret = NVIM9.try(function()
+                    {body}
+                end, {{
+                    {catches}
+                }}, {finally})
+
+                {}
+                end
+            end
+            "#,
+            exits.join("\n")
+        )
     }
 }
 
+impl Generate for ThrowCommand {
+    fn gen(&self, state: &mut State) -> String {
+        format!("NVIM9.throw({})", self.expr.gen(state))
+    }
+}
+
+/// Whether a `continue` in `body` continues the loop that `body` is in
 fn continue_exists_in_scope(body: &Body) -> bool {
     body.commands.iter().any(|c| match c {
         ExCommand::If(ex) => continue_exists_in_scope(&ex.body),
+        ExCommand::Try(ex) => try_has_continue(ex),
         ExCommand::Continue(_) => true,
         _ => false,
     })
 }
 
+fn try_has_continue(try_: &TryCommand) -> bool {
+    continue_exists_in_scope(&try_.body)
+        || try_
+            .catches
+            .iter()
+            .any(|catch| continue_exists_in_scope(&catch.body))
+        || try_
+            .finally
+            .as_ref()
+            .map_or(false, |finally| continue_exists_in_scope(&finally.body))
+}
+
 impl Generate for ForCommand {
     // 0 => nothing
     // 1 => continue
@@ -278,6 +490,7 @@ impl Generate for ForCommand {
         };
 
         if has_continue {
+            let ret = gen_return(state, Some("nvim9_ret".to_string()));
             format!(
                 r#"
                     local body = function(_, {ident})
@@ -293,7 +506,7 @@ impl Generate for ForCommand {
                         if nvim9_status == NVIM9.ITER_BREAK then
                             break
                         elseif nvim9_status == NVIM9.ITER_RETURN then
-                            return nvim9_ret
+                            {ret}
                         end
                     end
                 "#
@@ -342,8 +555,14 @@ impl Generate for ExportCommand {
     fn gen(&self, state: &mut State) -> String {
         let exported = self.command.gen(state);
         let ident = match self.command.as_ref() {
+            // Importing scripts resolve exported aliases when they are
+            // parsed, see `Parser::with_path`
+            ExCommand::TypeAlias(_) => return exported,
             ExCommand::Var(var) => var.name.gen(state),
             ExCommand::Def(def) => def.name.gen(state),
+            ExCommand::Class(class) => class.name.gen(state),
+            ExCommand::Interface(interface) => interface.name.gen(state),
+            ExCommand::Enum(enum_) => enum_.name.gen(state),
             // ExCommand::Heredoc(_) => todo!(),
             // ExCommand::Decl(_) => todo!(),
             _ => {
@@ -357,7 +576,353 @@ impl Generate for ExportCommand {
 
 impl Generate for SharedCommand {
     fn gen(&self, _: &mut State) -> String {
-        format!("vim.cmd [[ {} ]]", self.contents.trim())
+        match &self.range {
+            Some(range) => nvim_cmd(self, range, None),
+            None => format!("vim.cmd [[ {} ]]", self.contents.trim()),
+        }
+    }
+}
+
+impl Generate for MapCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let mode = lua_string(self.mode.as_bytes());
+        let lhs = self.lhs.as_deref().map(|lhs| lua_string(lhs.as_bytes()));
+        let args = &self.args;
+
+        match self.kind {
+            MapKind::Map | MapKind::Noremap => {
+                let rhs = match self.rhs.as_ref().unwrap() {
+                    MapRhs::Keys(keys) => lua_string(keys.as_bytes()),
+                    MapRhs::Commands(body) => {
+                        let (body, _) = state
+                            .with_scope(ScopeKind::Function, |s| body.gen(s));
+                        format!("function()\n{body}\nend")
+                    }
+                    MapRhs::Expr(expr) => {
+                        let (expr, _) = state
+                            .with_scope(ScopeKind::Function, |s| expr.gen(s));
+                        format!("function()\nreturn {expr}\nend")
+                    }
+                };
+
+                // Mappings made with vim.keymap.set do not remap by default
+                let mut opts = vec![];
+                if self.kind == MapKind::Map {
+                    opts.push("remap = true".to_string());
+                }
+
+                for (name, enabled) in [
+                    ("buffer", args.buffer),
+                    ("nowait", args.nowait),
+                    ("silent", args.silent),
+                    ("script", args.script),
+                    ("expr", args.expr),
+                    ("unique", args.unique),
+                ] {
+                    if enabled {
+                        opts.push(format!("{name} = true"));
+                    }
+                }
+
+                let lhs = lhs.unwrap();
+                match opts.is_empty() {
+                    true => format!("vim.keymap.set({mode}, {lhs}, {rhs})"),
+                    false => format!(
+                        "vim.keymap.set({mode}, {lhs}, {rhs}, {{ {} }})",
+                        opts.join(", ")
+                    ),
+                }
+            }
+            MapKind::Unmap => {
+                let lhs = lhs.unwrap();
+                match args.buffer {
+                    true => format!(
+                        "vim.keymap.del({mode}, {lhs}, {{ buffer = true }})"
+                    ),
+                    false => format!("vim.keymap.del({mode}, {lhs})"),
+                }
+            }
+            // There is no api to clear all the mappings of a mode
+            MapKind::Clear => {
+                let command = match self.mode.as_str() {
+                    "!" => "mapclear!".to_string(),
+                    mode => format!("{mode}mapclear"),
+                };
+
+                match args.buffer {
+                    true => format!("vim.cmd [[ {command} <buffer> ]]"),
+                    false => format!("vim.cmd [[ {command} ]]"),
+                }
+            }
+        }
+    }
+}
+
+impl Generate for SetCommand {
+    fn gen(&self, _: &mut State) -> String {
+        let opt = match self.scope {
+            SetScope::Both => "vim.opt",
+            SetScope::Local => "vim.opt_local",
+            SetScope::Global => "vim.opt_global",
+        };
+
+        self.options
+            .iter()
+            .map(|option| {
+                let name = &option.name;
+                let target = format!("{opt}.{name}");
+                match &option.action {
+                    SetAction::Enable => format!("{target} = true"),
+                    SetAction::Disable => format!("{target} = false"),
+                    SetAction::Invert => {
+                        format!("{target} = not {target}:get()")
+                    }
+                    SetAction::Default => format!(
+                        "{target} = NVIM9.option_default({})",
+                        lua_string(name.as_bytes())
+                    ),
+                    SetAction::Assign(value) => {
+                        format!("{target} = {}", gen_option_value(name, value))
+                    }
+                    SetAction::Append(value) => {
+                        format!(
+                            "{target}:append({})",
+                            gen_option_value(name, value)
+                        )
+                    }
+                    SetAction::Remove(value) => {
+                        format!(
+                            "{target}:remove({})",
+                            gen_option_value(name, value)
+                        )
+                    }
+                    SetAction::Prepend(value) => {
+                        format!(
+                            "{target}:prepend({})",
+                            gen_option_value(name, value)
+                        )
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The value that `:set` gives to the option `name`. The value is a number
+/// or a string depending on the type of the option, which is known at
+/// runtime.
+fn gen_option_value(name: &str, value: &str) -> String {
+    format!(
+        "NVIM9.option_value({}, {})",
+        lua_string(name.as_bytes()),
+        lua_string(value.as_bytes())
+    )
+}
+
+/// The line of `address`, where `current` is the line that `.` refers to
+fn gen_address(address: &Address, current: &str) -> String {
+    let line = match &address.line {
+        LineNumber::Number(number) => number.to_string(),
+        LineNumber::Current => current.to_string(),
+        LineNumber::Last => "vim.fn.line('$')".to_string(),
+        LineNumber::Mark(mark) => {
+            let mark = format!("'{mark}");
+            format!("vim.fn.line({})", lua_string(mark.as_bytes()))
+        }
+        LineNumber::SearchForward(pattern) => format!(
+            "NVIM9.range.search({}, false, {current})",
+            lua_string(pattern.as_bytes())
+        ),
+        LineNumber::SearchBackward(pattern) => format!(
+            "NVIM9.range.search({}, true, {current})",
+            lua_string(pattern.as_bytes())
+        ),
+        LineNumber::LastSearchForward => {
+            format!("NVIM9.range.search(vim.fn.getreg('/'), false, {current})")
+        }
+        LineNumber::LastSearchBackward => {
+            format!("NVIM9.range.search(vim.fn.getreg('/'), true, {current})")
+        }
+    };
+
+    match address.offset {
+        0 => line,
+        offset if offset > 0 => format!("{line} + {offset}"),
+        offset => format!("{line} - {}", -offset),
+    }
+}
+
+/// The first and last line of `range`, the last is `None` for a single line
+fn gen_lines(range: &Range) -> (String, Option<String>) {
+    let current = "vim.fn.line('.')";
+    match range {
+        Range::Whole { .. } => {
+            ("1".to_string(), Some("vim.fn.line('$')".to_string()))
+        }
+        Range::Lines {
+            start,
+            end,
+            relative,
+            ..
+        } => {
+            let start = gen_address(start, current);
+
+            // With `;` the end is found from the start of the range
+            let end = end.as_ref().map(|end| match relative {
+                true => gen_address(end, &start),
+                false => gen_address(end, current),
+            });
+
+            (start, end)
+        }
+    }
+}
+
+/// Runs `shared` through `nvim_cmd`, instead of pasting the range into
+/// the command text.
+fn nvim_cmd(
+    shared: &SharedCommand,
+    range: &Range,
+    mods: Option<String>,
+) -> String {
+    let (start, end) = gen_lines(range);
+
+    // A range on its own, like `:5`, moves the cursor to its last line
+    let contents = shared.contents.trim();
+    if contents.is_empty() {
+        return format!("vim.cmd(tostring({}))", end.unwrap_or(start));
+    }
+
+    let range = match end {
+        Some(end) => format!("{{ {start}, {end} }}"),
+        None => format!("{{ {start} }}"),
+    };
+
+    let mut opts = vec![format!("range = {range}")];
+    if let Some(mods) = mods {
+        opts.push(format!("mods = {mods}"));
+    }
+
+    format!(
+        "NVIM9.cmd({}, {{ {} }})",
+        lua_string(contents.as_bytes()),
+        opts.join(", ")
+    )
+}
+
+/// The `mods` for `nvim_cmd`, for the modifiers that have one
+fn nvim_cmd_mods(modified: &ModifiedCommand) -> String {
+    let mods = modified.modifiers.iter().filter_map(|m| {
+        let value = match m.kind {
+            ModifierKind::Silent if m.bang => {
+                "silent = true, emsg_silent = true"
+            }
+            ModifierKind::Silent => "silent = true",
+            ModifierKind::Unsilent => "unsilent = true",
+            ModifierKind::Noautocmd => "noautocmd = true",
+            ModifierKind::Sandbox => "sandbox = true",
+            ModifierKind::Browse => "browse = true",
+            ModifierKind::Confirm => "confirm = true",
+            ModifierKind::Hide => "hide = true",
+            ModifierKind::Horizontal => "horizontal = true",
+            ModifierKind::Vertical => "vertical = true",
+            ModifierKind::Keepalt => "keepalt = true",
+            ModifierKind::Keepjumps => "keepjumps = true",
+            ModifierKind::Keepmarks => "keepmarks = true",
+            ModifierKind::Keeppatterns => "keeppatterns = true",
+            ModifierKind::Lockmarks => "lockmarks = true",
+            ModifierKind::Noswapfile => "noswapfile = true",
+            ModifierKind::Aboveleft => "split = 'aboveleft'",
+            ModifierKind::Belowright => "split = 'belowright'",
+            ModifierKind::Topleft => "split = 'topleft'",
+            ModifierKind::Botright => "split = 'botright'",
+            ModifierKind::Tab => "tab = vim.fn.tabpagenr()",
+            ModifierKind::Verbose => "verbose = 1",
+            // These only pick the syntax of the command, neovim only has one
+            ModifierKind::Legacy | ModifierKind::Vim9cmd => return None,
+        };
+
+        Some(value)
+    });
+
+    format!("{{ {} }}", mods.collect::<Vec<_>>().join(", "))
+}
+
+impl Generate for ModifiedCommand {
+    fn gen(&self, state: &mut State) -> String {
+        // `silent!` and `noautocmd` run the lua in a closure. Only the
+        // expression of `var` and `return` goes in one, so that the variable
+        // is declared and the value returned outside of it.
+        let wrap_expr = |expr: String| {
+            self.modifiers
+                .iter()
+                .rev()
+                .fold(expr, |expr, m| match m.kind {
+                    ModifierKind::Silent if m.bang => {
+                        format!("NVIM9.silent(function()\nreturn {expr}\nend)")
+                    }
+                    ModifierKind::Noautocmd => format!(
+                        "NVIM9.noautocmd(function()\nreturn {expr}\nend)"
+                    ),
+                    _ => expr,
+                })
+        };
+
+        // Commands we pass through to vim get all of their modifiers,
+        // the rest only get the ones that change how the lua runs.
+        let (command, passthrough) = match self.command.as_ref() {
+            ExCommand::SharedCommand(shared) => {
+                let command = match &shared.range {
+                    Some(range) => {
+                        nvim_cmd(shared, range, Some(nvim_cmd_mods(self)))
+                    }
+                    None => format!(
+                        "NVIM9.cmd({}, {{ mods = {} }})",
+                        lua_string(shared.contents.trim().as_bytes()),
+                        nvim_cmd_mods(self)
+                    ),
+                };
+
+                (command, true)
+            }
+            ExCommand::Var(var) => {
+                let expr = wrap_expr(var.expr.gen(state));
+                return gen_var(state, var, expr);
+            }
+            ExCommand::Return(ret) => {
+                let expr = ret.expr.as_ref().map(|e| wrap_expr(e.gen(state)));
+                return gen_return(state, expr);
+            }
+            // Definitions can not fail, and must stay visible to the rest of
+            // the script
+            command @ (ExCommand::Decl(_)
+            | ExCommand::Def(_)
+            | ExCommand::Function(_)
+            | ExCommand::Class(_)
+            | ExCommand::Interface(_)
+            | ExCommand::Enum(_)
+            | ExCommand::TypeAlias(_)
+            | ExCommand::ExportCommand(_)
+            | ExCommand::ImportCommand(_)
+            | ExCommand::Break(_)
+            | ExCommand::Continue(_)
+            | ExCommand::Finish(_)) => return command.gen(state),
+            command => (command.gen(state), false),
+        };
+
+        self.modifiers
+            .iter()
+            .rev()
+            .fold(command, |command, m| match m.kind {
+                ModifierKind::Silent if m.bang => {
+                    format!("NVIM9.silent(function()\n{command}\nend)")
+                }
+                ModifierKind::Noautocmd if !passthrough => {
+                    format!("NVIM9.noautocmd(function()\n{command}\nend)")
+                }
+                _ => command,
+            })
     }
 }
 
@@ -418,178 +983,799 @@ impl Generate for Heredoc {
         let inner = self
             .contents
             .iter()
-            .map(|line| format!("[==[{}]==]", line))
-            .collect::<Vec<String>>()
-            .join(", ");
+            .map(|line| format!("[==[{}]==]", line))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let mut list = format!("{{ {} }}", inner);
+        if self.trim {
+            list = format!("NVIM9.heredoc.trim({})", list)
+        }
+
+        format!("local {} = {}", self.name.gen(state), list)
+    }
+}
+
+impl Generate for CallCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let call: CallExpression = self.into();
+        format!("{};", call.gen(state))
+    }
+}
+
+impl Generate for AugroupCommand {
+    fn gen(&self, state: &mut State) -> String {
+        state.augroup = Some(self.augroup_name.clone());
+
+        // An `autocmd!` that comes before any autocmds are added clears the
+        // group, which is what creating it with `clear = true` does
+        let is_clear = |cmd: &ExCommand| {
+            matches!(cmd, ExCommand::Autocmd(autocmd) if autocmd.bang
+                && autocmd.group.is_none()
+                && autocmd.events.is_empty())
+        };
+
+        let mut body = self.body.clone();
+        let clear = match body.commands.iter().position(|cmd| {
+            is_clear(cmd) || matches!(cmd, ExCommand::Autocmd(_))
+        }) {
+            Some(idx) if is_clear(&body.commands[idx]) => {
+                body.commands.remove(idx);
+                true
+            }
+            _ => false,
+        };
+
+        let group = self.augroup_name.token.text.clone();
+        let result = format!(
+            r#"
+    vim.api.nvim_create_augroup("{}", {{ clear = {} }})
+
+    {}
+"#,
+            group,
+            clear,
+            body.gen(state)
+        );
+
+        state.augroup = None;
+
+        result
+    }
+}
+
+impl Generate for AugroupDeleteCommand {
+    fn gen(&self, _: &mut State) -> String {
+        format!(
+            "vim.api.nvim_del_augroup_by_name({})",
+            lua_string(self.augroup_name.token.text.as_bytes())
+        )
+    }
+}
+
+impl Generate for AutocmdCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let group = match (&self.group, &state.augroup) {
+            (Some(group), _) => Some(lua_string(group.as_bytes())),
+            (None, Some(group)) => {
+                Some(lua_string(group.token.text.as_bytes()))
+            }
+            (None, None) => None,
+        };
+
+        let events = self
+            .events
+            .iter()
+            .map(|e| lua_string(e.as_bytes()))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        // The options that pick the autocmds, shared by creating and clearing
+        let mut opts = vec![];
+        if let Some(group) = group {
+            opts.push(format!("group = {group},"));
+        }
+
+        match &self.pattern {
+            AutocmdPattern::Pattern(patterns) if !patterns.is_empty() => {
+                let patterns = patterns
+                    .iter()
+                    .map(|p| lua_string(p.as_bytes()))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                opts.push(format!("pattern = {{ {patterns} }},"));
+            }
+            AutocmdPattern::Pattern(_) => {}
+            AutocmdPattern::Buffer(buffer) => {
+                let buffer = match buffer {
+                    AutocmdBuffer::Current => "0".to_string(),
+                    AutocmdBuffer::Number(number) => number.to_string(),
+                    AutocmdBuffer::Abuf => {
+                        "tonumber(vim.fn.expand('<abuf>'))".to_string()
+                    }
+                };
+
+                opts.push(format!("buffer = {buffer},"));
+            }
+        }
+
+        // `autocmd!` removes the autocmds that it would otherwise add to
+        let clear = match self.bang {
+            true => {
+                let mut clear_opts = opts.clone();
+                if !self.events.is_empty() && self.events != ["*"] {
+                    clear_opts.push(format!("event = {{ {events} }},"));
+                }
+
+                format!(
+                    "vim.api.nvim_clear_autocmds({{\n{}\n}})\n",
+                    clear_opts.join("\n")
+                )
+            }
+            false => "".to_string(),
+        };
+
+        let callback = match &self.block {
+            Some(AutocmdBlock::Command(cmd)) => {
+                format!("function()\n{}\nend", cmd.gen(state))
+            }
+            Some(AutocmdBlock::Block(block)) => {
+                format!("function()\n{}\nend", block.body.gen(state))
+            }
+            None => return clear,
+        };
+
+        if self.once {
+            opts.push("once = true,".to_string());
+        }
+
+        if self.nested {
+            opts.push("nested = true,".to_string());
+        }
+
+        format!(
+            r#"
+{}vim.api.nvim_create_autocmd({{ {} }}, {{
+    {}
+    callback = {},
+}})
+"#,
+            clear,
+            events,
+            opts.join("\n"),
+            callback
+        )
+    }
+}
+
+impl Generate for ReturnCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let expr = self.expr.as_ref().map(|expr| expr.gen(state));
+        gen_return(state, expr)
+    }
+}
+
+/// Returns `expr` from the current function, through any loop or try
+/// bodies that it is generated in
+fn gen_return(state: &State, expr: Option<String>) -> String {
+    // Loops without a continue are generated inline, so a return in one of
+    // them can still be inside of a closure further out
+    let in_closure = state
+        .scopes
+        .iter()
+        .rev()
+        .take_while(|scope| scope.kind != ScopeKind::Function)
+        .any(Scope::has_continue);
+
+    if in_closure {
+        match expr {
+            Some(expr) => format!("return NVIM9.ITER_RETURN, {expr}"),
+            None => "return NVIM9.ITER_RETURN".to_string(),
+        }
+    } else {
+        match expr {
+            Some(expr) => format!("return {expr}"),
+            None => "return".to_string(),
+        }
+    }
+}
+
+impl Generate for DefCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let name = self.name.gen(state);
+        let (body, scope) =
+            state.with_scope(ScopeKind::Function, |s| self.body.gen(s));
+
+        if state.is_test && name.starts_with("Test") {
+            assert!(scope.deferred == 0, "have not handled deferred in tests");
+
+            format!(
+                r#"
+                    it("{name}", function()
+                       -- Set errors to empty
+                       vim.v.errors = {{}}
+
+                       -- Actual test
+                       {body}
+
+                       -- Assert that errors is still empty
+                       assert.are.same({{}}, vim.v.errors)
+                    end)
+                "#,
+            )
+        } else {
+            let (signature, default_statements) =
+                gen_signature(state, &self.args);
+
+            let local = if state.is_top_level() || !self.name.is_valid_local() {
+                ""
+            } else {
+                "local"
+            };
+
+            let body = gen_deferred(body, &scope);
+
+            // TODO: If this command follows certain patterns,
+            // we will also need to define a vimscript function,
+            // so that this function is available.
+            //
+            // this could be something just like:
+            // function <NAME>(...)
+            //   return luaeval('...', ...)
+            // endfunction
+            //
+            // but we haven't done this part yet.
+            // This is a "must-have" aspect of what we're doing.
+            format!(
+                r#"
+                {local} {name} = function({signature})
+                    {default_statements}
+                    {body}
+                end
+            "#,
+            )
+        }
+    }
+}
+
+/// Wraps the body of a function that used `defer`, so that the deferred
+/// functions run after the body returns
+fn gen_deferred(body: String, scope: &Scope) -> String {
+    if scope.deferred == 0 {
+        return body;
+    }
+
+    // TODO: Should probably handle errors in default statements or body
+    format!(
+        r#"
+        local nvim9_deferred = {{}}
+        local _, ret = pcall(function()
+            {body}
+        end)
+
+        for _, nvim9_defer in ipairs(nvim9_deferred) do
+            pcall(nvim9_defer)
+        end
+
+        return ret
+        "#
+    )
+}
+
+impl Generate for FunctionCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let name = self.name.gen(state);
+
+        // Function variables are visible in the whole function, so they are
+        // declared up front instead of where they are first assigned
+        let mut locals = vec![];
+        legacy_locals(&self.body, |let_| let_locals(let_, false), &mut locals);
+        locals.retain(|local| local != "self");
+
+        let outer = state.legacy_locals.replace(locals.clone());
+        let (body, scope) =
+            state.with_scope(ScopeKind::Function, |s| self.body.gen(s));
+        let body = gen_deferred(body, &scope);
+
+        let (mut signature, default_statements) =
+            gen_signature(state, &legacy_arguments(&self.args));
+        state.legacy_locals = outer;
+        if self.is_dict() {
+            signature = match signature.is_empty() {
+                true => "self".to_string(),
+                false => format!("self, {signature}"),
+            };
+        }
+
+        let mut preamble = vec![];
+        if self.has_varargs() {
+            preamble.push("local nvim9_varargs = { ... }".to_string());
+        }
+
+        // Without a range, both are the line of the cursor
+        if self.attributes.range {
+            preamble.push(
+                "local a_firstline, a_lastline = vim.fn.line('.'), vim.fn.line('.')"
+                    .to_string(),
+            );
+        }
+
+        if !locals.is_empty() {
+            let locals = locals
+                .iter()
+                .map(|local| format!("l_{local}"))
+                .collect::<Vec<_>>();
+            preamble.push(format!("local {}", locals.join(", ")));
+        }
+
+        let preamble = preamble.join("\n");
+        let func = format!(
+            r#"
+            function({signature})
+                {preamble}
+                {default_statements}
+                {body}
+            end
+            "#
+        );
+
+        let func = match self.is_dict() {
+            true => format!("NVIM9.dict_function({func})"),
+            false => func,
+        };
+
+        let local = match &self.name {
+            Expression::Identifier(name)
+                if !state.is_top_level() && is_legacy_local(name) =>
+            {
+                "local"
+            }
+            _ => "",
+        };
+
+        format!("{local} {name} = {func}")
+    }
+}
+
+impl Generate for LetCommand {
+    fn gen(&self, state: &mut State) -> String {
+        match (&self.left, &self.operator.kind) {
+            (
+                Expression::Identifier(Identifier::Unpacked(unpacked)),
+                lexer::TokenKind::Equal,
+            ) => {
+                let identifiers = identifier_list(state, unpacked);
+                format!("{identifiers} = unpack({})", self.right.gen(state))
+            }
+            (_, lexer::TokenKind::Equal) => {
+                gen_assign(state, &self.left, &self.right)
+            }
+            (_, modifier) => {
+                gen_mutation(state, &self.left, modifier, &self.right)
+            }
+        }
+    }
+}
+
+/// `args` with the names that the arguments of a legacy function have in
+/// lua, which is where `a:name` refers to
+fn legacy_arguments(args: &Signature) -> Signature {
+    let mut args = args.clone();
+    for param in &mut args.params {
+        if let Identifier::Raw(raw) = &param.name {
+            param.name = Identifier::Raw(RawIdentifier {
+                name: format!("a_{}", raw.name),
+            });
+        }
+    }
+
+    args
+}
+
+/// Whether a legacy function or variable named `name` is a lua local,
+/// instead of being stored in `vim.g` and the like
+fn is_legacy_local(name: &Identifier) -> bool {
+    match name {
+        Identifier::Raw(_) => true,
+        Identifier::Scope(scoped) => {
+            matches!(scoped.scope, VimScope::Script | VimScope::Local)
+        }
+        _ => false,
+    }
+}
+
+/// The variables that `let_` declares, which are the ones it assigns with `=`
+fn let_targets(let_: &LetCommand) -> Vec<&Identifier> {
+    if let_.operator.kind != lexer::TokenKind::Equal {
+        return vec![];
+    }
+
+    match &let_.left {
+        Expression::Identifier(Identifier::Unpacked(unpacked)) => {
+            unpacked.identifiers.iter().collect()
+        }
+        Expression::Identifier(ident) => vec![ident],
+        _ => vec![],
+    }
+}
+
+/// The lua locals that `let_` assigns to. Script variables are only
+/// included at the top level, inside of a function they are upvalues.
+fn let_locals(let_: &LetCommand, top_level: bool) -> Vec<String> {
+    let_targets(let_)
+        .into_iter()
+        .filter_map(|name| match name {
+            Identifier::Raw(raw) => Some(raw.name.clone()),
+            Identifier::Scope(scoped)
+                if scoped.scope == VimScope::Local
+                    || (top_level && scoped.scope == VimScope::Script) =>
+            {
+                match scoped.accessor.as_ref() {
+                    Identifier::Raw(raw) => Some(raw.name.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The script variables that `let_` assigns to
+fn script_locals(let_: &LetCommand) -> Vec<String> {
+    let_targets(let_)
+        .into_iter()
+        .filter_map(|name| match name {
+            Identifier::Scope(scoped) if scoped.scope == VimScope::Script => {
+                match scoped.accessor.as_ref() {
+                    Identifier::Raw(raw) => Some(raw.name.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collects the variables that are assigned with `let` in `body`,
+/// `names` picks the ones of a `let`
+fn legacy_locals<F>(body: &Body, names: F, locals: &mut Vec<String>)
+where
+    F: Fn(&LetCommand) -> Vec<String> + Copy,
+{
+    for command in &body.commands {
+        match command {
+            ExCommand::Let(let_) => {
+                for name in names(let_) {
+                    if !locals.contains(&name) {
+                        locals.push(name);
+                    }
+                }
+            }
+            ExCommand::If(if_) => {
+                legacy_locals(&if_.body, names, locals);
+                for elseif in &if_.elseifs {
+                    legacy_locals(&elseif.body, names, locals);
+                }
+
+                if let Some(else_) = &if_.else_command {
+                    legacy_locals(&else_.body, names, locals);
+                }
+            }
+            ExCommand::For(for_) => legacy_locals(&for_.body, names, locals),
+            ExCommand::While(while_) => {
+                legacy_locals(&while_.body, names, locals)
+            }
+            ExCommand::Try(try_) => {
+                legacy_locals(&try_.body, names, locals);
+                for catch in &try_.catches {
+                    legacy_locals(&catch.body, names, locals);
+                }
+
+                if let Some(finally) = &try_.finally {
+                    legacy_locals(&finally.body, names, locals);
+                }
+            }
+            ExCommand::Modified(modified) => legacy_locals(
+                &Body {
+                    commands: vec![*modified.command.clone()],
+                },
+                names,
+                locals,
+            ),
+            _ => {}
+        }
+    }
+}
+
+/// The members of a class or enum, as the fields of the table that is
+/// passed to `NVIM9.class`
+#[derive(Default)]
+struct ClassFields {
+    vars: Vec<String>,
+    init: Vec<String>,
+    methods: Vec<String>,
+    abstract_methods: Vec<String>,
+    constructors: Vec<String>,
+    statics: Vec<String>,
+}
+
+impl ClassFields {
+    /// `parent` is the name of the class when it extends another class,
+    /// so that its methods can call `super`
+    fn new(
+        state: &mut State,
+        class: &str,
+        parent: Option<&str>,
+        members: &[ClassMember],
+    ) -> Self {
+        let statics = members
+            .iter()
+            .filter_map(|member| match member {
+                ClassMember::Var(var) if var.is_static => {
+                    Some(var.name.gen(state))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // Static variables are initialized before the class exists, so
+        // only the methods use the class to access them
+        let gen_class_method =
+            |state: &mut State,
+             def: &DefCommand,
+             this: bool,
+             parent: Option<&str>| {
+                let outer = state
+                    .class_statics
+                    .replace((class.to_string(), statics.clone()));
+                let func = gen_method(state, def, this, parent);
+                state.class_statics = outer;
+                func
+            };
 
-        let mut list = format!("{{ {} }}", inner);
-        if self.trim {
-            list = format!("NVIM9.heredoc.trim({})", list)
+        let mut fields = ClassFields::default();
+        for member in members {
+            match member {
+                ClassMember::Var(var) => {
+                    let name = var.name.gen(state);
+                    let value = match &var.expr {
+                        Some(expr) => expr.gen(state),
+                        None => type_default(var.ty.as_ref()),
+                    };
+
+                    if var.is_static {
+                        fields.statics.push(format!("{name} = {value},"));
+                    } else {
+                        fields.vars.push(format!("\"{name}\","));
+                        fields.init.push(format!("this.{name} = {value}"));
+                    }
+                }
+                ClassMember::Method(method) if method.is_static => {
+                    let name = method.def.name.gen(state);
+                    let func =
+                        gen_class_method(state, &method.def, false, None);
+                    fields.statics.push(format!("{name} = {func},"));
+                }
+                ClassMember::Method(method) => {
+                    let name = method.def.name.gen(state);
+                    let func =
+                        gen_class_method(state, &method.def, true, parent);
+                    if name.starts_with("new") {
+                        fields.constructors.push(format!("{name} = {func},"));
+                    } else {
+                        fields.methods.push(format!("{name} = {func},"));
+                    }
+                }
+                ClassMember::Abstract(signature) => {
+                    let name = signature.name.gen(state);
+                    fields.abstract_methods.push(format!("\"{name}\","));
+                }
+            }
         }
 
-        format!("local {} = {}", self.name.gen(state), list)
+        fields
     }
-}
 
-impl Generate for CallCommand {
-    fn gen(&self, state: &mut State) -> String {
-        let call: CallExpression = self.into();
-        format!("{};", call.gen(state))
-    }
-}
+    /// The fields that are not empty, one per line
+    fn gen(&self) -> String {
+        let mut lines = vec![];
+        let mut push = |field: &str, items: &[String], sep: &str| {
+            if !items.is_empty() {
+                lines.push(format!("{field} = {{ {} }},", items.join(sep)));
+            }
+        };
 
-impl Generate for AugroupCommand {
-    fn gen(&self, state: &mut State) -> String {
-        state.augroup = Some(self.augroup_name.clone());
+        push("vars", &self.vars, " ");
+        push("methods", &self.methods, "\n");
+        push("abstract_methods", &self.abstract_methods, " ");
+        push("constructors", &self.constructors, "\n");
+        push("static", &self.statics, "\n");
 
-        let group = self.augroup_name.token.text.clone();
-        let result = format!(
-            r#"
-    vim.api.nvim_create_augroup("{}", {{ clear = false }})
+        if !self.init.is_empty() {
+            lines.insert(
+                1.min(lines.len()),
+                format!("init = function(this) {} end,", self.init.join("\n")),
+            );
+        }
 
-    {}
-"#,
-            group,
-            self.body.gen(state)
+        lines.join("\n")
+    }
+}
+
+/// The value of an object variable that is declared without one
+fn type_default(ty: Option<&Type>) -> String {
+    match ty.map(|ty| &ty.inner) {
+        Some(InnerType::Number) => "0",
+        Some(InnerType::Float) => "0.0",
+        Some(InnerType::String) => "\"\"",
+        Some(InnerType::Bool) => "false",
+        Some(InnerType::List { .. }) | Some(InnerType::Tuple { .. }) => "{}",
+        Some(InnerType::Dict { .. }) => "vim.empty_dict()",
+        _ => "nil",
+    }
+    .to_string()
+}
+
+/// Generates a method as an anonymous function. Object methods and
+/// constructors take `this` first and assign their `this.name` params.
+fn gen_method(
+    state: &mut State,
+    def: &DefCommand,
+    this: bool,
+    parent: Option<&str>,
+) -> String {
+    let (body, scope) =
+        state.with_scope(ScopeKind::Function, |s| def.body.gen(s));
+    let body = gen_deferred(body, &scope);
+    let (signature, default_statements) = gen_signature(state, &def.args);
+    if !this {
+        return format!(
+            r#"function({signature})
+                {default_statements}
+                {body}
+            end"#
         );
+    }
 
-        state.augroup = None;
+    let signature = match signature.is_empty() {
+        true => "this".to_string(),
+        false => format!("this, {signature}"),
+    };
 
-        result
+    let mut prelude = def
+        .args
+        .params
+        .iter()
+        .filter(|p| p.is_member())
+        .map(|p| {
+            let name = p.name.gen(state);
+            format!("this.{name} = {name}")
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(parent) = parent {
+        if body.contains("super[") || body.contains("dict_call(super,") {
+            prelude.insert(
+                0,
+                format!("local super = NVIM9.class.super({parent}, this)"),
+            );
+        }
     }
+
+    format!(
+        r#"function({signature})
+            {}
+            {default_statements}
+            {body}
+        end"#,
+        prelude.join("\n")
+    )
 }
 
-impl Generate for AutocmdCommand {
-    fn gen(&self, state: &mut State) -> String {
-        let group = match &state.augroup {
-            Some(group) => format!("group = '{}',", group.token.text),
-            None => "".to_string(),
-        };
+/// The names of the interfaces or classes in `implements` or `extends`
+fn gen_names(state: &mut State, names: &[Identifier]) -> String {
+    names
+        .iter()
+        .map(|name| name.gen(state))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-        let event_list = self
-            .events
-            .iter()
-            .map(|e| format!("'{}'", e.token.text))
-            .collect::<Vec<String>>()
-            .join(", ");
+impl Generate for ClassCommand {
+    fn gen(&self, state: &mut State) -> String {
+        let name = self.name.gen(state);
+        let mut header = vec![
+            format!("name = \"{name}\","),
+            format!("abstract = {},", self.is_abstract()),
+        ];
+        if let Some(extends) = &self.extends {
+            header.push(format!("extends = {},", extends.gen(state)));
+        }
+        if !self.implements.is_empty() {
+            let implements = gen_names(state, &self.implements);
+            header.push(format!("implements = {{ {implements} }},"));
+        }
 
-        let callback = match &self.block {
-            parser::AutocmdBlock::Command(cmd) => {
-                format!("function()\n{}\nend", cmd.gen(state))
-            }
-            parser::AutocmdBlock::Block(block) => {
-                format!("function()\n{}\nend", block.body.gen(state))
-            }
-        };
+        let parent = self.extends.as_ref().map(|_| name.as_str());
+        let fields = ClassFields::new(state, &name, parent, &self.members);
 
         format!(
             r#"
-vim.api.nvim_create_autocmd({{ {} }}, {{
-    {}
-    callback = {},
-}})
-"#,
-            event_list, group, callback
+            {name} = NVIM9.class.new({{
+                {}
+                {}
+            }})
+            "#,
+            header.join("\n"),
+            fields.gen()
         )
     }
 }
 
-impl Generate for ReturnCommand {
+impl Generate for InterfaceCommand {
     fn gen(&self, state: &mut State) -> String {
-        let scope =
-            scope_or_empty!(state, Function | While { .. } | For { .. });
-
-        if scope.has_continue() {
-            match &self.expr {
-                Some(expr) => {
-                    format!("return NVIM9.ITER_RETURN, {}", expr.gen(state))
+        let name = self.name.gen(state);
+        let extends = gen_names(state, &self.extends);
+
+        let mut vars = vec![];
+        let mut methods = vec![];
+        for member in &self.members {
+            match member {
+                ClassMember::Var(var) => {
+                    vars.push(format!("\"{}\"", var.name.gen(state)))
+                }
+                ClassMember::Abstract(signature) => {
+                    methods.push(format!("\"{}\"", signature.name.gen(state)))
+                }
+                ClassMember::Method(_) => {
+                    unreachable!("interface methods do not have a body")
                 }
-                None => "return NVIM9.ITER_RETURN".to_string(),
-            }
-        } else {
-            match &self.expr {
-                Some(expr) => format!("return {}", expr.gen(state)),
-                None => "return".to_string(),
             }
         }
+
+        format!(
+            r#"
+            {name} = NVIM9.class.interface({{
+                name = "{name}",
+                extends = {{ {extends} }},
+                vars = {{ {} }},
+                methods = {{ {} }},
+            }})
+            "#,
+            vars.join(", "),
+            methods.join(", "),
+        )
     }
 }
 
-impl Generate for DefCommand {
+impl Generate for EnumCommand {
     fn gen(&self, state: &mut State) -> String {
         let name = self.name.gen(state);
-        let (body, scope) =
-            state.with_scope(ScopeKind::Function, |s| self.body.gen(s));
-
-        if state.is_test && name.starts_with("Test") {
-            assert!(scope.deferred == 0, "have not handled deferred in tests");
-
-            format!(
-                r#"
-                    it("{name}", function()
-                       -- Set errors to empty
-                       vim.v.errors = {{}}
-
-                       -- Actual test
-                       {body}
-
-                       -- Assert that errors is still empty
-                       assert.are.same({{}}, vim.v.errors)
-                    end)
-                "#,
-            )
-        } else {
-            let (signature, default_statements) =
-                gen_signature(state, &self.args);
-
-            let local = if state.is_top_level() || !self.name.is_valid_local() {
-                ""
-            } else {
-                "local"
-            };
-
-            if scope.deferred > 0 {
-                // TODO: Should probably handle errors in default statements or body
-                format!(
-                    r#"
-                    {local} {name} = function({signature})
-                        local nvim9_deferred = {{}}
-
-                        {default_statements}
-                        local _, ret = pcall(function()
-                            {body}
-                        end)
-
-                        for _, nvim9_defer in ipairs(nvim9_deferred) do
-                            pcall(nvim9_defer)
-                        end
+        let implements = gen_names(state, &self.implements);
+        let values = self
+            .values
+            .iter()
+            .map(|value| {
+                let mut items = vec![format!("\"{}\"", value.name.gen(state))];
+                items.extend(value.args.iter().map(|arg| arg.gen(state)));
+                format!("{{ {} }}", items.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let fields = ClassFields::new(state, &name, None, &self.members);
 
-                        return ret
-                    end
-                    "#
-                )
-            } else {
-                // TODO: If this command follows certain patterns,
-                // we will also need to define a vimscript function,
-                // so that this function is available.
-                //
-                // this could be something just like:
-                // function <NAME>(...)
-                //   return luaeval('...', ...)
-                // endfunction
-                //
-                // but we haven't done this part yet.
-                // This is a "must-have" aspect of what we're doing.
-                format!(
-                    r#"
-                    {local} {name} = function({signature})
-                        {default_statements}
-                        {body}
-                    end
-                "#,
-                )
-            }
-        }
+        format!(
+            r#"
+            {name} = NVIM9.class.enum({{
+                name = "{name}",
+                implements = {{ {implements} }},
+                values = {{ {values} }},
+                {}
+            }})
+            "#,
+            fields.gen()
+        )
     }
 }
 
@@ -627,70 +1813,84 @@ impl Generate for StatementCommand {
 impl Generate for MutationStatement {
     fn gen(&self, state: &mut State) -> String {
         // format!("--[[ {:#?} ]]", self)
-        let left = self.left.gen(state);
-        let operator = match self.modifier.kind {
-            lexer::TokenKind::PlusEquals => parser::Operator::Plus,
-            lexer::TokenKind::MinusEquals => parser::Operator::Minus,
-            lexer::TokenKind::MulEquals => parser::Operator::Multiply,
-            lexer::TokenKind::DivEquals => parser::Operator::Divide,
-            lexer::TokenKind::PercentEquals => parser::Operator::Modulo,
-            lexer::TokenKind::StringConcatEquals => {
-                parser::Operator::StringConcat
-            }
-            _ => unreachable!(),
-        };
+        gen_mutation(state, &self.left, &self.modifier.kind, &self.right)
+    }
+}
+
+fn gen_mutation(
+    state: &mut State,
+    left: &Expression,
+    modifier: &lexer::TokenKind,
+    right: &Expression,
+) -> String {
+    let operator = match modifier {
+        lexer::TokenKind::PlusEquals => parser::Operator::Plus,
+        lexer::TokenKind::MinusEquals => parser::Operator::Minus,
+        lexer::TokenKind::MulEquals => parser::Operator::Multiply,
+        lexer::TokenKind::DivEquals => parser::Operator::Divide,
+        lexer::TokenKind::PercentEquals => parser::Operator::Modulo,
+        lexer::TokenKind::StringConcatEquals => parser::Operator::StringConcat,
+        _ => unreachable!(),
+    };
 
-        // TODO(clone)
-        let infix = InfixExpression::new(
-            operator,
-            self.left.clone().into(),
-            self.right.clone().into(),
-        )
-        .gen(state);
+    // TODO(clone)
+    let infix = InfixExpression::new(
+        operator,
+        left.clone().into(),
+        right.clone().into(),
+    )
+    .gen(state);
 
-        format!("{left} = {infix}")
-    }
+    format!("{} = {infix}", left.gen(state))
 }
 
 impl Generate for AssignStatement {
     fn gen(&self, state: &mut State) -> String {
-        let left = match &self.left {
-            Expression::Index(idx) => {
-                format!(
-                    "{}[{} + 1]",
-                    idx.container.gen(state),
-                    match idx.index.as_ref() {
-                        IndexType::Item(item) => item.gen(state),
-                        IndexType::Slice(_) => todo!("Unknown index type"),
-                    }
-                )
-            }
-            // Expression::Empty => todo!(),
-            // Expression::Identifier(_) => todo!(),
-            // Expression::Number(_) => todo!(),
-            // Expression::String(_) => todo!(),
-            // Expression::Boolean(_) => todo!(),
-            // Expression::Grouped(_) => todo!(),
-            // Expression::Call(_) => todo!(),
-            // Expression::Slice(_) => todo!(),
-            // Expression::Array(_) => todo!(),
-            // Expression::Dict(_) => todo!(),
-            // Expression::DictAccess(_) => todo!(),
-            // Expression::VimOption(_) => todo!(),
-            // Expression::Register(_) => todo!(),
-            // Expression::Lambda(_) => todo!(),
-            // Expression::Expandable(_) => todo!(),
-            // Expression::MethodCall(_) => todo!(),
-            // Expression::Ternary(_) => todo!(),
-            // Expression::Prefix(_) => todo!(),
-            // Expression::Infix(_) => todo!(),
-            _ => self.left.gen(state),
-        };
+        gen_assign(state, &self.left, &self.right)
+    }
+}
+
+fn gen_assign(
+    state: &mut State,
+    left: &Expression,
+    right: &Expression,
+) -> String {
+    let left = match left {
+        Expression::Index(idx) => {
+            format!(
+                "{}[{} + 1]",
+                idx.container.gen(state),
+                match idx.index.as_ref() {
+                    IndexType::Item(item) => item.gen(state),
+                    IndexType::Slice(_) => todo!("Unknown index type"),
+                }
+            )
+        }
+        // Expression::Empty => todo!(),
+        // Expression::Identifier(_) => todo!(),
+        // Expression::Number(_) => todo!(),
+        // Expression::String(_) => todo!(),
+        // Expression::Boolean(_) => todo!(),
+        // Expression::Grouped(_) => todo!(),
+        // Expression::Call(_) => todo!(),
+        // Expression::Slice(_) => todo!(),
+        // Expression::Array(_) => todo!(),
+        // Expression::Dict(_) => todo!(),
+        // Expression::DictAccess(_) => todo!(),
+        // Expression::VimOption(_) => todo!(),
+        // Expression::Register(_) => todo!(),
+        // Expression::Lambda(_) => todo!(),
+        // Expression::Expandable(_) => todo!(),
+        // Expression::MethodCall(_) => todo!(),
+        // Expression::Ternary(_) => todo!(),
+        // Expression::Prefix(_) => todo!(),
+        // Expression::Infix(_) => todo!(),
+        _ => left.gen(state),
+    };
 
-        let right = self.right.gen(state);
+    let right = right.gen(state);
 
-        format!("{left} = {right}")
-    }
+    format!("{left} = {right}")
 }
 
 impl Generate for IfCommand {
@@ -742,14 +1942,51 @@ impl Generate for ElseCommand {
 
 impl Generate for Body {
     fn gen(&self, state: &mut State) -> String {
+        // Errors from vim do not know which command raised them, so the
+        // commands in a try tell the runtime
+        let in_try = state
+            .find_relevant_scope(|s| {
+                matches!(s.kind, ScopeKind::Function | ScopeKind::Try)
+            })
+            .map_or(false, |s| s.kind == ScopeKind::Try);
+
         self.commands
             .iter()
-            .map(|cmd| cmd.gen(state))
+            .map(|cmd| match try_command_name(cmd) {
+                Some(name) if in_try => {
+                    format!("NVIM9.try_at(\"{name}\")\n{}", cmd.gen(state))
+                }
+                _ => cmd.gen(state),
+            })
             .collect::<Vec<String>>()
             .join("\n")
     }
 }
 
+/// The name of the command in `Vim(name):E123: ...`, the form of the
+/// exceptions that vim raises for errors in `command`
+fn try_command_name(command: &ExCommand) -> Option<&'static str> {
+    Some(match command {
+        ExCommand::Var(_) => "var",
+        ExCommand::Let(_) => "let",
+        // Assignments are `:var` commands without the `var`
+        ExCommand::Statement(_) => "var",
+        ExCommand::Echo(_) => "echo",
+        ExCommand::Return(_) => "return",
+        ExCommand::Call(call) if call.has_call() => "call",
+        // vim9script runs `Func()` as an expression, like `:eval`
+        ExCommand::Call(_) | ExCommand::Eval(_) => "eval",
+        ExCommand::Unlet(_) => "unlet",
+        ExCommand::If(_) => "if",
+        ExCommand::For(_) => "for",
+        ExCommand::While(_) => "while",
+        ExCommand::Modified(modified) => {
+            return try_command_name(&modified.command)
+        }
+        _ => return None,
+    })
+}
+
 impl Generate for EchoCommand {
     fn gen(&self, state: &mut State) -> String {
         // TODO: Probably should add some function that
@@ -764,6 +2001,12 @@ impl Generate for EchoCommand {
     }
 }
 
+impl Generate for EchoErrCommand {
+    fn gen(&self, state: &mut State) -> String {
+        format!("NVIM9.echoerr({})", self.expr.gen(state))
+    }
+}
+
 impl Generate for ExecuteCommand {
     fn gen(&self, state: &mut State) -> String {
         format!("vim.api.nvim_command({})", self.expr.gen(state))
@@ -789,28 +2032,34 @@ fn identifier_list(state: &mut State, unpacked: &UnpackIdentifier) -> String {
 
 impl Generate for VarCommand {
     fn gen(&self, state: &mut State) -> String {
-        let expr = match self.ty {
-            Some(Type {
-                inner: InnerType::Bool,
-                ..
-            }) => format!("NVIM9.convert.decl_bool({})", self.expr.gen(state)),
-            _ => self.expr.gen(state),
-        };
+        let expr = self.expr.gen(state);
+        gen_var(state, self, expr)
+    }
+}
 
-        match &self.name {
-            Identifier::Unpacked(unpacked) => {
-                let identifiers: String = identifier_list(state, &unpacked);
-                format!("local {identifiers} = unpack({expr})")
-            }
-            _ => format!("local {} = {}", self.name.gen(state), expr),
+/// Declares the variable of `var`, with `expr` as the generated value
+fn gen_var(state: &mut State, var: &VarCommand, expr: String) -> String {
+    let expr = match var.ty {
+        Some(Type {
+            inner: InnerType::Bool,
+            ..
+        }) => format!("NVIM9.convert.decl_bool({})", expr),
+        _ => expr,
+    };
+
+    match &var.name {
+        Identifier::Unpacked(unpacked) => {
+            let identifiers: String = identifier_list(state, &unpacked);
+            format!("local {identifiers} = unpack({expr})")
         }
+        _ => format!("local {} = {}", var.name.gen(state), expr),
     }
 }
 
 impl Generate for Identifier {
     fn gen(&self, state: &mut State) -> String {
         match self {
-            Identifier::Raw(raw) => raw.gen(state),
+            Identifier::Raw(raw) => gen_raw_identifier(state, raw),
             Identifier::Scope(scoped) => scoped.gen(state),
             Identifier::Unpacked(_) => {
                 unreachable!("must be handled higher {:#?}", self)
@@ -820,6 +2069,14 @@ impl Generate for Identifier {
     }
 }
 
+/// `raw`, which is `l_name` for the variables of a legacy function
+fn gen_raw_identifier(state: &mut State, raw: &RawIdentifier) -> String {
+    match &state.legacy_locals {
+        Some(locals) if locals.contains(&raw.name) => format!("l_{}", raw.name),
+        _ => raw.gen(state),
+    }
+}
+
 impl Generate for ScopedIdentifier {
     fn gen(&self, state: &mut State) -> String {
         let scope = match self.scope {
@@ -828,11 +2085,39 @@ impl Generate for ScopedIdentifier {
             VimScope::Tab => "vim.t",
             VimScope::Window => "vim.w",
             VimScope::Buffer => "vim.b",
-            VimScope::Script => todo!("Not sure how to handle scriptvar"),
-            VimScope::Local => todo!("Not sure how to handle local"),
+            // Script and function variables are lua locals
+            VimScope::Script => return gen_accessor(state, &self.accessor),
+            VimScope::Local => {
+                return format!("l_{}", gen_accessor(state, &self.accessor))
+            }
+            VimScope::Argument => return gen_argument(state, &self.accessor),
         };
 
-        format!("{}['{}']", scope, self.accessor.gen(state))
+        format!("{}['{}']", scope, gen_accessor(state, &self.accessor))
+    }
+}
+
+/// The name after the scope of a scoped identifier, which is never one of
+/// the renamed variables of a legacy function
+fn gen_accessor(state: &mut State, accessor: &Identifier) -> String {
+    match accessor {
+        Identifier::Raw(raw) => raw.gen(state),
+        accessor => accessor.gen(state),
+    }
+}
+
+/// `a:name` is the argument itself, and `a:0`, `a:1`, ... and `a:000`
+/// refer to the list of the arguments passed for `...`
+fn gen_argument(state: &mut State, accessor: &Identifier) -> String {
+    let name = gen_accessor(state, accessor);
+    if !name.chars().all(|ch| ch.is_ascii_digit()) {
+        return format!("a_{name}");
+    }
+
+    match name.parse::<usize>().unwrap() {
+        0 if name.len() == 3 => "nvim9_varargs".to_string(),
+        0 => "#nvim9_varargs".to_string(),
+        idx => format!("nvim9_varargs[{idx}]"),
     }
 }
 
@@ -845,6 +2130,14 @@ impl Generate for RawIdentifier {
 impl Generate for Expression {
     fn gen(&self, state: &mut State) -> String {
         match self {
+            Expression::Identifier(Identifier::Raw(raw)) => {
+                match &state.class_statics {
+                    Some((class, statics)) if statics.contains(&raw.name) => {
+                        format!("{class}[\"{}\"]", raw.name)
+                    }
+                    _ => gen_raw_identifier(state, raw),
+                }
+            }
             Expression::Identifier(identifier) => identifier.gen(state),
             Expression::Number(num) => num.gen(state),
             Expression::String(str) => str.gen(state),
@@ -980,14 +2273,15 @@ impl Generate for Expandable {
 impl Generate for Lambda {
     fn gen(&self, state: &mut State) -> String {
         let (signature, default_statements) = gen_signature(state, &self.args);
+        let (body, scope) =
+            state.with_scope(ScopeKind::Function, |s| self.body.gen(s));
+        let body = gen_deferred(body, &scope);
+
         format!(
-            r#"(function({})
-                {}
-                {}
+            r#"(function({signature})
+                {default_statements}
+                {body}
                 end)"#,
-            signature,
-            default_statements,
-            self.body.gen(state)
         )
     }
 }
@@ -1095,7 +2389,7 @@ impl Generate for ArrayLiteral {
 }
 
 impl Generate for VimString {
-    fn gen(&self, _: &mut State) -> String {
+    fn gen(&self, state: &mut State) -> String {
         match self {
             VimString::SingleQuote(s) => format!(
                 "'{}'",
@@ -1107,26 +2401,8 @@ impl Generate for VimString {
                     })
                     .collect::<String>()
             ),
-            VimString::DoubleQuote(s) => {
-                let mut cs = s.chars();
-                while let Some(ch) = cs.next() {
-                    if ch == '\\' {
-                        if let Some(ch) = cs.next() {
-                            if ch == '<' {
-                                return format!("vim.api.nvim_replace_termcodes(\"{}\", true, true, true)", s);
-                            }
-                        }
-                    }
-                }
-
-                format!("\"{}\"", s)
-            }
-            VimString::Interpolated(interp) => {
-                format!("string.format('{}')", interp)
-            }
-            VimString::InterpolatedLit(interp) => {
-                format!("string.format('{}')", interp)
-            }
+            VimString::DoubleQuote(s) => gen_string_bytes(s),
+            VimString::Interpolated(interp) => interp.gen(state),
             VimString::EnvironmentVariable(env) => {
                 format!("vim.env['{}']", env)
             }
@@ -1134,6 +2410,82 @@ impl Generate for VimString {
     }
 }
 
+impl Generate for InterpolatedString {
+    fn gen(&self, state: &mut State) -> String {
+        if self.parts.is_empty() {
+            return "\"\"".to_string();
+        }
+
+        self.parts
+            .iter()
+            .map(|part| match part {
+                InterpolatedPart::Literal(s) => gen_string_bytes(s),
+                InterpolatedPart::Expression(expr) => {
+                    format!("NVIM9.convert.to_vim_string({})", expr.gen(state))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" .. ")
+    }
+}
+
+/// Generates the lua string of a decoded vim string, with the keys that only
+/// neovim knows translated at runtime
+fn gen_string_bytes(s: &StringBytes) -> String {
+    if s.1.is_empty() {
+        return lua_string(&s.0);
+    }
+
+    let mut parts = vec![];
+    let mut start = 0;
+    for key in &s.1 {
+        if key.offset > start {
+            parts.push(lua_string(&s.0[start..key.offset]));
+        }
+
+        parts.push(format!(
+            "vim.api.nvim_replace_termcodes({}, true, true, true)",
+            lua_string(key.notation.as_bytes())
+        ));
+        start = key.offset;
+    }
+
+    if start < s.0.len() {
+        parts.push(lua_string(&s.0[start..]));
+    }
+
+    format!("({})", parts.join(" .. "))
+}
+
+/// Generates a lua string literal with exactly the bytes of `bytes`
+fn lua_string(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('"');
+    for chunk in bytes.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                // Always three digits, so a following digit isn't part of it
+                ch if ch.is_ascii_control() => {
+                    out.push_str(&format!("\\{:03}", ch as u8))
+                }
+                ch => out.push(ch),
+            }
+        }
+
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\{:03}", byte));
+        }
+    }
+    out.push('"');
+
+    out
+}
+
 impl Generate for CallExpression {
     fn gen(&self, state: &mut State) -> String {
         call_expr::generate(self, state)
@@ -1154,7 +2506,19 @@ impl Generate for VimBoolean {
 
 impl Generate for VimNumber {
     fn gen(&self, _: &mut State) -> String {
-        self.value.clone()
+        match self {
+            VimNumber::Integer(value) => value.to_string(),
+            VimNumber::Float(value) if value.is_infinite() => {
+                "math.huge".to_string()
+            }
+            // Debug always includes the `.` or exponent, so lua reads a float
+            VimNumber::Float(value) => format!("{:?}", value),
+            VimNumber::Blob(bytes) => {
+                let hex: String =
+                    bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                format!("NVIM9.blob(\"{}\")", hex)
+            }
+        }
     }
 }
 
@@ -1197,41 +2561,80 @@ impl Generate for InfixExpression {
     }
 }
 
-fn toplevel_id(s: &mut State, command: &ExCommand) -> Option<String> {
+fn toplevel_id(s: &mut State, command: &ExCommand) -> Vec<String> {
     match command {
-        ExCommand::Decl(decl) => Some(decl.name.gen(s)),
-        ExCommand::Def(def) => {
-            def.name.is_valid_local().then_some(def.name.gen(s))
+        ExCommand::Decl(decl) => vec![decl.name.gen(s)],
+        ExCommand::Def(def) => match def.name.is_valid_local() {
+            true => vec![def.name.gen(s)],
+            false => vec![],
+        },
+        ExCommand::Function(func) => {
+            let mut names = match &func.name {
+                Expression::Identifier(name) if is_legacy_local(name) => {
+                    vec![name.gen(s)]
+                }
+                _ => vec![],
+            };
+
+            // Script variables that are first assigned in the function
+            // still belong to the script
+            legacy_locals(&func.body, script_locals, &mut names);
+            names
         }
+        ExCommand::Let(let_) => let_locals(let_, true),
+        ExCommand::Class(class) => vec![class.name.gen(s)],
+        ExCommand::Interface(interface) => vec![interface.name.gen(s)],
+        ExCommand::Enum(enum_) => vec![enum_.name.gen(s)],
         ExCommand::ExportCommand(e) => toplevel_id(s, e.command.as_ref()),
-        ExCommand::Heredoc(here) => Some(here.name.gen(s)),
+        ExCommand::Modified(modified) => toplevel_id(s, &modified.command),
+        ExCommand::Heredoc(here) => vec![here.name.gen(s)],
         // This might make sense, but I don't think it allows you to do this?
-        ExCommand::Var(_) => None,
+        ExCommand::Var(_) => vec![],
         // These make sense
-        ExCommand::Vim9Script(_) => None,
-        ExCommand::Echo(_) => None,
-        ExCommand::Execute(_) => None,
-        ExCommand::Return(_) => None,
-        ExCommand::If(_) => None,
-        ExCommand::For(_) => None,
-        ExCommand::While(_) => None,
-        ExCommand::Try(_) => None,
-        ExCommand::Call(_) => None,
-        ExCommand::Eval(_) => None,
-        ExCommand::Finish(_) => None,
-        ExCommand::Break(_) => None,
-        ExCommand::Continue(_) => None,
-        ExCommand::Augroup(_) => None,
-        ExCommand::Autocmd(_) => None,
-        ExCommand::Statement(_) => None,
-        ExCommand::UserCommand(_) => None,
-        ExCommand::SharedCommand(_) => None,
-        ExCommand::ImportCommand(_) => None,
-        ExCommand::Skip => None,
-        ExCommand::EndOfFile => None,
-        ExCommand::Comment(_) => None,
-        ExCommand::NoOp(_) => None,
-        ExCommand::Defer(_) => None,
+        ExCommand::Vim9Script(_) => vec![],
+        ExCommand::Echo(_) => vec![],
+        ExCommand::EchoErr(_) => vec![],
+        ExCommand::Execute(_) => vec![],
+        ExCommand::Return(_) => vec![],
+        // Blocks are lua blocks, so what they assign has to be declared
+        // before them
+        ExCommand::If(_)
+        | ExCommand::For(_)
+        | ExCommand::While(_)
+        | ExCommand::Try(_) => {
+            let mut names = vec![];
+            legacy_locals(
+                &Body {
+                    commands: vec![command.clone()],
+                },
+                |let_| let_locals(let_, true),
+                &mut names,
+            );
+            names
+        }
+        ExCommand::Throw(_) => vec![],
+        ExCommand::Call(_) => vec![],
+        ExCommand::Eval(_) => vec![],
+        ExCommand::Finish(_) => vec![],
+        ExCommand::Break(_) => vec![],
+        ExCommand::Continue(_) => vec![],
+        ExCommand::Augroup(_) => vec![],
+        ExCommand::AugroupDelete(_) => vec![],
+        ExCommand::Autocmd(_) => vec![],
+        ExCommand::Statement(_) => vec![],
+        ExCommand::UserCommand(_) => vec![],
+        ExCommand::Map(_) => vec![],
+        ExCommand::Set(_) => vec![],
+        ExCommand::SharedCommand(_) => vec![],
+        ExCommand::ImportCommand(_) => vec![],
+        ExCommand::Skip => vec![],
+        ExCommand::EndOfFile => vec![],
+        ExCommand::Comment(_) => vec![],
+        ExCommand::NoOp(_) => vec![],
+        ExCommand::Defer(_) => vec![],
+        ExCommand::Unlet(_) => vec![],
+        ExCommand::TypeAlias(_) => vec![],
+        ExCommand::Error(_) => vec![],
     }
 }
 
@@ -1240,6 +2643,8 @@ pub fn eval(program: parser::Program, is_test: bool) -> String {
         augroup: None,
         command_depth: 0,
         method_depth: 0,
+        class_statics: None,
+        legacy_locals: None,
         scopes: vec![Scope::new(ScopeKind::TopLevel)],
         is_test,
     };
@@ -1253,9 +2658,14 @@ pub fn eval(program: parser::Program, is_test: bool) -> String {
     }
 
     // "hoist" top-level declaractions to top of program.
+    let mut hoisted = HashSet::new();
     for command in program.commands.iter() {
-        if let Some(toplevel) = toplevel_id(&mut state, command) {
-            output += &format!("local {} = nil\n", toplevel);
+        let names = toplevel_id(&mut state, command)
+            .into_iter()
+            .filter(|name| hoisted.insert(name.clone()))
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            output += &format!("local {} = nil\n", names.join(", "));
         }
     }
 
@@ -1276,11 +2686,37 @@ pub fn eval(program: parser::Program, is_test: bool) -> String {
 pub fn generate(contents: &str, is_test: bool) -> String {
     let lexer = Lexer::new(contents);
     let mut parser = new_parser(lexer);
-    let program = parser.parse_program();
+    let program = parser.parse_program().expect("failed to parse program");
 
     let result = eval(program, is_test);
     println!("{}", result);
 
+    format_lua(result)
+}
+
+/// Like [`generate`], but reports every parse error instead of panicking
+/// on the first one. `path` is the path of the script, which its imports
+/// are relative to.
+pub fn generate_with_diagnostics(
+    contents: &str,
+    path: Option<&Path>,
+    is_test: bool,
+) -> Result<String, Vec<Diagnostic>> {
+    let lexer = Lexer::new(contents);
+    let parser = parser::Parser::new(&lexer);
+    let parser = match path {
+        Some(path) => parser.with_path(path),
+        None => parser,
+    };
+    let (program, diagnostics) = parser.parse_program_with_diagnostics();
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    Ok(format_lua(eval(program, is_test)))
+}
+
+fn format_lua(result: String) -> String {
     let config = stylua_lib::Config::new()
         .with_indent_type(stylua_lib::IndentType::Spaces)
         .with_indent_width(2)
@@ -1353,6 +2789,10 @@ mod test {
     busted!(busted_shared, "../testdata/busted/shared.vim");
     busted!(busted_loops, "../testdata/busted/loops.vim");
     busted!(busted_defer, "../testdata/busted/defer.vim");
+    busted!(busted_lambdas, "../testdata/busted/lambdas.vim");
+    busted!(busted_try, "../testdata/busted/try.vim");
+    busted!(busted_legacy, "../testdata/busted/legacy.vim");
+    busted!(busted_modifiers, "../testdata/busted/modifiers.vim");
     // busted!(busted_vimvars, "../testdata/busted/vimvars.vim");
 
     snapshot!(test_expr, "../testdata/snapshots/expr.vim");
@@ -1362,6 +2802,45 @@ mod test {
     snapshot!(test_autocmd, "../testdata/snapshots/autocmd.vim");
     snapshot!(test_cfilter, "../testdata/snapshots/cfilter.vim");
     snapshot!(test_export, "../testdata/snapshots/export.vim");
+    snapshot!(test_strings, "../testdata/snapshots/strings.vim");
+    snapshot!(test_interpolated, "../testdata/snapshots/interpolated.vim");
+    snapshot!(test_numbers, "../testdata/snapshots/numbers.vim");
+    snapshot!(test_modifiers, "../testdata/snapshots/modifiers.vim");
+    snapshot!(test_ranges, "../testdata/snapshots/ranges.vim");
+    snapshot!(test_types, "../testdata/snapshots/types.vim");
+    snapshot!(test_classes, "../testdata/snapshots/classes.vim");
+    snapshot!(test_block_lambda, "../testdata/snapshots/block_lambda.vim");
+    snapshot!(test_try, "../testdata/snapshots/try.vim");
+    snapshot!(test_throw, "../testdata/snapshots/throw.vim");
+    snapshot!(test_unlet, "../testdata/snapshots/unlet.vim");
+    snapshot!(test_legacy, "../testdata/snapshots/legacy.vim");
+    snapshot!(test_type_alias, "../testdata/snapshots/type_alias.vim");
+    snapshot!(test_map, "../testdata/snapshots/map.vim");
+    snapshot!(test_set, "../testdata/snapshots/set.vim");
+    snapshot!(
+        test_autocmd_options,
+        "../testdata/snapshots/autocmd_options.vim"
+    );
+    snapshot!(test_augroup, "../testdata/snapshots/augroup.vim");
+    snapshot!(test_alias_export, "../testdata/snapshots/alias_export.vim");
+
+    #[test]
+    fn test_alias_import() {
+        // The imported script is found next to the script
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/snapshots/alias_import.vim"
+        ));
+        let contents = std::fs::read_to_string(path).unwrap();
+        let generated =
+            generate_with_diagnostics(&contents, Some(path), false).unwrap();
+
+        let mut settings = insta::Settings::clone_current();
+        settings.set_snapshot_path("../testdata/output/");
+        settings.bind(|| {
+            insta::assert_snapshot!(generated);
+        });
+    }
     // snapshot!(test_matchparen, "../../shared/snapshots/matchparen.vim");
 
     #[test]
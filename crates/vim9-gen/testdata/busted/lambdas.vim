@@ -0,0 +1,82 @@
+vim9script
+
+def Test_block_lambda()
+  var Add = (x, y) => {
+    var sum = x + y
+    return sum
+  }
+
+  var sum = Add(1, 2)
+  assert_equal(3, sum)
+enddef
+
+def Test_block_lambda_sort()
+  var sorted = sort([3, 1, 2], (a, b) => {
+    if a == b
+      return 0
+    endif
+
+    return a < b ? -1 : 1
+  })
+
+  assert_equal([1, 2, 3], sorted)
+enddef
+
+def Test_block_lambda_continue()
+  var Evens = (items) => {
+    var result = []
+    for item in items
+      if item % 2 == 1
+        continue
+      endif
+
+      add(result, item)
+    endfor
+
+    return result
+  }
+
+  var evens = Evens([1, 2, 3, 4])
+  assert_equal([2, 4], evens)
+enddef
+
+def Test_block_lambda_in_loop()
+  var result = []
+  for i in [1, 2, 3]
+    if i == 2
+      continue
+    endif
+
+    var Double = () => {
+      return i + i
+    }
+    var doubled = Double()
+    add(result, doubled)
+  endfor
+
+  assert_equal([2, 6], result)
+enddef
+
+def Test_block_lambda_defer()
+  var x = []
+  var Deferred = () => {
+    defer add(x, 'deferred')
+    add(x, 'body')
+    return x
+  }
+
+  var result = Deferred()
+  assert_equal(['body', 'deferred'], result)
+enddef
+
+def Test_nested_block_lambda()
+  var MakeAdder = (x) => {
+    return (y) => {
+      return x + y
+    }
+  }
+
+  var AddTwo = MakeAdder(2)
+  var five = AddTwo(3)
+  assert_equal(5, five)
+enddef
@@ -0,0 +1,58 @@
+vim9script
+
+function s:Sum(first, ...) abort
+  let l:total = a:first
+  for value in a:000
+    let l:total += value
+  endfor
+  return l:total
+endfunction
+
+function s:Describe(name, count = 1) abort
+  return a:name . ': ' . a:count
+endfunction
+
+var name = 'script'
+
+function s:SetName(name) abort
+  let s:name = a:name
+  let name = 'local'
+  return name
+endfunction
+
+function s:MakeCounter() abort
+  let counter = {'value': 0}
+  function counter.Add(n) dict
+    let self.value += a:n
+    return self.value
+  endfunction
+  return counter
+endfunction
+
+def Test_legacy_varargs()
+  var one = Sum(1)
+  var six = Sum(1, 2, 3)
+  assert_equal(1, one)
+  assert_equal(6, six)
+enddef
+
+def Test_legacy_default_args()
+  var apples = Describe('apples')
+  var pears = Describe('pears', 3)
+  assert_equal('apples: 1', apples)
+  assert_equal('pears: 3', pears)
+enddef
+
+def Test_legacy_dict_function()
+  var counter = MakeCounter()
+  var first = counter.Add(2)
+  var second = counter.Add(3)
+  assert_equal(2, first)
+  assert_equal(5, second)
+enddef
+
+def Test_legacy_scopes_share_a_name()
+  var local = SetName('argument')
+  assert_equal('local', local)
+  assert_equal('argument', name)
+enddef
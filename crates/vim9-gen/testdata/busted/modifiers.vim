@@ -0,0 +1,13 @@
+vim9script
+
+def Test_silent_bang_hides_messages()
+  silent! echomsg "hidden"
+  var messages = execute("messages")
+  assert_true(messages !~ "hidden")
+enddef
+
+def Test_silent_bang_ignores_errors()
+  var x = 1
+  silent! x = [1][5]
+  assert_equal(1, x)
+enddef
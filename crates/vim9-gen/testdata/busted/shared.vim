@@ -6,3 +6,11 @@ def Test_syn()
         ->split("\n")
         ->len())
 enddef
+
+def Test_range_with_next_command()
+  new
+  setline(1, ['a', 'a', 'a'])
+  :1,2s/a/b/ | :3s/a/c/
+  assert_equal(['b', 'b', 'c'], getline(1, '$'))
+  bwipe!
+enddef
@@ -0,0 +1,164 @@
+vim9script
+
+def Test_catch_sets_exception()
+  var caught = ''
+  try
+    eval('nosuchvar')
+  catch /E121/
+    caught = v:exception
+  endtry
+
+  assert_match('E121: Undefined variable: nosuchvar', caught)
+enddef
+
+def Test_exception_has_the_command()
+  var caught = []
+  try
+    eval('nosuchvar')
+  catch
+    add(caught, v:exception)
+  endtry
+
+  try
+    var value = eval('nosuchvar')
+  catch
+    add(caught, v:exception)
+  endtry
+
+  assert_equal([
+    'Vim(eval):E121: Undefined variable: nosuchvar',
+    'Vim(var):E121: Undefined variable: nosuchvar',
+  ], caught)
+enddef
+
+def Test_catch_picks_matching_pattern()
+  var caught = ''
+  try
+    eval('nosuchvar')
+  catch /E15:/
+    caught = 'wrong'
+  catch /E121/
+    caught = 'right'
+  catch
+    caught = 'all'
+  endtry
+
+  assert_equal('right', caught)
+enddef
+
+def Test_unmatched_is_raised_again()
+  var result = []
+  try
+    try
+      eval('nosuchvar')
+    catch /E15:/
+      add(result, 'inner')
+    finally
+      add(result, 'finally')
+    endtry
+  catch
+    add(result, 'outer')
+  endtry
+
+  assert_equal(['finally', 'outer'], result)
+enddef
+
+def ReturnInTry(result: list<string>): string
+  try
+    return 'returned'
+  finally
+    add(result, 'finally')
+  endtry
+
+  return 'not returned'
+enddef
+
+def Test_finally_runs_on_return()
+  var result = []
+  var returned = ReturnInTry(result)
+
+  assert_equal('returned', returned)
+  assert_equal(['finally'], result)
+enddef
+
+def Test_break_and_continue_in_try()
+  var result = []
+  for i in [1, 2, 3, 4]
+    try
+      if i == 2
+        continue
+      endif
+
+      if i == 4
+        break
+      endif
+
+      add(result, i)
+    finally
+      add(result, 'finally')
+    endtry
+  endfor
+
+  assert_equal([1, 'finally', 'finally', 3, 'finally', 'finally'], result)
+enddef
+
+def ReturnFromLoopInTry(): number
+  try
+    for i in [1, 2, 3]
+      if i == 2
+        return i
+      endif
+    endfor
+  catch
+  endtry
+
+  return 0
+enddef
+
+def Test_return_from_loop_in_try()
+  assert_equal(2, ReturnFromLoopInTry())
+enddef
+
+def Test_throw_sets_exception()
+  var caught = ''
+  try
+    throw 'MyPlugin:not found'
+  catch /^MyPlugin:/
+    caught = v:exception
+  endtry
+
+  assert_equal('MyPlugin:not found', caught)
+enddef
+
+def Test_throw_vim_prefix()
+  var caught = ''
+  try
+    throw 'Vim:oops'
+  catch
+    caught = v:exception
+  endtry
+
+  assert_equal("Vim(throw):E608: Cannot :throw exceptions with 'Vim' prefix", caught)
+enddef
+
+def Test_echoerr_exception()
+  var caught = ''
+  try
+    echoerr 'bad ' .. 'thing'
+  catch
+    caught = v:exception
+  endtry
+
+  assert_equal('Vim(echoerr):bad thing', caught)
+enddef
+
+def Test_throwpoint_is_the_throw()
+  var point = ''
+  try
+    throw 'MyPlugin:oops'
+  catch
+    point = v:throwpoint
+  endtry
+
+  assert_match('busted_try\.lua, line \d\+$', point)
+enddef
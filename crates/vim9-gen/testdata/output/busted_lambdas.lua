@@ -0,0 +1,174 @@
+local NVIM9 = require("vim9script")
+local __VIM9_MODULE = {}
+describe("filename", function()
+  local Test_block_lambda = nil
+  local Test_block_lambda_sort = nil
+  local Test_block_lambda_continue = nil
+  local Test_block_lambda_in_loop = nil
+  local Test_block_lambda_defer = nil
+  local Test_nested_block_lambda = nil
+  -- vim9script
+
+  it("Test_block_lambda", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local Add = function(x, y)
+      local sum = NVIM9.ops["Plus"](x, y)
+      return sum
+    end
+
+    local sum = Add(1, 2)
+    NVIM9.fn["assert_equal"](3, sum)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_block_lambda_sort", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local sorted = NVIM9.fn["sort"](
+      { 3, 1, 2 },
+      function(a, b)
+        if NVIM9.bool(NVIM9.ops["EqualTo"](a, b)) then
+          return 0
+        end
+
+        return NVIM9.ternary(NVIM9.ops["LessThan"](a, b), NVIM9.prefix["Minus"](1), 1)
+      end
+    )
+
+    NVIM9.fn["assert_equal"]({ 1, 2, 3 }, sorted)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_block_lambda_continue", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local Evens = function(items)
+      local result = {}
+
+      local body = function(_, item)
+        if NVIM9.bool(NVIM9.ops["EqualTo"](NVIM9.ops["Modulo"](item, 2), 1)) then
+          return NVIM9.ITER_CONTINUE
+        end
+
+        NVIM9.fn["add"](result, item)
+
+        return NVIM9.ITER_DEFAULT
+      end
+
+      for _, item in NVIM9.iter(items) do
+        local nvim9_status, nvim9_ret = body(_, item)
+        if nvim9_status == NVIM9.ITER_BREAK then
+          break
+        elseif nvim9_status == NVIM9.ITER_RETURN then
+          return nvim9_ret
+        end
+      end
+
+      return result
+    end
+
+    local evens = Evens({ 1, 2, 3, 4 })
+    NVIM9.fn["assert_equal"]({ 2, 4 }, evens)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_block_lambda_in_loop", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local result = {}
+
+    local body = function(_, i)
+      if NVIM9.bool(NVIM9.ops["EqualTo"](i, 2)) then
+        return NVIM9.ITER_CONTINUE
+      end
+
+      local Double = function()
+        return NVIM9.ops["Plus"](i, i)
+      end
+      local doubled = Double()
+      NVIM9.fn["add"](result, doubled)
+
+      return NVIM9.ITER_DEFAULT
+    end
+
+    for _, i in NVIM9.iter({ 1, 2, 3 }) do
+      local nvim9_status, nvim9_ret = body(_, i)
+      if nvim9_status == NVIM9.ITER_BREAK then
+        break
+      elseif nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]({ 2, 6 }, result)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_block_lambda_defer", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local x = {}
+    local Deferred = function()
+      local nvim9_deferred = {}
+      local _, ret = pcall(function()
+        table.insert(nvim9_deferred, 1, function()
+          NVIM9.fn["add"](x, "deferred")
+        end)
+
+        NVIM9.fn["add"](x, "body")
+        return x
+      end)
+
+      for _, nvim9_defer in ipairs(nvim9_deferred) do
+        pcall(nvim9_defer)
+      end
+
+      return ret
+    end
+
+    local result = Deferred()
+    NVIM9.fn["assert_equal"]({ "body", "deferred" }, result)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_nested_block_lambda", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local MakeAdder = function(x)
+      return function(y)
+        return NVIM9.ops["Plus"](x, y)
+      end
+    end
+
+    local AddTwo = MakeAdder(2)
+    local five = AddTwo(3)
+    NVIM9.fn["assert_equal"](5, five)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+end)
+return __VIM9_MODULE
@@ -0,0 +1,84 @@
+local NVIM9 = require("vim9script")
+local __VIM9_MODULE = {}
+describe("filename", function()
+  local Sum = nil
+  local Describe = nil
+  local MakeCounter = nil
+  local Test_legacy_varargs = nil
+  local Test_legacy_default_args = nil
+  local Test_legacy_dict_function = nil
+  -- vim9script
+
+  Sum = function(first, ...)
+    local nvim9_varargs = { ... }
+    local total
+
+    total = first
+
+    for _, value in NVIM9.iter(nvim9_varargs) do
+      total = NVIM9.ops["Plus"](total, value)
+    end
+
+    return total
+  end
+
+  Describe = function(name, count)
+    count = vim.F.if_nil(count, 1, count)
+    return NVIM9.ops["StringConcat"](NVIM9.ops["StringConcat"](name, ": "), count)
+  end
+
+  MakeCounter = function()
+    local counter
+
+    counter = { value = 0 }
+    counter["Add"] = NVIM9.dict_function(function(self, n)
+      self["value"] = NVIM9.ops["Plus"](self["value"], n)
+      return self["value"]
+    end)
+    return counter
+  end
+
+  it("Test_legacy_varargs", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local one = Sum(1)
+    local six = Sum(1, 2, 3)
+    NVIM9.fn["assert_equal"](1, one)
+    NVIM9.fn["assert_equal"](6, six)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_legacy_default_args", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local apples = Describe("apples")
+    local pears = Describe("pears", 3)
+    NVIM9.fn["assert_equal"]("apples: 1", apples)
+    NVIM9.fn["assert_equal"]("pears: 3", pears)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_legacy_dict_function", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local counter = MakeCounter()
+    local first = NVIM9.dict_call(counter, "Add", 2)
+    local second = NVIM9.dict_call(counter, "Add", 3)
+    NVIM9.fn["assert_equal"](2, first)
+    NVIM9.fn["assert_equal"](5, second)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+end)
+return __VIM9_MODULE
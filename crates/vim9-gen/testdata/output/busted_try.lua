@@ -0,0 +1,303 @@
+local NVIM9 = require("vim9script")
+local __VIM9_MODULE = {}
+describe("filename", function()
+  local Test_catch_sets_exception = nil
+  local Test_catch_picks_matching_pattern = nil
+  local Test_unmatched_is_raised_again = nil
+  local ReturnInTry = nil
+  local Test_finally_runs_on_return = nil
+  local Test_break_and_continue_in_try = nil
+  local Test_throw_sets_exception = nil
+  local Test_throw_vim_prefix = nil
+  local Test_echoerr_exception = nil
+  -- vim9script
+
+  it("Test_catch_sets_exception", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local caught = ""
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        NVIM9.fn["eval"]("nosuchvar")
+      end, {
+        {
+          pattern = "E121",
+          body = function()
+            caught = vim.v["exception"]
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_match"]("E121: Undefined variable: nosuchvar", caught)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_catch_picks_matching_pattern", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local caught = ""
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        NVIM9.fn["eval"]("nosuchvar")
+      end, {
+        {
+          pattern = "E15:",
+          body = function()
+            caught = "wrong"
+          end,
+        },
+        {
+          pattern = "E121",
+          body = function()
+            caught = "right"
+          end,
+        },
+        {
+          body = function()
+            caught = "all"
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]("right", caught)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_unmatched_is_raised_again", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local result = {}
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        do
+          local nvim9_status, nvim9_ret = NVIM9.try(function()
+            NVIM9.fn["eval"]("nosuchvar")
+          end, {
+            {
+              pattern = "E15:",
+              body = function()
+                NVIM9.fn["add"](result, "inner")
+              end,
+            },
+          }, function()
+            NVIM9.fn["add"](result, "finally")
+          end)
+
+          if nvim9_status == NVIM9.ITER_RETURN then
+            return NVIM9.ITER_RETURN, nvim9_ret
+          elseif nvim9_status == NVIM9.ITER_BREAK then
+            return NVIM9.ITER_BREAK
+          end
+        end
+      end, {
+        {
+          body = function()
+            NVIM9.fn["add"](result, "outer")
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]({ "finally", "outer" }, result)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  ReturnInTry = function(result)
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        return NVIM9.ITER_RETURN, "returned"
+      end, {}, function()
+        NVIM9.fn["add"](result, "finally")
+      end)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    return "not returned"
+  end
+
+  it("Test_finally_runs_on_return", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local result = {}
+    local returned = ReturnInTry(result)
+
+    NVIM9.fn["assert_equal"]("returned", returned)
+    NVIM9.fn["assert_equal"]({ "finally" }, result)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_break_and_continue_in_try", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local result = {}
+
+    local body = function(_, i)
+      do
+        local nvim9_status, nvim9_ret = NVIM9.try(function()
+          if NVIM9.bool(NVIM9.ops["EqualTo"](i, 2)) then
+            return NVIM9.ITER_CONTINUE
+          end
+
+          if NVIM9.bool(NVIM9.ops["EqualTo"](i, 4)) then
+            return NVIM9.ITER_BREAK
+          end
+
+          NVIM9.fn["add"](result, i)
+        end, {}, function()
+          NVIM9.fn["add"](result, "finally")
+        end)
+
+        if nvim9_status == NVIM9.ITER_RETURN then
+          return NVIM9.ITER_RETURN, nvim9_ret
+        elseif nvim9_status == NVIM9.ITER_BREAK then
+          return NVIM9.ITER_BREAK
+        elseif nvim9_status == NVIM9.ITER_CONTINUE then
+          return NVIM9.ITER_CONTINUE
+        end
+      end
+
+      return NVIM9.ITER_DEFAULT
+    end
+
+    for _, i in NVIM9.iter({ 1, 2, 3, 4 }) do
+      local nvim9_status, nvim9_ret = body(_, i)
+      if nvim9_status == NVIM9.ITER_BREAK then
+        break
+      elseif nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]({ 1, "finally", "finally", 3, "finally", "finally" }, result)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_throw_sets_exception", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local caught = ""
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        NVIM9.throw("MyPlugin:not found")
+      end, {
+        {
+          pattern = "^MyPlugin:",
+          body = function()
+            caught = vim.v["exception"]
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]("MyPlugin:not found", caught)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_throw_vim_prefix", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local caught = ""
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        NVIM9.throw("Vim:oops")
+      end, {
+        {
+          body = function()
+            caught = vim.v["exception"]
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]("Vim(throw):E608: Cannot :throw exceptions with 'Vim' prefix", caught)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+
+  it("Test_echoerr_exception", function()
+    -- Set errors to empty
+    vim.v.errors = {}
+
+    -- Actual test
+    local caught = ""
+
+    do
+      local nvim9_status, nvim9_ret = NVIM9.try(function()
+        NVIM9.echoerr(NVIM9.ops["StringConcat"]("bad ", "thing"))
+      end, {
+        {
+          body = function()
+            caught = vim.v["exception"]
+          end,
+        },
+      }, nil)
+
+      if nvim9_status == NVIM9.ITER_RETURN then
+        return nvim9_ret
+      end
+    end
+
+    NVIM9.fn["assert_equal"]("Vim(echoerr):bad thing", caught)
+
+    -- Assert that errors is still empty
+    assert.are.same({}, vim.v.errors)
+  end)
+end)
+return __VIM9_MODULE
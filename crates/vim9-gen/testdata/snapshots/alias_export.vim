@@ -0,0 +1,7 @@
+vim9script
+
+export type Names = list<string>
+export type Flag = bool
+type Count = number
+
+export var count: Count = 0
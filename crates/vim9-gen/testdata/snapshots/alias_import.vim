@@ -0,0 +1,12 @@
+vim9script
+
+import './alias_export.vim'
+import './alias_export.vim' as other
+
+var enabled: alias_export.Flag = 1
+var disabled: other.Flag = 0
+
+class Holder
+  var names: other.Names
+  var flag: alias_export.Flag
+endclass
@@ -0,0 +1,18 @@
+vim9script
+
+augroup options
+  au!
+  au BufWritePre *.rs,*.toml echo "rust"
+  autocmd FileType vim ++once echo "once"
+  autocmd BufEnter <buffer> ++nested echo "local"
+  autocmd BufLeave <buffer=3> echo "third"
+  autocmd BufRead * {
+    autocmd BufWinEnter <buffer=abuf> echo "read"
+  }
+augroup END
+
+autocmd! options BufWritePre *.rs
+autocmd! options FileType
+autocmd! options
+au! CursorHold * echo "replaced"
+autocmd! TextChanged
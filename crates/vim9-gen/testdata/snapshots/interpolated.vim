@@ -0,0 +1,11 @@
+vim9script
+
+var name = "world"
+var greeting = $"hello {name}!"
+var math = $"1 + 1 = {1 + 1}"
+var braces = $"{{literal}} {name}"
+var escapes = $"tab\t{name}\n"
+var literal = $'no \t escapes, it''s {name}'
+var dict = $"{ {a: 1} }"
+var empty = $""
+var nested = $"outer {$'inner {name}'}"
@@ -0,0 +1,62 @@
+" Legacy functions and variables
+
+let s:count = 0
+let g:legacy_loaded = 1
+let [s:first, s:second] = [1, 2]
+
+function! s:Increment(amount = 1) abort
+  let s:count += a:amount
+  return s:count
+endfunction
+
+function s:Join(sep, ...) abort
+  let l:result = ''
+  for item in a:000
+    if l:result != ''
+      let l:result .= a:sep
+    endif
+    let l:result = l:result . item
+  endfor
+  let total = a:0
+  return l:result
+endfunction
+
+function! g:LegacyFirst(...) range
+  if a:0 > 0
+    return a:1
+  endif
+  return a:firstline
+endfunction
+
+let s:counter = {'value': 0}
+function s:counter.Add(n) dict
+  let self.value += a:n
+  return self.value
+endfunction
+
+function! s:Bump() dict closure
+  let self.value += 1
+endfunc
+
+call s:Increment(2)
+echo s:Join(', ', 'a', 'b')
+echo s:counter.Add(3)
+
+function Legacy()
+  let s:n = 3
+  if s:n > 2
+    let [s:low, s:high] = [1, s:n]
+  endif
+endfunction
+
+if !exists('s:loaded')
+  let s:loaded = 1
+endif
+
+let s:name = ''
+function! s:Set(name) abort
+  let s:name = a:name
+  let name = s:name . '!'
+  let l:count = s:count + len(name)
+  return l:count
+endfunction
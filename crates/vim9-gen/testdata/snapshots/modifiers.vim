@@ -0,0 +1,20 @@
+vim9script
+
+silent! call Foo()
+noautocmd keepjumps normal! gg
+vertical botright split
+sil! noa :echo "quiet"
+var silent = 1
+silent = 2
+
+silent! var x = Foo()
+echo x
+
+def Bar(): number
+  silent! return Foo()
+enddef
+
+silent! def Quiet()
+  echo "quiet"
+enddef
+Quiet()
@@ -0,0 +1,14 @@
+vim9script
+
+var decimal = 1'000'000
+var hex = 0x1F
+var octal = 0o17
+var binary = 0b1010
+var float = 3.14
+var exponent = 1.5e-3
+var big_exponent = 1.0e+300
+var leading_zero = 017
+var too_big = 0xFFFFFFFFFFFFFFFF
+var blob = 0zFF00ED
+var dotted_blob = 0zFF.00.ED
+var empty_blob = 0z
@@ -0,0 +1,14 @@
+vim9script
+
+var plain = "hello"
+var quotes = "say \"hi\" \\ bye"
+var whitespace = "a\tb\nc\rd"
+var escape = "\e[0m"
+var hex = "\x41\x7e\xff"
+var octal = "\101\0101"
+var unicode = "\u00e9 é \U0001F600"
+var keys = "\<C-a>\<Esc>\<CR>\<lt>"
+var special = "\<Up>\<S-Tab>"
+var unknown = "\<Nope> \q"
+var cmd = "\<Cmd>echo 1\<CR>"
+var nul = "before\x00after"
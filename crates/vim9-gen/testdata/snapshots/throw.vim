@@ -0,0 +1,15 @@
+vim9script
+
+def Check(value: number)
+  if value < 0
+    throw 'Check: negative ' .. value
+  elseif value == 0
+    echoerr 'Check: zero'
+  endif
+enddef
+
+try
+  Check(-1)
+catch /^Check:/
+  echomsg v:exception
+endtry
@@ -0,0 +1,9 @@
+vim9script
+
+var Check: func(number, ?string): bool = (n, s = '') => n > 0
+var nested: list<list<string>> = [['a']]
+var ok: bool = Check(1)
+
+def Id<T>(x: T): T
+  return x
+enddef
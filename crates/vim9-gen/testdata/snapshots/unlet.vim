@@ -0,0 +1,14 @@
+vim9script
+
+g:name = 'value'
+unlet g:name
+unlet! b:maybe w:other
+unlet $MY_ENV
+
+var config = {key: 1, other: 2}
+var items = [1, 2, 3, 4, 5]
+unlet config.key
+unlet config['other']
+unlet items[0]
+unlet items[1 : 2]
+unlet! items[-1 :]
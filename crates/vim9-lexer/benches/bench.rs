@@ -2,28 +2,37 @@
 
 extern crate test;
 
+const HANDLERS: &str =
+    include_str!("../../shared/snapshots/lsp_handlers.vim");
+
+fn lex_all(contents: &str) -> usize {
+    let mut count = 0;
+    for tok in vim9_lexer::Lexer::new(contents) {
+        test::black_box(tok.expect("lexing error"));
+        count += 1;
+    }
+
+    assert!(count > 0, "Must have some items");
+    count
+}
+
 #[bench]
 fn lexer_handler(b: &mut test::Bencher) {
-    b.iter(|| {
-        let contents = include_str!(
-            "/home/tjdevries/git/lsp/src/autoload/lsp/handlers.vim"
-        );
-        let lexer = vim9_lexer::Lexer::new(&contents);
-
-        let mut res = vec![];
-        loop {
-            match test::black_box(lexer.next_token()) {
-                Ok(tok) => {
-                    if tok.kind == vim9_lexer::TokenKind::EndOfFile {
-                        break;
-                    }
-
-                    res.push(tok);
-                }
-                Err(err) => assert!(false, "error: {:?}", err),
-            }
-        }
-
-        assert!(res.len() > 0, "Must have some items");
-    });
+    b.iter(|| lex_all(HANDLERS));
+}
+
+// The same file repeated. Rows are found with a binary search, so lexing is
+// O(n log n) and these should take a little over ~4x and ~16x as long as
+// `lexer_handler`, where a quadratic lexer takes ~16x and ~256x.
+
+#[bench]
+fn lexer_handler_x4(b: &mut test::Bencher) {
+    let contents = HANDLERS.repeat(4);
+    b.iter(|| lex_all(&contents));
+}
+
+#[bench]
+fn lexer_handler_x16(b: &mut test::Bencher) {
+    let contents = HANDLERS.repeat(16);
+    b.iter(|| lex_all(&contents));
 }
@@ -5,9 +5,10 @@ use std::{
     cell::{Cell, RefCell},
     collections::VecDeque,
     fmt::{Debug, Display},
+    iter::FusedIterator,
 };
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 
 #[derive(Clone, PartialEq)]
 pub struct Span {
@@ -26,6 +27,13 @@ impl Span {
             end_col: 0,
         }
     }
+
+    /// Whether `other` starts inside of this span
+    pub fn contains(&self, other: &Span) -> bool {
+        let start = (other.start_row, other.start_col);
+        (self.start_row, self.start_col) <= start
+            && start <= (self.end_row, self.end_col)
+    }
 }
 
 impl Debug for Span {
@@ -40,7 +48,9 @@ impl Debug for Span {
 
 #[derive(Clone, PartialEq)]
 pub enum TokenText<'a> {
-    Slice(&'a [char]),
+    /// Text borrowed straight from the source
+    Slice(&'a str),
+    /// Text that differs from the source, such as numbers without `'`
     Owned(String),
     Empty,
 }
@@ -54,7 +64,7 @@ impl Default for TokenText<'_> {
 impl<'a> Into<String> for TokenText<'a> {
     fn into(self) -> String {
         match self {
-            TokenText::Slice(s) => s.into_iter().collect(),
+            TokenText::Slice(s) => s.to_string(),
             TokenText::Owned(s) => s,
             TokenText::Empty => "".to_string(),
         }
@@ -63,20 +73,14 @@ impl<'a> Into<String> for TokenText<'a> {
 
 impl<'a> Into<String> for &TokenText<'a> {
     fn into(self) -> String {
-        match self {
-            TokenText::Slice(s) => s.into_iter().collect(),
-            TokenText::Owned(s) => s.clone(),
-            TokenText::Empty => "".to_string(),
-        }
+        self.as_str().to_string()
     }
 }
 
 impl<'a> Debug for TokenText<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TokenText::Slice(s) => {
-                write!(f, "{:?}", s.iter().cloned().collect::<String>())
-            }
+            TokenText::Slice(s) => write!(f, "{:?}", s),
             TokenText::Owned(o) => write!(f, "{:?}", o),
             TokenText::Empty => write!(f, "<Empty>"),
         }
@@ -90,18 +94,13 @@ impl<'a> Display for TokenText<'a> {
 }
 
 impl<'a> TokenText<'a> {
-    // TODO: It'd be cool if we didn't clone here
     pub fn to_string(&self) -> String {
-        match self {
-            TokenText::Slice(s) => s.iter().collect::<String>(),
-            TokenText::Owned(s) => s.clone(),
-            TokenText::Empty => "".to_string(),
-        }
+        self.as_str().to_string()
     }
 
-    pub fn as_str(&'a self) -> &'a str {
+    pub fn as_str(&self) -> &str {
         match self {
-            TokenText::Slice(_) => todo!(),
+            TokenText::Slice(s) => s,
             TokenText::Owned(s) => s,
             TokenText::Empty => "",
         }
@@ -109,12 +108,8 @@ impl<'a> TokenText<'a> {
 
     pub fn eq(&self, val: &str) -> bool {
         match self {
-            TokenText::Slice(s) => {
-                // This seems like I shouldn't have to do this... oh well
-                val.chars().collect::<Vec<char>>() == *s
-            }
-            TokenText::Owned(s) => s.as_str() == val,
             TokenText::Empty => false,
+            _ => self.as_str() == val,
         }
     }
 }
@@ -164,10 +159,25 @@ pub enum TokenKind {
     Comment,
     Literal,
 
+    // Trivia, only produced by `Lexer::with_trivia`
+    /// Spaces and tabs
+    Whitespace,
+    /// A newline that is continued by the next line, along with the `\`
+    /// and any continuation comments before it
+    LineContinuation,
+
     // Identifiers and literals
     Identifier,
     Integer,
+    /// `0x1F`
+    HexInteger,
+    /// `0o17`
+    OctalInteger,
+    /// `0b1010`
+    BinaryInteger,
     Float,
+    /// `0zFF00ED`
+    Blob,
     EnvironmentVariable,
 
     // Reserved Tokens
@@ -247,8 +257,19 @@ pub enum TokenKind {
     SingleQuote,
     SingleQuoteString,
     DoubleQuoteString,
+
+    /// The opening `$"` of an interpolated string
     InterpolatedString,
+    /// The opening `$'` of an interpolated literal string
     InterpolatedLiteralString,
+    /// Literal text between the `{expr}`s of an interpolated string
+    InterpolatedChunk,
+    /// The `{` that starts an expression in an interpolated string
+    InterpolationStart,
+    /// The `}` that ends an expression in an interpolated string
+    InterpolationEnd,
+    /// The closing quote of an interpolated string
+    InterpolatedStringEnd,
 
     LeftParen,
     RightParen,
@@ -271,6 +292,10 @@ impl TokenKind {
         }
     }
 
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Self::Whitespace | Self::LineContinuation)
+    }
+
     pub fn is_eof(&self) -> bool {
         *self == Self::EndOfFile
     }
@@ -315,111 +340,231 @@ impl TokenKind {
     }
 }
 
+/// SubLexers take over tokenizing for parts of the input with their own
+/// rules. They must leave the lexer after the token they return.
 trait SubLexer {
-    fn next_token(
-        &self,
-        lexer: &Lexer,
-    ) -> Result<(Token<'static>, Option<Box<dyn SubLexer>>)>;
+    fn next_token<'a>(
+        self: Box<Self>,
+        lexer: &Lexer<'a>,
+    ) -> Result<TokenAndLexer<'a>>;
+
+    /// Called when this sublexer was started from within `parent`,
+    /// which should take over again when this sublexer is done.
+    fn with_parent(
+        self: Box<Self>,
+        _parent: Box<dyn SubLexer>,
+    ) -> Box<dyn SubLexer>;
 }
 
-type TokenAndLexer = (Token<'static>, Option<Box<dyn SubLexer>>);
+type TokenAndLexer<'a> = (Token<'a>, Option<Box<dyn SubLexer>>);
 
 pub struct LexerState {
+    /// Byte offset of the current char in the input
     position: usize,
-    read_position: usize,
 }
 
-pub struct Lexer {
+pub struct Lexer<'a> {
     state: RefCell<LexerState>,
 
     sublexer: Cell<Option<Box<dyn SubLexer>>>,
 
-    /// Vec containing all the chars,
-    /// this allows easy accessing (and accounts for unicode chars and what not)
-    chars: Vec<char>,
+    /// The source being lexed, tokens borrow their text from here
+    input: &'a str,
 
-    /// Helper vec for quick line lookup
+    /// Helper vec for quick line lookup, the byte offset of each line start
     lines: Vec<usize>,
+
+    /// Byte offset and column of the last span boundary, so that the next
+    /// column on the same line only counts the chars after it
+    last_col: Cell<(usize, usize)>,
+
+    /// Set once iterating has reached the end of the file or an error
+    finished: bool,
+
+    /// Whether to emit trivia tokens, see `Lexer::with_trivia`
+    trivia: bool,
+
+    /// The marker that ends the heredoc we are in, lines are never
+    /// continued inside of a heredoc
+    heredoc_end: Cell<Option<&'a str>>,
 }
 
-impl Debug for Lexer {
+impl Debug for Lexer<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Lexer {{ position: {}, read_position: {}, ch: {:?} }}",
+            "Lexer {{ position: {}, ch: {:?} }}",
             self.state.borrow().position,
-            self.state.borrow().read_position,
             self.ch()
         )
     }
 }
 
-impl Lexer {
-    fn ch(&self) -> Option<&char> {
-        self.chars.get(self.state.borrow().position)
+impl<'a> Lexer<'a> {
+    fn ch(&self) -> Option<char> {
+        self.input[self.position()..].chars().next()
     }
 
-    pub fn new(input: &str) -> Self {
-        let chars = input.chars().collect();
-
+    pub fn new(input: &'a str) -> Self {
         let mut lines = vec![0];
         lines.extend(
             input
-                .chars()
+                .bytes()
                 .enumerate()
-                .filter_map(|(idx, ch)| (ch == '\n').then_some(idx + 1)),
+                .filter_map(|(idx, ch)| (ch == b'\n').then_some(idx + 1)),
         );
 
         Self {
-            state: RefCell::new(LexerState {
-                position: 0,
-                read_position: 1,
-            }),
+            state: RefCell::new(LexerState { position: 0 }),
             sublexer: Cell::new(None),
-            chars,
+            input,
             lines,
+            last_col: Cell::new((0, 0)),
+            finished: false,
+            trivia: false,
+            heredoc_end: Cell::new(None),
+        }
+    }
+
+    /// Lex losslessly: whitespace and line continuations are emitted as
+    /// trivia tokens, and the text of every token is exactly its source
+    /// (including the quotes of strings), so joining the text of all the
+    /// tokens gives back the input.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    /// The whole source that is lexed
+    pub fn source(&self) -> &'a str {
+        self.input
+    }
+
+    /// The source of line `row`, without the newline
+    pub fn line(&self, row: usize) -> &'a str {
+        let start = self.lines[row];
+        let end = match self.lines.get(row + 1) {
+            Some(next) => next - 1,
+            None => self.input.len(),
+        };
+
+        &self.input[start..end]
+    }
+
+    /// Continues lexing at column `col` of line `row`, skipping the source
+    /// before it
+    pub fn skip_to(&self, row: usize, col: usize) {
+        self.state.borrow_mut().position = self.offset_of(row, col);
+    }
+
+    /// The source from column `start_col` of line `start_row` up to column
+    /// `end_col` of line `end_row`, with its line continuations joined
+    pub fn joined_source(
+        &self,
+        (start_row, start_col): (usize, usize),
+        (end_row, end_col): (usize, usize),
+    ) -> String {
+        let mut position = self.offset_of(start_row, start_col);
+        let end = self.offset_of(end_row, end_col);
+
+        let mut joined = String::new();
+        while position < end {
+            let rest = &self.input[position..end];
+            match rest.find('\n') {
+                Some(newline) => {
+                    joined += &rest[..newline];
+
+                    let next = position + newline + 1;
+                    position = match continued_line(self.input, next) {
+                        Some(continued) => continued,
+                        None => next,
+                    };
+                }
+                None => {
+                    joined += rest;
+                    break;
+                }
+            }
         }
+
+        joined
     }
 
+    /// Continues lexing at the start of the next line after an error, and
+    /// returns the empty span of where the lexer stopped
+    pub fn skip_line(&self) -> Span {
+        let position = self.position().min(self.input.len());
+        let row = self.row_of(position);
+        let col = self.col_of(row, position);
+
+        self.sublexer.set(None);
+        self.heredoc_end.set(None);
+        self.state.borrow_mut().position = match self.lines.get(row + 1) {
+            Some(&next) => next,
+            None => self.input.len(),
+        };
+
+        Span {
+            start_row: row,
+            start_col: col,
+            end_row: row,
+            end_col: col,
+        }
+    }
+
+    /// Byte offset of column `col` of line `row`
+    fn offset_of(&self, row: usize, col: usize) -> usize {
+        let line = self.line(row);
+        let offset = line
+            .char_indices()
+            .nth(col)
+            .map_or(line.len(), |(idx, _)| idx);
+
+        self.lines[row] + offset
+    }
+
+    /// Spans count columns in chars, `start` and `end` are byte offsets.
     fn make_span(&self, start: usize, end: usize) -> Result<Span> {
         anyhow::ensure!(start <= end, "start must be less than end");
 
-        let start_row = self
-            .lines
-            .iter()
-            .enumerate()
-            .find_map(|(line, &ch)| (ch > start).then_some(line))
-            .ok_or_else(|| anyhow::anyhow!("input: {} {}", start, end))
-            .context("start_row")?
-            - 1;
-
-        let end_row = self
-            .lines
-            .iter()
-            .enumerate()
-            .skip(start_row)
-            .find_map(|(line, &ch)| (ch > end).then_some(line))
-            .context("end_row")?
-            - 1;
+        let start_row = self.row_of(start);
+        let end_row = self.row_of(end);
 
         Ok(Span {
             start_row,
-            start_col: start - self.lines[start_row],
+            start_col: self.col_of(start_row, start),
             end_row,
-            end_col: end - self.lines[end_row],
+            end_col: self.col_of(end_row, end),
         })
     }
 
-    pub fn read_char(&self) {
-        // println!("read_char: {:?}", self);
+    /// Row that contains the byte at `position`.
+    ///
+    /// `lines` is sorted, so this is a binary search instead of a scan,
+    /// which makes lexing O(n log n) in the size of the file instead of
+    /// quadratic.
+    fn row_of(&self, position: usize) -> usize {
+        // lines[0] is always 0, so there is always at least one line before
+        self.lines.partition_point(|&line| line <= position) - 1
+    }
 
-        let pos = self.state.borrow().position;
-        match self.chars.get(pos) {
-            Some(_) => {
-                self.state.borrow_mut().position = pos + 1;
-                self.state.borrow_mut().read_position += 1;
+    fn col_of(&self, row: usize, position: usize) -> usize {
+        let line_start = self.lines[row];
+        let (from, col) = match self.last_col.get() {
+            (last, col) if line_start <= last && last <= position => {
+                (last, col)
             }
-            None => {}
+            _ => (line_start, 0),
+        };
+
+        let col = col + self.input[from..position].chars().count();
+        self.last_col.set((position, col));
+        col
+    }
+
+    pub fn read_char(&self) {
+        if let Some(ch) = self.ch() {
+            self.state.borrow_mut().position += ch.len_utf8();
         }
     }
 
@@ -429,7 +574,7 @@ impl Lexer {
     {
         let position = self.state.borrow().position;
         loop {
-            if !cond(*self.ch().unwrap()) {
+            if !cond(self.ch().unwrap()) {
                 break;
             }
 
@@ -443,35 +588,138 @@ impl Lexer {
         self.state.borrow().position
     }
 
-    fn read_number(&self) -> Result<Token> {
-        let (pos, _) = self.read_while(|ch| ch.is_numeric() || ch == '\'')?;
+    /// Byte offset just past the current char
+    fn next_position(&self) -> usize {
+        self.position() + self.ch().map_or(0, |ch| ch.len_utf8())
+    }
+
+    /// Byte offset of the char before the current one
+    fn prev_position(&self) -> usize {
+        let position = self.position();
+        match self.input[..position].chars().next_back() {
+            Some(ch) => position - ch.len_utf8(),
+            None => position,
+        }
+    }
+
+    /// Source text from `start` up to the current char
+    fn text_from(&self, start: usize) -> TokenText<'a> {
+        TokenText::Slice(&self.input[start..self.position()])
+    }
+
+    /// Source text of the current char
+    fn ch_text(&self) -> TokenText<'a> {
+        TokenText::Slice(&self.input[self.position()..self.next_position()])
+    }
+
+    /// Reads digits accepted by `is_digit`, along with the `'` separators
+    /// between them
+    fn read_digits<F>(&self, is_digit: F)
+    where
+        F: Fn(char) -> bool,
+    {
+        while let Some(ch) = self.ch() {
+            let separated = ch == '\''
+                && matches!(self.peek_char(), Some(next) if is_digit(next));
+
+            if !is_digit(ch) && !separated {
+                break;
+            }
+
+            self.read_char();
+        }
+    }
+
+    fn read_number(&self) -> Result<Token<'a>> {
+        let pos = self.position();
+
+        let prefixed = |kind, is_digit: fn(char) -> bool| {
+            match self.peek_n(2) {
+                Some(ch) if is_digit(ch) => Some((kind, is_digit)),
+                _ => None,
+            }
+        };
+
+        let radix = match (self.ch(), self.peek_char()) {
+            (Some('0'), Some('x' | 'X')) => {
+                prefixed(TokenKind::HexInteger, |ch| ch.is_ascii_hexdigit())
+            }
+            (Some('0'), Some('o' | 'O')) => {
+                prefixed(TokenKind::OctalInteger, |ch| matches!(ch, '0'..='7'))
+            }
+            (Some('0'), Some('b' | 'B')) => {
+                prefixed(TokenKind::BinaryInteger, |ch| matches!(ch, '0' | '1'))
+            }
+            _ => None,
+        };
+
+        let kind = if let Some((kind, is_digit)) = radix {
+            self.read_char();
+            self.read_char();
+            self.read_digits(is_digit);
 
-        if self.ch() == Some(&'.') {
-            // consume the .
+            kind
+        } else if self.ch() == Some('0')
+            && matches!(self.peek_char(), Some('z' | 'Z'))
+        {
+            // Blobs can have a `.` between any two bytes: 0z0011.2233
+            self.read_char();
             self.read_char();
+            loop {
+                match (self.ch(), self.peek_char()) {
+                    (Some(c), _) if c.is_ascii_hexdigit() => {}
+                    (Some('.'), Some(c)) if c.is_ascii_hexdigit() => {}
+                    _ => break,
+                }
 
-            // read the rest of the number
-            while let Some(ch) = self.ch() && ch.is_numeric() {
                 self.read_char();
             }
 
-            Ok(Token {
-                kind: TokenKind::Float,
-                text: TokenText::Slice(self.chars[pos..self.position()].into()),
-                span: self.make_span(pos, self.position())?,
-            })
+            TokenKind::Blob
         } else {
-            Ok(Token {
-                kind: TokenKind::Integer,
-                text: TokenText::Owned(
-                    self.chars[pos..self.position()]
-                        .iter()
-                        .filter(|c| **c != '\'')
-                        .collect(),
-                ),
-                span: self.make_span(pos, self.position())?,
-            })
-        }
+            self.read_digits(|ch| ch.is_ascii_digit());
+
+            if self.ch() == Some('.')
+                && matches!(self.peek_char(), Some(c) if c.is_ascii_digit())
+            {
+                // consume the .
+                self.read_char();
+
+                // read the rest of the number
+                self.read_digits(|ch| ch.is_ascii_digit());
+
+                // and the exponent, as in 1.5e-3
+                let exponent = match (self.peek_char(), self.peek_n(2)) {
+                    (Some('+' | '-'), Some(c)) => c.is_ascii_digit(),
+                    (Some(c), _) => c.is_ascii_digit(),
+                    _ => false,
+                };
+
+                if matches!(self.ch(), Some('e' | 'E')) && exponent {
+                    self.read_char();
+                    if matches!(self.ch(), Some('+' | '-')) {
+                        self.read_char();
+                    }
+
+                    self.read_digits(|ch| ch.is_ascii_digit());
+                }
+
+                TokenKind::Float
+            } else {
+                TokenKind::Integer
+            }
+        };
+
+        Ok(Token {
+            kind,
+            text: match &self.input[pos..self.position()] {
+                text if text.contains('\'') => {
+                    TokenText::Owned(text.replace('\'', ""))
+                }
+                text => TokenText::Slice(text),
+            },
+            span: self.make_span(pos, self.position())?,
+        })
     }
 
     fn read_until_or<F>(
@@ -480,26 +728,26 @@ impl Lexer {
         fail: F,
         passed: TokenKind,
         failed: TokenKind,
-    ) -> Result<Token>
+    ) -> Result<Token<'a>>
     where
         F: Fn(&char) -> bool,
     {
         self.read_char();
-        if let Some(ch) = self.ch() && ch == &until {
+        if let Some(ch) = self.ch() && ch == until {
             return Ok(Token {
                 kind: passed,
-                text: TokenText::Slice(self.chars[self.position()..self.position()].into()),
+                text: TokenText::Slice(""),
                 span: self.make_span(self.position(), self.position())?,
             });
         }
 
         let position = self.position();
 
-        while let Some(ch) = self.ch() && ch != &until {
+        while let Some(ch) = self.ch() && ch != until {
             if fail(&ch) {
                 return Ok(Token {
                     kind: failed,
-                    text: TokenText::Slice(self.chars[self.position()..self.position()].into()),
+                    text: TokenText::Slice(""),
                     span: self.make_span(self.position(), self.position())?,
                 });
             }
@@ -509,29 +757,21 @@ impl Lexer {
 
         Ok(Token {
             kind: passed,
-            text: TokenText::Slice(
-                self.chars[position..self.position()].into(),
-            ),
-            span: self.make_span(position, self.position() - 1)?,
+            text: self.text_from(position),
+            span: self.make_span(position, self.prev_position())?,
         })
     }
 
-    fn read_line(&self) -> Result<Token<'static>> {
-        // TODO: Could probably do this without static and just peek until we see
-        // a newline, then return that slice, but I wrote it this way the first time
-        // so I think it's OK
-        //
-        // Also, we're only using this right now for NormalModeParser, so we need
-        // to figure that out later
+    /// Reads the rest of the line, leaving the lexer on its last char.
+    ///
+    /// We're only using this right now for NormalModeParser
+    fn read_line(&self) -> Result<Token<'a>> {
         let position = self.position();
 
-        let mut line = String::new();
         loop {
             match self.ch() {
-                Some(&ch) => {
-                    line += &ch.to_string();
-
-                    if let Some(&peek) = self.peek_char() {
+                Some(_) => {
+                    if let Some(peek) = self.peek_char() {
                         if peek == '\n' {
                             break;
                         }
@@ -545,7 +785,7 @@ impl Lexer {
 
         Ok(Token {
             kind: TokenKind::Literal,
-            text: TokenText::Owned(line),
+            text: TokenText::Slice(&self.input[position..self.next_position()]),
             span: self.make_span(position, self.position())?,
         })
     }
@@ -555,22 +795,22 @@ impl Lexer {
         until: char,
         kind: TokenKind,
         fail: F,
-    ) -> Result<Option<Token>>
+    ) -> Result<Option<Token<'a>>>
     where
         F: Fn(&char) -> bool,
     {
         self.read_char();
-        if let Some(ch) = self.ch() && ch == &until {
+        if let Some(ch) = self.ch() && ch == until {
             return Ok(Some(Token {
                 kind,
-                text: TokenText::Slice(self.chars[self.position()..self.position()].into()),
+                text: TokenText::Slice(""),
                 span: self.make_span(self.position(), self.position())?,
             }));
         }
 
         let position = self.position();
 
-        while let Some(ch) = self.ch() && ch != &until {
+        while let Some(ch) = self.ch() && ch != until {
             if fail(&ch) {
                 return Ok(None);
             }
@@ -580,38 +820,73 @@ impl Lexer {
 
         Ok(Some(Token {
             kind,
-            text: TokenText::Slice(
-                self.chars[position..self.position()].into(),
-            ),
-            span: self.make_span(position, self.position() - 1)?,
+            text: self.text_from(position),
+            span: self.make_span(position, self.prev_position())?,
         }))
     }
 
-    fn read_comment(&self) -> Result<Token> {
+    /// Whether the `"` at the current position is closed on the same line,
+    /// skipping over escaped quotes.
+    fn peek_closing_double_quote(&self) -> bool {
+        let mut n = 1;
+        while let Some(peeked) = self.peek_n(n) && peeked != '\n' {
+            match peeked {
+                '"' => return true,
+                '\\' if self.peek_n(n + 1) != Some('\n') => n += 2,
+                _ => n += 1,
+            }
+        }
+
+        false
+    }
+
+    /// Reads a double quoted string that is known to be closed on this line.
+    ///
+    /// The text is left as written, decoding escapes is up to the parser.
+    fn read_double_quoted(&self, kind: TokenKind) -> Result<Token<'a>> {
+        self.read_char();
+
+        let position = self.position();
+        while let Some(ch) = self.ch() && ch != '"' {
+            if ch == '\\' {
+                self.read_char();
+            }
+
+            self.read_char();
+        }
+
+        let end = if self.position() > position {
+            self.prev_position()
+        } else {
+            position
+        };
+
+        Ok(Token {
+            kind,
+            text: self.text_from(position),
+            span: self.make_span(position, end)?,
+        })
+    }
+
+    fn read_comment(&self) -> Result<Token<'a>> {
         let (pos, _) = self.read_while(|ch| ch != '\n')?;
 
         Ok(Token {
             kind: TokenKind::Comment,
-            text: TokenText::Slice(self.chars[pos..self.position()].into()),
+            text: self.text_from(pos),
             span: self.make_span(pos, self.position())?,
         })
     }
 
-    fn read_identifier(&self) -> Result<Token> {
+    fn read_identifier(&self) -> Result<Token<'a>> {
         let position = self.position();
-        while let Some(ch) = self.ch() && is_identifier(*ch) {
+        while let Some(ch) = self.ch() && is_identifier(ch) {
             self.read_char();
         }
 
-        // TODO(clone)
-        let text_str = self.chars[position..self.position()]
-            .iter()
-            .collect::<String>();
+        let text = self.text_from(position);
 
-        let text =
-            TokenText::Slice(self.chars[position..self.position()].into());
-
-        let kind = match text_str.as_str() {
+        let kind = match text.as_str() {
             "true" => TokenKind::True,
             "false" => TokenKind::False,
             "null" => TokenKind::Null,
@@ -652,26 +927,110 @@ impl Lexer {
         })
     }
 
+    /// Skips blanks and line continuations, but not the end of the line
     fn skip_whitespace(&self) {
-        if self.ch() == Some(&'\n') {
+        if self.trivia {
             return;
         }
 
-        while let Some(&ch) = self.ch() && ch.is_ascii_whitespace() {
-            if ch == '\n' {
-                return
+        loop {
+            while let Some(ch) = self.ch() && is_blank(ch) {
+                self.read_char();
             }
 
-            self.read_char();
+            if self.ch() != Some('\n') {
+                return;
+            }
+
+            match self.line_continuation_end() {
+                Some(end) => {
+                    while self.position() < end {
+                        self.read_char();
+                    }
+                }
+                None => return,
+            }
         }
     }
 
-    fn peek_char(&self) -> Option<&char> {
-        self.chars.get(self.position() + 1)
+    /// Whitespace and line continuations, when lexing with trivia
+    fn read_trivia(&self) -> Result<Option<Token<'a>>> {
+        if !self.trivia {
+            return Ok(None);
+        }
+
+        let position = self.position();
+        let kind = match self.ch() {
+            Some('\n') => match self.line_continuation_end() {
+                Some(end) => {
+                    while self.position() < end {
+                        self.read_char();
+                    }
+
+                    TokenKind::LineContinuation
+                }
+                None => return Ok(None),
+            },
+            Some(ch) if is_blank(ch) => {
+                while let Some(ch) = self.ch() && is_blank(ch) {
+                    self.read_char();
+                }
+
+                TokenKind::Whitespace
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Token {
+            kind,
+            text: self.text_from(position),
+            span: self.make_span(position, self.position())?,
+        }))
+    }
+
+    /// When the newline at the current position is continued by the next
+    /// line, the offset where the logical line picks up again.
+    ///
+    /// Lines are continued by a leading `\` (legacy) or a leading operator
+    /// (Vim9), with any `"\ ` or `#\ ` comment lines in between skipped.
+    /// See `:help line-continuation` and `:help vim9-line-continuation`.
+    fn line_continuation_end(&self) -> Option<usize> {
+        if let Some(marker) = self.heredoc_end.get() {
+            let line_start = self.lines[self.row_of(self.position())];
+            if self.input[line_start..self.position()].trim() != marker {
+                return None;
+            }
+
+            self.heredoc_end.set(None);
+        }
+
+        continued_line(self.input, self.next_position())
+    }
+
+    /// Remembers the marker of a heredoc started on the current line
+    fn start_heredoc(&self) {
+        let rest = &self.input[self.position()..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        if let Some(marker) = line.split_whitespace().last() {
+            self.heredoc_end.set(Some(marker));
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.peek_n(1)
     }
 
-    fn peek_n(&self, n: usize) -> Option<&char> {
-        self.chars.get(self.position() + n)
+    fn peek_n(&self, n: usize) -> Option<char> {
+        self.input[self.position()..].chars().nth(n)
+    }
+
+    /// The two characters after the current one, where the end of the
+    /// input reads like the end of a line
+    fn peek_two(&self) -> (char, char) {
+        (
+            self.peek_n(1).unwrap_or('\n'),
+            self.peek_n(2).unwrap_or('\n'),
+        )
     }
 
     fn if_peek(
@@ -679,71 +1038,86 @@ impl Lexer {
         peeked: char,
         no: TokenKind,
         yes: TokenKind,
-    ) -> Result<Token> {
+    ) -> Result<Token<'a>> {
         if let Some(ch) = self.peek_char() {
-            if *ch == peeked {
+            if ch == peeked {
                 let position = self.position();
                 self.read_char();
 
                 Ok(Token {
                     kind: yes,
                     text: TokenText::Slice(
-                        self.chars[position..=self.position()].into(),
+                        &self.input[position..self.next_position()],
                     ),
-                    span: self.make_span(position, self.position() + 1)?,
+                    span: self.make_span(position, self.next_position())?,
                 })
             } else {
                 Ok(Token {
                     kind: no,
                     // text: self.ch.unwrap().to_string(),
-                    text: TokenText::Slice(
-                        self.chars[self.position()..=self.position()].into(),
-                    ),
+                    text: self.ch_text(),
                     span: self
-                        .make_span(self.position(), self.position() + 1)?,
+                        .make_span(self.position(), self.next_position())?,
                 })
             }
         } else {
             Ok(Token {
                 kind: TokenKind::EndOfFile,
-                text: TokenText::Owned("".to_string()),
+                text: TokenText::Slice(""),
                 span: Span::empty(),
             })
         }
     }
 
-    fn literal(&self, ch: char, kind: TokenKind) -> Result<Token> {
+    fn literal(&self, kind: TokenKind) -> Result<Token<'a>> {
         Ok(Token {
             kind,
-            text: TokenText::Owned(ch.to_string()),
-            span: self.make_span(self.position(), self.position() + 1)?,
+            text: self.ch_text(),
+            span: self.make_span(self.position(), self.next_position())?,
         })
     }
 
-    fn empty_literal(&self, ch: char, kind: TokenKind) -> Result<Token> {
+    fn empty_literal(&self, kind: TokenKind) -> Result<Token<'a>> {
         Ok(Token {
             kind,
-            text: TokenText::Owned(ch.to_string()),
+            text: self.ch_text(),
             span: self.make_span(self.position(), self.position())?,
         })
     }
 
-    pub fn next_token(&self) -> Result<Token> {
+    pub fn next_token(&self) -> Result<Token<'a>> {
+        let position = self.position();
+
         // Handle sublexers...
         //  there is some goofiness with all this stuff
-        if let Some(sublexer) = self.sublexer.take() {
+        let tok = if let Some(sublexer) = self.sublexer.take() {
             let (tok, next_lexer) = sublexer.next_token(&self)?;
             self.sublexer.set(next_lexer);
-            self.read_char();
-            return Ok(tok);
+            tok
+        } else {
+            self.read_token()?
+        };
+
+        if self.trivia {
+            return Ok(Token {
+                text: self.text_from(position),
+                ..tok
+            });
         }
 
+        Ok(tok)
+    }
+
+    fn read_token(&self) -> Result<Token<'a>> {
         use TokenKind::*;
 
         self.skip_whitespace();
+        if let Some(tok) = self.read_trivia()? {
+            return Ok(tok);
+        }
 
         let tok = match self.ch() {
-            Some(&ch) => {
+            Some(ch) => {
                 match ch {
                     // Operators that can optionally have an additional equals
                     '=' => self.handle_equal(),
@@ -763,11 +1137,9 @@ impl Lexer {
                         self.read_char();
                         Ok(Token {
                             kind: Escaped,
-                            text: TokenText::Owned(
-                                self.ch().unwrap().to_string(),
-                            ),
+                            text: self.ch_text(),
                             span: self.make_span(
-                                self.position() - 1,
+                                self.prev_position(),
                                 self.position(),
                             )?,
                         })
@@ -776,27 +1148,25 @@ impl Lexer {
                         self.read_char();
                         Ok(Token {
                             kind: Register,
-                            text: TokenText::Owned(
-                                self.ch().unwrap().to_string(),
-                            ),
+                            text: self.ch_text(),
                             span: self.make_span(
-                                self.position() - 1,
+                                self.prev_position(),
                                 self.position(),
                             )?,
                         })
                     }
 
                     ':' => self.handle_colon(),
-                    '?' => self.literal(ch, QuestionMark),
-                    '^' => self.literal(ch, Caret),
-                    '(' => self.literal(ch, LeftParen),
-                    ')' => self.literal(ch, RightParen),
-                    '[' => self.literal(ch, LeftBracket),
-                    ']' => self.literal(ch, RightBracket),
-                    '{' => self.literal(ch, LeftBrace),
-                    '}' => self.literal(ch, RightBrace),
-                    ',' => self.literal(ch, Comma),
-                    '\n' => self.empty_literal(ch, EndOfLine),
+                    '?' => self.literal(QuestionMark),
+                    '^' => self.literal(Caret),
+                    '(' => self.literal(LeftParen),
+                    ')' => self.literal(RightParen),
+                    '[' => self.literal(LeftBracket),
+                    ']' => self.literal(RightBracket),
+                    '{' => self.literal(LeftBrace),
+                    '}' => self.literal(RightBrace),
+                    ',' => self.literal(Comma),
+                    '\n' => self.empty_literal(EndOfLine),
                     '#' => self.read_comment(),
 
                     // TODO: Handle escaped strings.
@@ -812,8 +1182,7 @@ impl Lexer {
                         } else {
                             Ok(Token {
                                 kind: Illegal,
-                                // text: TokenText::Ch(ch),
-                                text: todo!(),
+                                text: self.ch_text(),
                                 span: self.make_span(
                                     self.position(),
                                     self.position(),
@@ -825,61 +1194,62 @@ impl Lexer {
             }
             None => Ok(Token {
                 kind: EndOfFile,
-                text: TokenText::Owned("".to_string()),
+                text: TokenText::Slice(""),
                 span: Span::empty(),
             }),
         }?;
 
-        self.read_char();
+        // Comments swallow their newline, unless it is kept as its own token
+        if !(self.trivia && tok.kind == Comment) {
+            self.read_char();
+        }
+
         Ok(tok)
     }
 
-    fn read_one(&self, kind: TokenKind) -> Result<Token> {
+    fn read_one(&self, kind: TokenKind) -> Result<Token<'a>> {
         Ok(Token {
             kind,
-            text: TokenText::Owned(self.ch().unwrap().to_string()),
-            span: self.make_span(self.position(), self.position() + 1)?,
+            text: self.ch_text(),
+            span: self.make_span(self.position(), self.next_position())?,
         })
     }
 
-    fn read_two(&self, kind: TokenKind) -> Result<Token> {
+    fn read_two(&self, kind: TokenKind) -> Result<Token<'a>> {
         let position = self.position();
         self.read_char();
 
         Ok(Token {
             kind,
-            text: TokenText::Slice(
-                self.chars[position..=self.position()].into(),
-            ),
-            span: self.make_span(position, self.position() + 1)?,
+            text: TokenText::Slice(&self.input[position..self.next_position()]),
+            span: self.make_span(position, self.next_position())?,
         })
     }
 
-    fn read_three(&self, kind: TokenKind) -> Result<Token> {
+    fn read_three(&self, kind: TokenKind) -> Result<Token<'a>> {
         let position = self.position();
         self.read_char();
         self.read_char();
 
         Ok(Token {
             kind,
-            text: TokenText::Slice(
-                self.chars[position..=self.position()].into(),
-            ),
-            span: self.make_span(position, self.position() + 1)?,
+            text: TokenText::Slice(&self.input[position..self.next_position()]),
+            span: self.make_span(position, self.next_position())?,
         })
     }
 
-    fn handle_equal(&self) -> Result<Token> {
-        let peeked = (
-            self.peek_n(1).unwrap().to_owned(),
-            self.peek_n(2).unwrap().to_owned(),
-        );
+    fn handle_equal(&self) -> Result<Token<'a>> {
+        let peeked = self.peek_two();
 
         match peeked {
             ('=', '#') => self.read_three(TokenKind::EqualTo),
             ('=', '?') => self.read_three(TokenKind::EqualToIns),
             ('=', _) => self.read_two(TokenKind::EqualTo),
-            ('<', '<') => self.read_three(TokenKind::HeredocOperator),
+            ('<', '<') => {
+                let tok = self.read_three(TokenKind::HeredocOperator);
+                self.start_heredoc();
+                tok
+            }
             ('<', _) => self.read_two(TokenKind::Illegal),
             ('~', '#') => self.read_three(TokenKind::RegexpMatches),
             ('~', '?') => self.read_three(TokenKind::RegexpMatchesIns),
@@ -889,11 +1259,8 @@ impl Lexer {
         }
     }
 
-    fn handle_bang(&self) -> Result<Token> {
-        let peeked = (
-            self.peek_n(1).unwrap().to_owned(),
-            self.peek_n(2).unwrap().to_owned(),
-        );
+    fn handle_bang(&self) -> Result<Token<'a>> {
+        let peeked = self.peek_two();
 
         match peeked {
             ('=', '#') => self.read_three(TokenKind::NotEqualTo),
@@ -906,11 +1273,8 @@ impl Lexer {
         }
     }
 
-    fn handle_gt(&self) -> Result<Token> {
-        let peeked = (
-            self.peek_n(1).unwrap().to_owned(),
-            self.peek_n(2).unwrap().to_owned(),
-        );
+    fn handle_gt(&self) -> Result<Token<'a>> {
+        let peeked = self.peek_two();
 
         match peeked {
             ('=', '#') => self.read_three(TokenKind::GreaterThanOrEqual),
@@ -925,11 +1289,8 @@ impl Lexer {
         }
     }
 
-    fn handle_lt(&self) -> Result<Token> {
-        let peeked = (
-            self.peek_n(1).unwrap().to_owned(),
-            self.peek_n(2).unwrap().to_owned(),
-        );
+    fn handle_lt(&self) -> Result<Token<'a>> {
+        let peeked = self.peek_two();
 
         match peeked {
             ('=', '#') => self.read_three(TokenKind::LessThanOrEqual),
@@ -942,7 +1303,7 @@ impl Lexer {
         }
     }
 
-    fn handle_dash(&self) -> Result<Token> {
+    fn handle_dash(&self) -> Result<Token<'a>> {
         match self.peek_char().unwrap() {
             '=' => self.read_two(TokenKind::MinusEquals),
             '>' => self.read_two(TokenKind::MethodArrow),
@@ -950,65 +1311,66 @@ impl Lexer {
         }
     }
 
-    pub fn handle_dollar(&self) -> Result<Token> {
+    pub fn handle_dollar(&self) -> Result<Token<'a>> {
         Ok(match self.peek_char().unwrap() {
-            '\'' => {
-                self.read_char();
-                self.read_until(
-                    '\'',
-                    TokenKind::InterpolatedLiteralString,
-                    |ch| *ch == '\n',
-                )?
-                .unwrap()
-            }
-            '"' => {
-                self.read_char();
-                self.read_until('\"', TokenKind::InterpolatedString, |ch| {
-                    *ch == '\n'
-                })?
-                .unwrap()
+            quote @ ('\'' | '"') => {
+                let kind = if quote == '"' {
+                    TokenKind::InterpolatedString
+                } else {
+                    TokenKind::InterpolatedLiteralString
+                };
+
+                self.sublexer
+                    .set(Some(Box::new(InterpolatedStringLexer::new(quote))));
+                self.read_two(kind)?
             }
-            c if is_identifier(*c) => {
+            c if is_identifier(c) => {
                 self.read_char();
 
+                // Stop on the last char of the name, it is read along with
+                // the token so that the char after it is not skipped
                 let position = self.position();
-                while let Some(&ch) = self.ch() && is_identifier(ch) {
+                while let Some(ch) = self.peek_char() && is_identifier(ch) {
                     self.read_char();
                 }
 
+                let end = self.next_position();
                 Token {
                     kind: TokenKind::EnvironmentVariable,
-                    text: TokenText::Slice(
-                        self.chars[position..self.position()].into(),
-                    ),
-                    span: self.make_span(position, self.position())?,
+                    text: TokenText::Slice(&self.input[position..end]),
+                    span: self.make_span(position, end)?,
                 }
             }
             _ => Token {
                 kind: TokenKind::Illegal,
-                // text: TokenText::Ch(self.ch.unwrap()),
-                text: todo!(),
+                text: self.ch_text(),
                 span: self.make_span(self.position(), self.position())?,
             },
         })
     }
 
-    fn handle_dot(&self) -> Result<Token> {
-        let peeked = (
-            self.peek_n(1).unwrap().to_owned(),
-            self.peek_n(2).unwrap().to_owned(),
-        );
+    fn handle_dot(&self) -> Result<Token<'a>> {
+        let peeked = self.peek_two();
+
+        // Legacy script concatenates with `.` and `.=`, which vim9 script
+        // spells `..` and `..=`. A `.` between blanks can not be a dict
+        // access, so it is read as a concatenation.
+        let before = self.input[..self.position()].chars().next_back();
+        let spaced = matches!(peeked.0, ' ' | '\t')
+            && matches!(before, Some(' ' | '\t'));
 
         match peeked {
             ('.', '=') => self.read_three(TokenKind::StringConcatEquals),
             ('.', '.') => self.read_three(TokenKind::Ellipsis),
             ('.', _) => self.read_two(TokenKind::StringConcat),
+            ('=', _) => self.read_two(TokenKind::StringConcatEquals),
+            _ if spaced => self.read_one(TokenKind::StringConcat),
             (_, _) => self.read_one(TokenKind::Dot),
         }
     }
 
-    fn handle_colon(&self) -> Result<Token> {
-        match self.peek_char().unwrap() {
+    fn handle_colon(&self) -> Result<Token<'a>> {
+        match self.peek_char().unwrap_or('\n') {
             ' ' => self.read_two(TokenKind::SpacedColon),
             ']' => self.read_one(TokenKind::SpacedColon),
             _ => self.read_one(TokenKind::Colon),
@@ -1020,8 +1382,8 @@ impl Lexer {
         F: Fn(char) -> bool,
     {
         let mut n = 1;
-        while let Some(peeked) = self.peek_n(n) && *peeked != '\n' {
-            if f(*peeked) {
+        while let Some(peeked) = self.peek_n(n) && peeked != '\n' {
+            if f(peeked) {
                 return true
             }
 
@@ -1031,7 +1393,7 @@ impl Lexer {
         false
     }
 
-    fn handle_single_quote(&self) -> Result<Token> {
+    fn handle_single_quote(&self) -> Result<Token<'a>> {
         if self.peek_in_line(|ch| ch == '\'') {
             return Ok(self
                 .read_until('\'', TokenKind::SingleQuoteString, is_newline)?
@@ -1041,11 +1403,9 @@ impl Lexer {
         self.read_one(TokenKind::SingleQuote)
     }
 
-    fn handle_double_quote(&self) -> Result<Token> {
-        if self.peek_in_line(|ch| ch == '"') {
-            return Ok(self
-                .read_until('"', TokenKind::DoubleQuoteString, is_newline)?
-                .unwrap());
+    fn handle_double_quote(&self) -> Result<Token<'a>> {
+        if self.peek_closing_double_quote() {
+            return self.read_double_quoted(TokenKind::DoubleQuoteString);
         }
 
         self.read_until_or(
@@ -1064,48 +1424,261 @@ impl Lexer {
 struct NormalModeParser {}
 
 impl SubLexer for NormalModeParser {
-    fn next_token(&self, lexer: &Lexer) -> Result<TokenAndLexer> {
-        if let Some(&ch) = lexer.ch() {
+    fn next_token<'a>(
+        self: Box<Self>,
+        lexer: &Lexer<'a>,
+    ) -> Result<TokenAndLexer<'a>> {
+        if let Some(ch) = lexer.ch() {
             if ch == ' ' {
+                if let Some(tok) = lexer.read_trivia()? {
+                    return Ok((tok, Some(self)));
+                }
+
                 lexer.read_char();
             }
         }
 
-        match lexer.ch() {
-            Some(&ch) => match ch {
-                '!' => Ok((
-                    Token::owned(lexer.handle_bang()?.to_owned()),
-                    Some(Box::new(NormalModeParser {})),
-                )),
-                _ => Ok((Token::owned(lexer.read_line()?), None)),
+        let result: TokenAndLexer = match lexer.ch() {
+            Some(ch) => match ch {
+                '!' => (lexer.handle_bang()?, Some(self)),
+                _ => (lexer.read_line()?, None),
             },
             None => unreachable!("don't think this should happen..."),
+        };
+
+        lexer.read_char();
+        Ok(result)
+    }
+
+    fn with_parent(
+        self: Box<Self>,
+        _parent: Box<dyn SubLexer>,
+    ) -> Box<dyn SubLexer> {
+        self
+    }
+}
+
+/// InterpolatedStringLexer splits `$"..."` and `$'...'` into the literal
+/// chunks and the tokens of each `{expr}`.
+///
+/// `{{` and `}}` are left in the literal chunks, it is up to the parser
+/// to turn them into a single brace.
+struct InterpolatedStringLexer {
+    quote: char,
+
+    /// Depth of braces inside of an `{expr}`, `None` while reading literal text
+    depth: Option<usize>,
+
+    /// Sublexer to go back to when this string is done
+    parent: Option<Box<dyn SubLexer>>,
+}
+
+impl InterpolatedStringLexer {
+    fn new(quote: char) -> Self {
+        Self {
+            quote,
+            depth: None,
+            parent: None,
         }
     }
+
+    fn next_literal<'a>(
+        mut self: Box<Self>,
+        lexer: &Lexer<'a>,
+    ) -> Result<TokenAndLexer<'a>> {
+        let position = lexer.position();
+
+        let mut end = position;
+        loop {
+            let mut chars = lexer.input[end..].chars();
+            let ch = chars.next();
+            let next = chars.next();
+            match (ch, next) {
+                (None | Some('\n'), _) => {
+                    return Err(anyhow::anyhow!(
+                        "unterminated interpolated string"
+                    ))
+                }
+                (Some('{'), Some('{')) | (Some('}'), Some('}')) => end += 2,
+                (Some('\\'), Some(escaped)) if self.quote == '"' => {
+                    end += 1 + escaped.len_utf8()
+                }
+                (Some('\''), Some('\'')) if self.quote == '\'' => end += 2,
+                (Some(ch), _) if ch == self.quote || ch == '{' => break,
+                (Some(ch), _) => end += ch.len_utf8(),
+            }
+        }
+
+        if end == position {
+            let tok = if lexer.ch() == Some('{') {
+                self.depth = Some(0);
+                lexer.read_one(TokenKind::InterpolationStart)?
+            } else {
+                lexer.read_one(TokenKind::InterpolatedStringEnd)?
+            };
+
+            lexer.read_char();
+
+            return match self.depth {
+                Some(_) => Ok((tok, Some(self))),
+                None => Ok((tok, self.parent)),
+            };
+        }
+
+        let tok = Token {
+            kind: TokenKind::InterpolatedChunk,
+            text: TokenText::Slice(&lexer.input[position..end]),
+            span: lexer.make_span(position, end)?,
+        };
+
+        while lexer.position() < end {
+            lexer.read_char();
+        }
+
+        Ok((tok, Some(self)))
+    }
+}
+
+impl SubLexer for InterpolatedStringLexer {
+    fn next_token<'a>(
+        mut self: Box<Self>,
+        lexer: &Lexer<'a>,
+    ) -> Result<TokenAndLexer<'a>> {
+        let depth = match self.depth {
+            Some(depth) => depth,
+            None => return self.next_literal(lexer),
+        };
+
+        lexer.skip_whitespace();
+        match lexer.ch() {
+            Some('}') if depth == 0 => {
+                self.depth = None;
+
+                let tok = lexer.read_one(TokenKind::InterpolationEnd)?;
+                lexer.read_char();
+
+                Ok((tok, Some(self)))
+            }
+            None | Some('\n') => {
+                Err(anyhow::anyhow!("unterminated interpolated string"))
+            }
+            Some(ch) => {
+                match ch {
+                    '{' => self.depth = Some(depth + 1),
+                    '}' => self.depth = Some(depth - 1),
+                    _ => {}
+                }
+
+                let tok = lexer.read_token()?;
+
+                // Strings nested in the expression take over until they end
+                let next = match lexer.sublexer.take() {
+                    Some(nested) => nested.with_parent(self),
+                    None => self,
+                };
+
+                Ok((tok, Some(next)))
+            }
+        }
+    }
+
+    fn with_parent(
+        mut self: Box<Self>,
+        parent: Box<dyn SubLexer>,
+    ) -> Box<dyn SubLexer> {
+        self.parent = Some(parent);
+        self
+    }
+}
+
+/// Iterates over the tokens of the input, stopping before `EndOfFile`.
+///
+/// The iterator is fused: after the end of the file or the first error,
+/// it only returns `None`.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_token() {
+            Ok(tok) if tok.kind == TokenKind::EndOfFile => {
+                self.finished = true;
+                None
+            }
+            Ok(tok) => Some(Ok(tok)),
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl FusedIterator for Lexer<'_> {}
+
+/// All the tokens of `input`, not including `EndOfFile`.
+///
+/// Lexing stops at the first error, use [`Lexer`] as an iterator
+/// to find out about errors.
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    Lexer::new(input).map_while(Result::ok).collect()
 }
 
 fn is_newline(ch: &char) -> bool {
     *ch == '\n' || *ch == '\0'
 }
 
-fn is_identifier(ch: char) -> bool {
-    ch.is_alphanumeric() || ch == '_'
+/// Whether a Vim9 line starting with `line` continues the previous line.
+///
+/// `:` only counts when followed by a blank, so that `:command` still
+/// starts a new command.
+fn starts_with_operator(line: &str) -> bool {
+    const OPERATORS: [&str; 13] = [
+        "->", "..", "&&", "||", "==", "!=", "=~", "!~", "+", "-", "*", "/",
+        "?",
+    ];
+
+    OPERATORS.iter().any(|op| line.starts_with(op))
+        || line.starts_with(": ")
+        || line.starts_with(":\t")
 }
 
-pub fn snapshot_lexing(input: &str) -> String {
-    let lexer = Lexer::new(input);
-
-    let mut tokens = VecDeque::new();
-    while let Ok(tok) = lexer.next_token() {
-        if tok.kind == TokenKind::EndOfFile {
-            break;
+/// Whitespace that does not end the line
+/// When the line that starts at offset `start` of `input` continues the line
+/// before it, the offset where the logical line picks up again.
+fn continued_line(input: &str, mut start: usize) -> Option<usize> {
+    loop {
+        let line = &input[start..];
+        let indent = line.len() - line.trim_start_matches(is_blank).len();
+        let rest = &line[indent..];
+
+        if rest.starts_with("\"\\ ") || rest.starts_with("#\\ ") {
+            start += indent + rest.find('\n')? + 1;
+        } else if rest.starts_with('\\') {
+            return Some(start + indent + 1);
+        } else if starts_with_operator(rest) {
+            return Some(start + indent);
+        } else {
+            return None;
         }
+    }
+}
 
-        if tok.kind == TokenKind::Illegal {
-            panic!("failure: {:#?}", input);
-        }
+fn is_blank(ch: char) -> bool {
+    ch.is_ascii_whitespace() && ch != '\n'
+}
+
+fn is_identifier(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
 
-        tokens.push_back(tok);
+pub fn snapshot_lexing(input: &str) -> String {
+    let mut tokens = VecDeque::from(tokenize(input));
+    if tokens.iter().any(|tok| tok.kind == TokenKind::Illegal) {
+        panic!("failure: {:#?}", input);
     }
 
     let mut output = String::new();
@@ -1163,6 +1736,9 @@ mod test {
     snapshot!(test_types, "../testdata/snapshots/types.vim");
     snapshot!(test_methods, "../testdata/snapshots/methods.vim");
     snapshot!(test_normal, "../testdata/snapshots/normal.vim");
+    snapshot!(test_numbers, "../testdata/snapshots/numbers.vim");
+    snapshot!(test_unicode, "../testdata/snapshots/unicode.vim");
+    snapshot!(test_continuation, "../testdata/snapshots/continuation.vim");
 
     // snapshot!(test_cfilter, "../testdata/snapshots/cfilter.vim");
 
@@ -1170,4 +1746,94 @@ mod test {
     snapshot!(test_matchparen, "../../shared/snapshots/matchparen.vim");
     snapshot!(test_handlers, "../../shared/snapshots/lsp_handlers.vim");
     snapshot!(test_selection, "../../shared/snapshots/lsp_selection.vim");
+
+    #[test]
+    fn test_iterator_is_fused() {
+        let mut lexer = Lexer::new("echo x\n");
+        let kinds = lexer.by_ref().map(|tok| tok.unwrap().kind);
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Identifier,
+                TokenKind::EndOfLine,
+            ]
+        );
+        assert!(lexer.next().is_none());
+
+        let mut lexer = Lexer::new("echo $\"{x\n");
+        assert!(lexer.by_ref().any(|tok| tok.is_err()));
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn test_operators_at_end_of_input() {
+        for input in [
+            "echo 1<CR>\n",
+            "x >",
+            "x ==\n",
+            "x !\n",
+            "x <\n",
+            "var f = 1.",
+            "x.",
+            "g:",
+        ] {
+            let lexer = Lexer::new(input);
+            assert!(lexer.into_iter().all(|tok| tok.is_ok()), "{:?}", input);
+        }
+    }
+
+    fn assert_lossless(input: &str) {
+        let lexer = Lexer::new(input).with_trivia();
+        let text = lexer.map(|tok| tok.unwrap().text.to_string());
+        assert_eq!(text.collect::<String>(), input);
+    }
+
+    #[test]
+    fn test_trivia_is_lossless() {
+        let files = [
+            include_str!("../testdata/snapshots/lexer.vim"),
+            include_str!("../testdata/snapshots/string.vim"),
+            include_str!("../testdata/snapshots/numbers.vim"),
+            include_str!("../testdata/snapshots/normal.vim"),
+            include_str!("../testdata/snapshots/unicode.vim"),
+            include_str!("../../shared/snapshots/matchparen.vim"),
+            include_str!("../../shared/snapshots/lsp_handlers.vim"),
+        ];
+
+        for file in files {
+            assert_lossless(file);
+        }
+
+        assert_lossless("let x = 1\n\t\\ + 2\n");
+    }
+
+    #[test]
+    fn test_trivia_tokens() {
+        let lexer = Lexer::new("echo 1\n  \\ + 2 # two\n").with_trivia();
+        let kinds = lexer.map(|tok| tok.unwrap().kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Whitespace,
+                TokenKind::Integer,
+                TokenKind::LineContinuation,
+                TokenKind::Whitespace,
+                TokenKind::Plus,
+                TokenKind::Whitespace,
+                TokenKind::Integer,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::EndOfLine,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("var x = 1");
+        let text = tokens.iter().map(|tok| tok.text.as_str());
+        assert_eq!(text.collect::<Vec<_>>(), vec!["var", "x", "=", "1"]);
+    }
 }
@@ -0,0 +1,17 @@
+let x = 1
+      \ + 2
+      "\ a comment in between
+      \ + 3
+var y = x
+  ->abs()
+  #\ another comment
+  .. "z"
+var z = true
+  ? 1
+  : 2
+:echo z
+var lines =<< trim END
+  \ not continued
+  + nor this
+END
+  + 4
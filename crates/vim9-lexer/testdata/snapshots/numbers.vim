@@ -0,0 +1,6 @@
+vim9script
+
+var x = 1'000 + 0x1F + 0o17 + 0b1010
+var y = 3.14 + 1.5e-3 + 2.0E+10
+var z = 0zFF00.ED
+var list = [1, 2][0]
@@ -5,3 +5,6 @@ var x = "hello"
 const CD_COMMAND: string = $'[lt]\=cd!\=\s\+-{END_OF_COMMAND}'
 const X_COMMAND: string = $"{x}"
 
+
+const ESCAPED = "a \"quoted\" string"
+const NESTED = $"a {{b}} {x .. $'{y}'} {{c}}"
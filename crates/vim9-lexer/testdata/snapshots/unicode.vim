@@ -0,0 +1,5 @@
+vim9script
+
+var café = "naïve → résumé"
+var emoji = '🦀' .. $"{café} ✓"
+normal! ü
@@ -1,86 +1,456 @@
 use anyhow::Result;
-use vim9_lexer::{Span, Token, TokenKind};
+use vim9_lexer::{Span, TokenKind};
 
-use crate::{Block, Body, ExCommand, Literal, Parser, TokenMeta};
+use crate::{
+    Block, Body, ExCommand, Literal, ParseError, Parser, SharedCommand,
+    TokenMeta,
+};
 
+/// `:augroup Name`, along with the commands up to `augroup END` or the
+/// next `augroup`, which switches to another group. See `:help :augroup`
 #[derive(Debug, PartialEq, Clone)]
 pub struct AugroupCommand {
     augroup: TokenMeta,
     pub augroup_name: Literal,
     augroup_eol: TokenMeta,
     pub body: Body,
-    augroup_end: TokenMeta,
-    augroup_end_name: TokenMeta,
-    augroup_end_eol: TokenMeta,
+    augroup_end: Option<AugroupEnd>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct AugroupEnd {
+    augroup: TokenMeta,
+    name: TokenMeta,
+    eol: TokenMeta,
+}
+
+/// `:augroup! Name`, which deletes the group
+#[derive(Debug, PartialEq, Clone)]
+pub struct AugroupDeleteCommand {
+    augroup: TokenMeta,
+    pub augroup_name: Literal,
+    eol: TokenMeta,
+}
+
+fn is_augroup_end(parser: &Parser) -> bool {
+    parser.command_match("augroup")
+        && parser.peek_ref(1).text.as_str().eq_ignore_ascii_case("END")
 }
 
 impl AugroupCommand {
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let augroup = parser.expect_identifier_with_text("augroup")?.into();
+        match parser.front_kind() {
+            TokenKind::Bang => {
+                parser.next_token();
+                return Ok(ExCommand::AugroupDelete(AugroupDeleteCommand {
+                    augroup,
+                    augroup_name: parser
+                        .expect_token(TokenKind::Identifier)?
+                        .try_into()?,
+                    eol: parser.expect_eol()?,
+                }));
+            }
+            // An `augroup END` without a group only switches back to the
+            // default group, which is where commands are outside of groups
+            _ if parser.front_text().eq_ignore_ascii_case("END") => {
+                parser.next_token();
+
+                let eol = parser.front_owned();
+                parser.expect_eol()?;
+                return Ok(match eol.kind {
+                    TokenKind::Comment => ExCommand::Comment(eol),
+                    _ => ExCommand::NoOp(eol),
+                });
+            }
+            _ => {}
+        }
+
         Ok(ExCommand::Augroup(AugroupCommand {
-            augroup: parser.expect_identifier_with_text("augroup")?.into(),
+            augroup,
             augroup_name: parser
                 .expect_token(TokenKind::Identifier)?
                 .try_into()?,
             augroup_eol: parser.expect_eol()?,
-            // TODO: This should be until augroup END, unless you can't have nested ones legally
-            body: Body::parse_until(parser, "augroup")?,
-            augroup_end: parser.expect_identifier_with_text("augroup")?.into(),
-            augroup_end_name: parser.expect_identifier_with_text("END")?.into(),
-            augroup_end_eol: parser.expect_eol()?,
+            body: {
+                let mut commands = vec![];
+                while !parser.command_match("augroup")
+                    && !parser.front_kind().is_eof()
+                {
+                    commands.push(parser.parse_command_recoverable()?);
+                }
+
+                Body { commands }
+            },
+            // Without an END, the group lasts until the next one
+            augroup_end: match is_augroup_end(parser) {
+                true => Some(AugroupEnd {
+                    augroup: parser.pop().into(),
+                    name: parser.pop().into(),
+                    eol: parser.expect_eol()?,
+                }),
+                false => None,
+            },
         }))
     }
 }
 
+/// `:autocmd`, see `:help :autocmd`
 #[derive(Debug, PartialEq, Clone)]
 pub struct AutocmdCommand {
     autocmd: TokenMeta,
+    /// `autocmd!` removes the matching autocmds before adding the new one
     pub bang: bool,
-    pub events: Vec<Literal>,
+    pub group: Option<String>,
+    /// The events, empty for `autocmd! Group` and `*` for every event
+    pub events: Vec<String>,
     pub pattern: AutocmdPattern,
-    pub block: AutocmdBlock,
+    pub once: bool,
+    pub nested: bool,
+    /// The command to run, `None` when `autocmd!` only removes autocmds
+    pub block: Option<AutocmdBlock>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AutocmdPattern {
+    /// The file patterns, empty when there is none
     Pattern(Vec<String>),
-    Buffer,
+    /// `<buffer>`, `<buffer=N>` or `<buffer=abuf>`
+    Buffer(AutocmdBuffer),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AutocmdBuffer {
+    Current,
+    Number(usize),
+    /// The buffer of the autocmd that is running, see `:help <abuf>`
+    Abuf,
 }
 
-fn tokens_are_neighbors(left: &Span, right: &Span) -> bool {
-    left.end_row == right.start_row && left.end_col == right.start_col
+const AUTOCMD_NAMES: &[&str] =
+    &["au", "aut", "auto", "autoc", "autocm", "autocmd"];
+
+/// The events of both vim and neovim, see `:help autocmd-events`
+const EVENTS: &[&str] = &[
+    "BufAdd",
+    "BufCreate",
+    "BufDelete",
+    "BufEnter",
+    "BufFilePost",
+    "BufFilePre",
+    "BufHidden",
+    "BufLeave",
+    "BufModifiedSet",
+    "BufNew",
+    "BufNewFile",
+    "BufRead",
+    "BufReadCmd",
+    "BufReadPost",
+    "BufReadPre",
+    "BufUnload",
+    "BufWinEnter",
+    "BufWinLeave",
+    "BufWipeout",
+    "BufWrite",
+    "BufWriteCmd",
+    "BufWritePost",
+    "BufWritePre",
+    "ChanInfo",
+    "ChanOpen",
+    "CmdUndefined",
+    "CmdlineChanged",
+    "CmdlineEnter",
+    "CmdlineLeave",
+    "CmdwinEnter",
+    "CmdwinLeave",
+    "ColorScheme",
+    "ColorSchemePre",
+    "CompleteChanged",
+    "CompleteDone",
+    "CompleteDonePre",
+    "CursorHold",
+    "CursorHoldI",
+    "CursorMoved",
+    "CursorMovedI",
+    "DiagnosticChanged",
+    "DiffUpdated",
+    "DirChanged",
+    "DirChangedPre",
+    "EncodingChanged",
+    "ExitPre",
+    "FileAppendCmd",
+    "FileAppendPost",
+    "FileAppendPre",
+    "FileChangedRO",
+    "FileChangedShell",
+    "FileChangedShellPost",
+    "FileEncoding",
+    "FileReadCmd",
+    "FileReadPost",
+    "FileReadPre",
+    "FileType",
+    "FileWriteCmd",
+    "FileWritePost",
+    "FileWritePre",
+    "FilterReadPost",
+    "FilterReadPre",
+    "FilterWritePost",
+    "FilterWritePre",
+    "FocusGained",
+    "FocusLost",
+    "FuncUndefined",
+    "GUIEnter",
+    "GUIFailed",
+    "InsertChange",
+    "InsertCharPre",
+    "InsertEnter",
+    "InsertLeave",
+    "InsertLeavePre",
+    "LspAttach",
+    "LspDetach",
+    "LspNotify",
+    "LspProgress",
+    "LspRequest",
+    "LspTokenUpdate",
+    "MenuPopup",
+    "ModeChanged",
+    "OptionSet",
+    "QuickFixCmdPost",
+    "QuickFixCmdPre",
+    "QuitPre",
+    "RecordingEnter",
+    "RecordingLeave",
+    "RemoteReply",
+    "SafeState",
+    "SafeStateAgain",
+    "SearchWrapped",
+    "SessionLoadPost",
+    "SessionWritePost",
+    "ShellCmdPost",
+    "ShellFilterPost",
+    "Signal",
+    "SourceCmd",
+    "SourcePost",
+    "SourcePre",
+    "SpellFileMissing",
+    "StdinReadPost",
+    "StdinReadPre",
+    "SwapExists",
+    "Syntax",
+    "TabClosed",
+    "TabEnter",
+    "TabLeave",
+    "TabNew",
+    "TabNewEntered",
+    "TermChanged",
+    "TermClose",
+    "TermEnter",
+    "TermLeave",
+    "TermOpen",
+    "TermRequest",
+    "TermResponse",
+    "TerminalOpen",
+    "TerminalWinOpen",
+    "TextChanged",
+    "TextChangedI",
+    "TextChangedP",
+    "TextChangedT",
+    "TextYankPost",
+    "UIEnter",
+    "UILeave",
+    "User",
+    "VimEnter",
+    "VimLeave",
+    "VimLeavePre",
+    "VimResized",
+    "VimResume",
+    "VimSuspend",
+    "WinClosed",
+    "WinEnter",
+    "WinLeave",
+    "WinNew",
+    "WinResized",
+    "WinScrolled",
+];
+
+/// Whether `word` is `*` or a list of events, which are not case sensitive
+fn is_event_list(word: &str) -> bool {
+    word == "*"
+        || word.split(',').all(|event| {
+            EVENTS.iter().any(|known| known.eq_ignore_ascii_case(event))
+        })
 }
 
 impl AutocmdCommand {
-    pub fn parse(parser: &Parser) -> Result<ExCommand> {
-        Ok(ExCommand::Autocmd(AutocmdCommand {
-            // TODO: Accept au! for example
-            autocmd: parser.expect_identifier_with_text("autocmd")?.into(),
-            bang: if parser.front_kind() == TokenKind::Bang {
-                parser.next_token();
+    pub fn matches(parser: &Parser) -> bool {
+        if !AUTOCMD_NAMES.contains(&&*parser.front_text()) {
+            return false;
+        }
+
+        // `au = 1` and the like are assignments to a variable
+        let front = parser.front_ref().span.clone();
+        let peek = parser.peek_ref(1);
+        match peek.kind {
+            TokenKind::EndOfLine | TokenKind::EndOfFile | TokenKind::Bang => {
                 true
-            } else {
-                false
-            },
-            events: {
-                let mut events = vec![];
+            }
+            TokenKind::Identifier | TokenKind::Mul => {
+                peek.span.start_col > front.end_col
+            }
+            _ => false,
+        }
+    }
 
-                loop {
-                    events.push(parser.pop().try_into()?);
-                    if parser.front_kind() != TokenKind::Comma {
-                        break;
-                    }
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        // The lexer does not know about patterns, so read the arguments
+        // from the source and skip the tokens up to the command
+        let span = parser.front_ref().span.clone();
+        let line = parser.source_line(span.start_row);
+        let rest: String = line.chars().skip(span.end_col).collect();
+        let (bang, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest.as_str()),
+        };
+
+        let mut words =
+            Words::new(rest, span.start_row, span.end_col + bang as usize);
+        let mut group = None;
+        let mut events = vec![];
+        let mut pattern = AutocmdPattern::Pattern(vec![]);
+        let mut once = false;
+        let mut nested = false;
+
+        if let Some(word) = words.peek() {
+            if !is_event_list(word) {
+                group = Some(word.to_string());
+                words.next();
+            }
+        }
+
+        if let Some(word) = words.peek() {
+            if is_event_list(word) {
+                events = word.split(',').map(str::to_string).collect();
+                words.next();
 
-                    parser.next_token();
+                if let Some(word) = words.peek() {
+                    pattern = AutocmdPattern::parse(word, words.span(word))?;
+                    words.next();
                 }
+            }
+        }
+
+        if events.is_empty() {
+            if let Some(word) = words.peek() {
+                return Err(ParseError::Invalid {
+                    message: format!("E216: No such group or event: {word}"),
+                    span: words.span(word),
+                }
+                .into());
+            }
+        }
+
+        while let Some(word) = words.peek() {
+            match word {
+                "++once" => once = true,
+                "++nested" | "nested" => nested = true,
+                _ => break,
+            }
+
+            words.next();
+        }
 
-                events
+        // Without a command, `autocmd` lists the autocmds, which is left
+        // to vim
+        let command_col = match words.peek() {
+            Some(word) => Some(words.span(word).start_col),
+            None if bang => None,
+            None => return SharedCommand::parse(parser),
+        };
+
+        let autocmd = parser.pop();
+        while !parser.front_kind().is_whitespace()
+            && parser.front_kind() != TokenKind::Comment
+            && command_col
+                .map_or(true, |col| parser.front_ref().span.start_col < col)
+        {
+            parser.pop();
+        }
+
+        Ok(ExCommand::Autocmd(AutocmdCommand {
+            autocmd: autocmd.into(),
+            bang,
+            group,
+            events,
+            pattern,
+            once,
+            nested,
+            block: match command_col {
+                Some(_) => Some(AutocmdBlock::parse(parser)?),
+                None => {
+                    parser.expect_eol()?;
+                    None
+                }
             },
-            pattern: AutocmdPattern::parse(parser)?,
-            block: AutocmdBlock::parse(parser)?,
         }))
     }
 }
 
+/// The words of the arguments of `:autocmd`, which are split by white space
+/// and end at a comment
+struct Words<'a> {
+    text: &'a str,
+    offset: usize,
+
+    /// Where `text` starts in the source, for the spans of the words
+    row: usize,
+    col: usize,
+}
+
+impl<'a> Words<'a> {
+    fn new(text: &'a str, row: usize, col: usize) -> Self {
+        let mut words = Words {
+            text,
+            offset: 0,
+            row,
+            col,
+        };
+        words.skip_whitespace();
+        words
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.offset..];
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        let rest = &self.text[self.offset..];
+        let word = rest.split(char::is_whitespace).next()?;
+        match word.is_empty() || word.starts_with('#') {
+            true => None,
+            false => Some(word),
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let word = self.peek()?;
+        self.offset += word.len();
+        self.skip_whitespace();
+        Some(word)
+    }
+
+    /// The span of `word`, which is the next word
+    fn span(&self, word: &str) -> Span {
+        let col = self.col + self.text[..self.offset].chars().count();
+        Span {
+            start_row: self.row,
+            start_col: col,
+            end_row: self.row,
+            end_col: col + word.chars().count(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum AutocmdBlock {
     Command(Box<ExCommand>),
@@ -96,39 +466,33 @@ impl AutocmdBlock {
     }
 }
 impl AutocmdPattern {
-    fn parse(parser: &Parser) -> Result<Self> {
-        Ok(if parser.front_kind() == TokenKind::AngleLeft {
-            parser.read_until(|t| {
-                matches!(t.kind, TokenKind::AngleRight | TokenKind::GreaterThan)
-            });
-
-            AutocmdPattern::Buffer
-        } else {
-            AutocmdPattern::Pattern({
-                let mut pattern = vec![];
-                let mut text = String::new();
-
-                loop {
-                    let tok = parser.pop();
-
-                    // Commas split different patterns
-                    if tok.kind == TokenKind::Comma {
-                        pattern.push(std::mem::take(&mut text));
-                        continue;
-                    }
-
-                    // Append the text of the token.
-                    text += tok.text.as_str();
+    fn parse(word: &str, span: Span) -> Result<Self> {
+        let buffer = match word.strip_prefix("<buffer") {
+            Some(buffer) => buffer,
+            None => {
+                return Ok(AutocmdPattern::Pattern(
+                    word.split(',').map(str::to_string).collect(),
+                ))
+            }
+        };
 
-                    if !tokens_are_neighbors(
-                        &tok.span,
-                        &parser.front_ref().span,
-                    ) {
-                        pattern.push(std::mem::take(&mut text));
-                        break pattern;
+        Ok(AutocmdPattern::Buffer(match buffer {
+            ">" => AutocmdBuffer::Current,
+            "=abuf>" => AutocmdBuffer::Abuf,
+            _ => match buffer
+                .strip_prefix('=')
+                .and_then(|buffer| buffer.strip_suffix('>'))
+                .and_then(|number| number.parse().ok())
+            {
+                Some(number) => AutocmdBuffer::Number(number),
+                None => {
+                    return Err(ParseError::Invalid {
+                        message: format!("E680: {word}: invalid buffer number"),
+                        span,
                     }
+                    .into())
                 }
-            })
-        })
+            },
+        }))
     }
 }
@@ -0,0 +1,336 @@
+use anyhow::Result;
+use vim9_lexer::TokenKind;
+
+use crate::{
+    DefCommand, ExCommand, Expression, Identifier, Parser, Precedence,
+    Signature, TokenMeta, Type,
+};
+
+/// See `:help vim9class`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassCommand {
+    abstract_: Option<TokenMeta>,
+    class: TokenMeta,
+    pub name: Identifier,
+    pub extends: Option<Identifier>,
+    pub implements: Vec<Identifier>,
+    eol: TokenMeta,
+    pub members: Vec<ClassMember>,
+    endclass: TokenMeta,
+    end_eol: TokenMeta,
+}
+
+impl ClassCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let opener = parser.command_start();
+
+        let abstract_ = match parser.command_match("abstract") {
+            true => Some(parser.pop().into()),
+            false => None,
+        };
+
+        let class = parser.expect_identifier_with_text("class")?.into();
+        let name = Identifier::parse(parser)?;
+
+        let mut extends = None;
+        let mut implements = vec![];
+        while parser.front_kind() == TokenKind::Identifier {
+            if parser.command_match("extends") {
+                parser.next_token();
+                extends = Some(Identifier::parse(parser)?);
+            } else {
+                parser.expect_identifier_with_text("implements")?;
+                implements.extend(parse_names(parser)?);
+            }
+        }
+
+        Ok(ExCommand::Class(ClassCommand {
+            abstract_,
+            class,
+            name,
+            extends,
+            implements,
+            eol: parser.expect_eol()?,
+            members: ClassMember::parse_until(
+                parser,
+                MemberContext::Class,
+                opener,
+                "endclass",
+            )?,
+            endclass: parser.expect_identifier_with_text("endclass")?.into(),
+            end_eol: parser.expect_eol()?,
+        }))
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.abstract_.is_some()
+    }
+}
+
+/// See `:help Interface`
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterfaceCommand {
+    interface: TokenMeta,
+    pub name: Identifier,
+    pub extends: Vec<Identifier>,
+    eol: TokenMeta,
+    pub members: Vec<ClassMember>,
+    endinterface: TokenMeta,
+    end_eol: TokenMeta,
+}
+
+impl InterfaceCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let opener = parser.command_start();
+
+        Ok(ExCommand::Interface(InterfaceCommand {
+            interface: parser.expect_identifier_with_text("interface")?.into(),
+            name: Identifier::parse(parser)?,
+            extends: match parser.command_match("extends") {
+                true => {
+                    parser.next_token();
+                    parse_names(parser)?
+                }
+                false => vec![],
+            },
+            eol: parser.expect_eol()?,
+            members: ClassMember::parse_until(
+                parser,
+                MemberContext::Interface,
+                opener,
+                "endinterface",
+            )?,
+            endinterface: parser
+                .expect_identifier_with_text("endinterface")?
+                .into(),
+            end_eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+/// See `:help Vim9-enum`
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumCommand {
+    enum_: TokenMeta,
+    pub name: Identifier,
+    pub implements: Vec<Identifier>,
+    eol: TokenMeta,
+    pub values: Vec<EnumValue>,
+    pub members: Vec<ClassMember>,
+    endenum: TokenMeta,
+    end_eol: TokenMeta,
+}
+
+/// One of the values of an enum, such as `Red` or `Large(10)`
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumValue {
+    pub name: Identifier,
+    pub args: Vec<Expression>,
+}
+
+impl EnumCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let opener = parser.command_start();
+
+        let enum_ = parser.expect_identifier_with_text("enum")?.into();
+        let name = Identifier::parse(parser)?;
+        let implements = match parser.command_match("implements") {
+            true => {
+                parser.next_token();
+                parse_names(parser)?
+            }
+            false => vec![],
+        };
+        let eol = parser.expect_eol()?;
+
+        Ok(ExCommand::Enum(EnumCommand {
+            enum_,
+            name,
+            implements,
+            eol,
+            values: EnumValue::parse_all(parser)?,
+            members: ClassMember::parse_until(
+                parser,
+                MemberContext::Class,
+                opener,
+                "endenum",
+            )?,
+            endenum: parser.expect_identifier_with_text("endenum")?.into(),
+            end_eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+impl EnumValue {
+    /// Parses the values at the start of an enum, which are separated by
+    /// commas and may be spread over several lines
+    fn parse_all(parser: &Parser) -> Result<Vec<EnumValue>> {
+        let mut values = vec![];
+        loop {
+            parser.skip_whitespace();
+            if parser.front_kind() != TokenKind::Identifier
+                || ClassMember::starts_member(parser)
+                || parser.command_match("endenum")
+            {
+                return Ok(values);
+            }
+
+            let name = Identifier::parse(parser)?;
+            let args = match parser.front_kind() {
+                TokenKind::LeftParen => {
+                    let args =
+                        parser.parse_expression_list(TokenKind::RightParen)?;
+                    parser.expect_token(TokenKind::RightParen)?;
+                    args
+                }
+                _ => vec![],
+            };
+
+            values.push(EnumValue { name, args });
+
+            if parser.front_kind() == TokenKind::Comma {
+                parser.next_token();
+            } else {
+                parser.skip_whitespace();
+                return Ok(values);
+            }
+        }
+    }
+}
+
+/// Which block the members are in, methods of an interface have no body
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum MemberContext {
+    Class,
+    Interface,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClassMember {
+    Var(ClassVar),
+    Method(ClassMethod),
+    /// A method without a body, from an interface or an `abstract def`
+    Abstract(MethodSignature),
+}
+
+/// `var x: number`, `public static var count = 0` or `final name: string`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassVar {
+    pub public: bool,
+    pub is_static: bool,
+    var: TokenMeta,
+    pub name: Identifier,
+    pub ty: Option<Type>,
+    pub expr: Option<Expression>,
+    eol: TokenMeta,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassMethod {
+    pub is_static: bool,
+    pub def: DefCommand,
+}
+
+/// The first line of a `def`, for methods that do not have a body
+#[derive(Debug, PartialEq, Clone)]
+pub struct MethodSignature {
+    def: TokenMeta,
+    pub name: Identifier,
+    pub args: Signature,
+    pub ret: Option<Type>,
+    eol: TokenMeta,
+}
+
+impl ClassMember {
+    const MODIFIERS: [&'static str; 3] = ["public", "static", "abstract"];
+
+    fn starts_member(parser: &Parser) -> bool {
+        let text = parser.front_ref().text.to_string();
+        Self::MODIFIERS.contains(&text.as_str())
+            || matches!(text.as_str(), "var" | "final" | "const" | "def")
+    }
+
+    fn parse_until(
+        parser: &Parser,
+        context: MemberContext,
+        opener: vim9_lexer::Span,
+        end: &str,
+    ) -> Result<Vec<ClassMember>> {
+        let mut members = vec![];
+        loop {
+            parser.skip_whitespace();
+            if parser.command_match(end) {
+                return Ok(members);
+            }
+
+            if parser.front_kind().is_eof() {
+                return Err(parser
+                    .unterminated_block(opener, vec![end.to_string()])
+                    .into());
+            }
+
+            members.push(Self::parse(parser, context)?);
+        }
+    }
+
+    fn parse(parser: &Parser, context: MemberContext) -> Result<ClassMember> {
+        let mut public = false;
+        let mut is_static = false;
+        let mut is_abstract = context == MemberContext::Interface;
+        while Self::MODIFIERS.contains(&parser.front_ref().text.as_str()) {
+            match parser.pop().text.as_str() {
+                "public" => public = true,
+                "static" => is_static = true,
+                _ => is_abstract = true,
+            }
+        }
+
+        let text = parser.front_ref().text.to_string();
+        Ok(match text.as_str() {
+            "var" | "final" | "const" => ClassMember::Var(ClassVar {
+                public,
+                is_static,
+                var: parser.pop().into(),
+                name: Identifier::parse(parser)?,
+                ty: match parser.front_kind() {
+                    TokenKind::SpacedColon => Some(Type::parse(parser)?),
+                    _ => None,
+                },
+                expr: match parser.front_kind() {
+                    TokenKind::Equal => {
+                        parser.next_token();
+                        Some(Expression::parse(parser, Precedence::Lowest)?)
+                    }
+                    _ => None,
+                },
+                eol: parser.expect_eol()?,
+            }),
+            "def" if is_abstract => ClassMember::Abstract(MethodSignature {
+                def: parser.pop().into(),
+                name: Identifier::parse(parser)?,
+                args: Signature::parse(parser)?,
+                ret: match parser.front_kind() {
+                    TokenKind::SpacedColon => Some(Type::parse(parser)?),
+                    _ => None,
+                },
+                eol: parser.expect_eol()?,
+            }),
+            "def" => ClassMember::Method(ClassMethod {
+                is_static,
+                def: DefCommand::parse_def(parser)?,
+            }),
+            _ => return Err(parser.unexpected_token(vec![]).into()),
+        })
+    }
+}
+
+/// Parses `Foo, Bar`, for the names after `implements`
+fn parse_names(parser: &Parser) -> Result<Vec<Identifier>> {
+    let mut names = vec![Identifier::parse(parser)?];
+    while parser.front_kind() == TokenKind::Comma {
+        parser.next_token();
+        names.push(Identifier::parse(parser)?);
+    }
+
+    Ok(names)
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use vim9_lexer::TokenKind;
+
+use crate::{
+    Body, DictAccess, ExCommand, Expression, Identifier, Parser, Precedence,
+    RawIdentifier, Signature, TokenMeta, TokenOwned,
+};
+
+/// A legacy `:function`, see `:help :function`
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionCommand {
+    function: TokenMeta,
+    pub bang: bool,
+    /// The name of the function, or `dict.name` for a function that is
+    /// stored in a dict
+    pub name: Expression,
+    pub args: Signature,
+    pub attributes: FunctionAttributes,
+    eol: TokenMeta,
+    pub body: Body,
+    endfunction: TokenMeta,
+    end_eol: TokenMeta,
+}
+
+/// The attributes that follow the arguments of a legacy function
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct FunctionAttributes {
+    pub abort: bool,
+    pub range: bool,
+    pub dict: bool,
+    pub closure: bool,
+}
+
+impl FunctionCommand {
+    pub fn matches(parser: &Parser) -> bool {
+        ["function", "func", "fun", "fu"]
+            .iter()
+            .any(|name| parser.command_match(name))
+            && matches!(
+                parser.peek_kind(),
+                TokenKind::Identifier | TokenKind::Bang
+            )
+    }
+
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let endings: HashSet<String> = HashSet::from_iter(
+            ["endfunction", "endfunc", "endfun", "endfu", "endf"]
+                .iter()
+                .map(|name| name.to_string()),
+        );
+
+        Ok(ExCommand::Function(FunctionCommand {
+            function: parser.pop().into(),
+            bang: match parser.front_kind() {
+                TokenKind::Bang => {
+                    parser.next_token();
+                    true
+                }
+                _ => false,
+            },
+            name: {
+                let mut name =
+                    Expression::Identifier(Identifier::parse(parser)?);
+                while parser.front_kind() == TokenKind::Dot {
+                    name = Expression::DictAccess(DictAccess {
+                        container: Box::new(name),
+                        dot: parser.pop().into(),
+                        index: RawIdentifier {
+                            name: parser.pop().text.to_string(),
+                        },
+                    });
+                }
+
+                name
+            },
+            args: Signature::parse_legacy(parser)?,
+            attributes: {
+                let mut attributes = FunctionAttributes::default();
+                while parser.front_kind() == TokenKind::Identifier {
+                    let attribute = match &*parser.front_text() {
+                        "abort" => &mut attributes.abort,
+                        "range" => &mut attributes.range,
+                        "dict" => &mut attributes.dict,
+                        "closure" => &mut attributes.closure,
+                        _ => break,
+                    };
+
+                    *attribute = true;
+                    parser.next_token();
+                }
+
+                attributes
+            },
+            eol: parser.expect_eol()?,
+            body: Body::parse_until_any(parser, &endings)?,
+            endfunction: parser.pop().into(),
+            end_eol: parser.expect_eol()?,
+        }))
+    }
+
+    /// Whether the function is called with `self`, which is implied when
+    /// it is defined as `dict.name`
+    pub fn is_dict(&self) -> bool {
+        self.attributes.dict || matches!(self.name, Expression::DictAccess(_))
+    }
+
+    /// Whether the function takes `...`
+    pub fn has_varargs(&self) -> bool {
+        self.args
+            .params
+            .iter()
+            .any(|param| param.name == Identifier::Ellipsis)
+    }
+}
+
+/// A legacy `:let`, see `:help :let`
+#[derive(Debug, PartialEq, Clone)]
+pub struct LetCommand {
+    let_: TokenMeta,
+    pub left: Expression,
+    /// `=`, or one of the operators that modify `left`, such as `+=`
+    pub operator: TokenOwned,
+    pub right: Expression,
+    eol: TokenMeta,
+}
+
+impl LetCommand {
+    pub fn matches(parser: &Parser) -> bool {
+        parser.command_match("let")
+            && matches!(
+                parser.peek_kind(),
+                TokenKind::Identifier | TokenKind::LeftBracket
+            )
+    }
+
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        Ok(ExCommand::Let(LetCommand {
+            let_: parser.expect_identifier_with_text("let")?.into(),
+            left: match parser.front_kind() {
+                TokenKind::LeftBracket => {
+                    Expression::Identifier(Identifier::parse(parser)?)
+                }
+                _ => Expression::parse(parser, Precedence::Lowest)?,
+            },
+            operator: {
+                let operators = vec![
+                    TokenKind::Equal,
+                    TokenKind::PlusEquals,
+                    TokenKind::MinusEquals,
+                    TokenKind::MulEquals,
+                    TokenKind::DivEquals,
+                    TokenKind::PercentEquals,
+                    TokenKind::StringConcatEquals,
+                ];
+                if !operators.contains(&parser.front_kind()) {
+                    return Err(parser.unexpected_token(operators).into());
+                }
+
+                parser.pop().into()
+            },
+            right: Expression::parse(parser, Precedence::Lowest)?,
+            eol: parser.expect_eol()?,
+        }))
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::Result;
+use vim9_lexer::{Lexer, TokenKind};
+
+use crate::{
+    Body, ExCommand, Expression, Parser, Precedence, SharedCommand, TokenMeta,
+};
+
+/// `:map`, `:noremap`, `:unmap` and `:mapclear`, along with their variants
+/// for each mode such as `:nnoremap`, see `:help map-commands`
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapCommand {
+    map: TokenMeta,
+    pub kind: MapKind,
+    /// The mode of the command, `n` in `nnoremap`, `!` for `map!` and
+    /// empty for `map`
+    pub mode: String,
+    pub args: MapArguments,
+    /// The keys that are mapped, `None` for `mapclear`
+    pub lhs: Option<String>,
+    /// What the keys are mapped to, `None` for `unmap` and `mapclear`
+    pub rhs: Option<MapRhs>,
+    eol: TokenMeta,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MapKind {
+    Map,
+    Noremap,
+    Unmap,
+    Clear,
+}
+
+/// See `:help :map-arguments`
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct MapArguments {
+    pub buffer: bool,
+    pub nowait: bool,
+    pub silent: bool,
+    pub script: bool,
+    pub expr: bool,
+    pub unique: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MapRhs {
+    /// Keys that are typed when the mapping is used
+    Keys(String),
+    /// The commands of `<ScriptCmd>...<CR>`, or of a `:` or `<Cmd>` command
+    /// that calls a script function. Script functions only exist in the
+    /// generated code, so these can not be typed as keys.
+    Commands(Body),
+    /// The expression of an `<expr>` mapping
+    Expr(Expression),
+}
+
+const MAP_MODES: &[&str] = &["n", "v", "x", "s", "o", "i", "l", "c", "t"];
+
+impl MapCommand {
+    /// The mode and kind of the map command called `name`
+    fn split_name(name: &str) -> Option<(&str, MapKind)> {
+        [
+            ("map", MapKind::Map),
+            ("noremap", MapKind::Noremap),
+            ("unmap", MapKind::Unmap),
+            ("mapclear", MapKind::Clear),
+        ]
+        .into_iter()
+        .find_map(|(command, kind)| match name.strip_suffix(command) {
+            Some(mode) if mode.is_empty() || MAP_MODES.contains(&mode) => {
+                Some((mode, kind))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn matches(parser: &Parser) -> bool {
+        if Self::split_name(&parser.front_text()).is_none() {
+            return false;
+        }
+
+        // `map(...)` is a function call and `map = ...` an assignment
+        let front = parser.front_ref().span.clone();
+        let peek = parser.peek_ref(1);
+        match peek.kind {
+            TokenKind::EndOfLine | TokenKind::EndOfFile | TokenKind::Bang => {
+                true
+            }
+            TokenKind::Equal => false,
+            _ => peek.span.start_col > front.end_col,
+        }
+    }
+
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let span = parser.front_ref().span.clone();
+        let (mut mode, kind) = Self::split_name(&parser.front_text())
+            .map(|(mode, kind)| (mode.to_string(), kind))
+            .unwrap();
+
+        // The lexer does not know about keys, so read them from the source
+        let line = parser.source_line(span.start_row);
+        let rest: String = line.chars().skip(span.end_col).collect();
+        let mut rest = match rest.strip_prefix('!') {
+            Some(rest) if mode.is_empty() => {
+                mode = "!".to_string();
+                rest
+            }
+            _ => rest.as_str(),
+        }
+        .trim_start();
+
+        let mut args = MapArguments::default();
+        loop {
+            let arg = match rest.split_once('>') {
+                Some((arg, _)) => arg,
+                None => break,
+            };
+
+            let flag = match arg {
+                "<buffer" => &mut args.buffer,
+                "<nowait" => &mut args.nowait,
+                "<silent" => &mut args.silent,
+                "<script" => &mut args.script,
+                "<expr" => &mut args.expr,
+                "<unique" => &mut args.unique,
+                _ => break,
+            };
+
+            *flag = true;
+            rest = rest[arg.len() + 1..].trim_start();
+        }
+
+        // A `|` ends the command, unless it is escaped
+        let (rest, bar) = split_bar(rest);
+        let rest = rest.replace("\\|", "|");
+        let rest = rest.as_str();
+
+        let (lhs, rhs) = match kind {
+            MapKind::Clear => (None, None),
+            MapKind::Unmap => (Some(rest.trim_end().to_string()), None),
+            MapKind::Map | MapKind::Noremap => {
+                let (lhs, rhs) =
+                    rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let rhs = rhs.trim_start();
+
+                // Without a rhs, the mappings that start with `lhs` are listed
+                if rhs.is_empty() {
+                    return SharedCommand::parse(parser);
+                }
+
+                (Some(lhs.to_string()), Some(MapRhs::parse(rhs, args.expr)))
+            }
+        };
+
+        // Without a lhs, all of the mappings are listed, or `unmap` fails
+        if lhs.as_ref().map_or(false, |lhs| lhs.is_empty()) {
+            return SharedCommand::parse(parser);
+        }
+
+        let map = parser.pop();
+
+        // Keys are not tokens, so continue after them in the source. A `|`
+        // ends the command like the end of the line does.
+        let end =
+            line.chars().count() - bar.map_or(0, |bar| bar.chars().count());
+        parser.skip_to(map.span.start_row, end);
+        let eol = match bar {
+            Some(_) => parser.pop().into(),
+            None => parser.expect_eol()?,
+        };
+
+        Ok(ExCommand::Map(MapCommand {
+            map: map.into(),
+            kind,
+            mode,
+            args,
+            lhs,
+            rhs,
+            eol,
+        }))
+    }
+}
+
+impl MapRhs {
+    fn parse(rhs: &str, expr: bool) -> MapRhs {
+        let parsed = match expr {
+            true => Self::parse_expr(rhs),
+            false => Self::parse_commands(rhs),
+        };
+
+        parsed.unwrap_or_else(|| MapRhs::Keys(rhs.to_string()))
+    }
+
+    fn parse_expr(rhs: &str) -> Option<MapRhs> {
+        let source = script_functions(rhs);
+        let lexer = Lexer::new(&source);
+        let parser = Parser::new(&lexer);
+
+        let expr = Expression::parse(&parser, Precedence::Lowest).ok()?;
+        parser
+            .front_kind()
+            .is_whitespace()
+            .then_some(MapRhs::Expr(expr))
+    }
+
+    fn parse_commands(rhs: &str) -> Option<MapRhs> {
+        let lower = rhs.to_ascii_lowercase();
+        let command = lower.strip_suffix("<cr>")?;
+        let (script_cmd, start) = if command.starts_with("<scriptcmd>") {
+            (true, "<scriptcmd>".len())
+        } else if command.starts_with("<cmd>") {
+            (false, "<cmd>".len())
+        } else if command.starts_with(":<c-u>") {
+            (false, ":<c-u>".len())
+        } else if command.starts_with(':') {
+            (false, 1)
+        } else {
+            return None;
+        };
+
+        let command = &rhs[start..command.len()];
+        if !script_cmd && !command.contains("<SID>") && !command.contains("s:")
+        {
+            return None;
+        }
+
+        let source = script_functions(command);
+        let lexer = Lexer::new(&source);
+        let parser = Parser::new(&lexer);
+        let (program, diagnostics) = parser.parse_program_with_diagnostics();
+
+        diagnostics.is_empty().then_some(MapRhs::Commands(Body {
+            commands: program.commands,
+        }))
+    }
+}
+
+/// Splits `text` at the first `|` that is not escaped with a backslash or
+/// CTRL-V, see `:help map-bar`
+fn split_bar(text: &str) -> (&str, Option<&str>) {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            '|' if !escaped => return (&text[..idx], Some(&text[idx..])),
+            '\\' | '\x16' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+
+    (text, None)
+}
+
+/// `<SID>Name` is the script function `s:Name`
+fn script_functions(text: &str) -> String {
+    text.replace("<SID>", "s:")
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use vim9_lexer::TokenKind;
+
+use crate::{ExCommand, Parser, TokenMeta};
+
+/// See `:help command-modifiers`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ModifierKind {
+    Aboveleft,
+    Belowright,
+    Botright,
+    Browse,
+    Confirm,
+    Hide,
+    Horizontal,
+    Keepalt,
+    Keepjumps,
+    Keepmarks,
+    Keeppatterns,
+    Legacy,
+    Lockmarks,
+    Noautocmd,
+    Noswapfile,
+    Sandbox,
+    Silent,
+    Tab,
+    Topleft,
+    Unsilent,
+    Verbose,
+    Vertical,
+    Vim9cmd,
+}
+
+/// Full name, shortest abbreviation and the modifier it is for
+const MODIFIERS: &[(&str, &str, ModifierKind)] = &[
+    ("aboveleft", "abo", ModifierKind::Aboveleft),
+    ("belowright", "bel", ModifierKind::Belowright),
+    ("botright", "bo", ModifierKind::Botright),
+    ("browse", "bro", ModifierKind::Browse),
+    ("confirm", "conf", ModifierKind::Confirm),
+    ("hide", "hid", ModifierKind::Hide),
+    ("horizontal", "hor", ModifierKind::Horizontal),
+    ("keepalt", "keepa", ModifierKind::Keepalt),
+    ("keepjumps", "keepj", ModifierKind::Keepjumps),
+    ("keepmarks", "kee", ModifierKind::Keepmarks),
+    ("keeppatterns", "keepp", ModifierKind::Keeppatterns),
+    ("leftabove", "lefta", ModifierKind::Aboveleft),
+    ("legacy", "leg", ModifierKind::Legacy),
+    ("lockmarks", "loc", ModifierKind::Lockmarks),
+    ("noautocmd", "noa", ModifierKind::Noautocmd),
+    ("noswapfile", "nos", ModifierKind::Noswapfile),
+    ("rightbelow", "rightb", ModifierKind::Belowright),
+    ("sandbox", "san", ModifierKind::Sandbox),
+    ("silent", "sil", ModifierKind::Silent),
+    ("tab", "tab", ModifierKind::Tab),
+    ("topleft", "to", ModifierKind::Topleft),
+    ("unsilent", "uns", ModifierKind::Unsilent),
+    ("verbose", "verb", ModifierKind::Verbose),
+    ("vertical", "vert", ModifierKind::Vertical),
+    ("vim9cmd", "vim9", ModifierKind::Vim9cmd),
+];
+
+impl ModifierKind {
+    /// The modifier named by `text`, which may be abbreviated
+    pub fn from_name(text: &str) -> Option<Self> {
+        MODIFIERS
+            .iter()
+            .find(|(name, short, _)| {
+                text.starts_with(short) && name.starts_with(text)
+            })
+            .map(|(_, _, kind)| *kind)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandModifier {
+    name: TokenMeta,
+    pub kind: ModifierKind,
+
+    /// Only used by `silent!`, which also hides errors
+    pub bang: bool,
+}
+
+impl CommandModifier {
+    /// The modifier at the front of the parser, if it is followed by a
+    /// command. `silent = 1` is an assignment, not a modifier.
+    fn matches(parser: &Parser) -> Option<ModifierKind> {
+        if parser.front_kind() != TokenKind::Identifier {
+            return None;
+        }
+
+        let kind = ModifierKind::from_name(parser.front_ref().text.as_str())?;
+
+        let mut next = parser.peek_nkind(1);
+        if kind == ModifierKind::Silent && next == TokenKind::Bang {
+            next = parser.peek_nkind(2);
+        }
+
+        matches!(next, TokenKind::Identifier | TokenKind::Colon).then_some(kind)
+    }
+
+    /// Parses all the modifiers before a command, along with the `:` that
+    /// may come after each of them.
+    pub fn parse_all(parser: &Parser) -> Result<Vec<CommandModifier>> {
+        let mut modifiers = vec![];
+        while let Some(kind) = Self::matches(parser) {
+            let name = parser.pop().into();
+            let bang = parser.front_kind() == TokenKind::Bang;
+            if bang {
+                parser.next_token();
+            }
+
+            if parser.front_kind() == TokenKind::Colon {
+                parser.next_token();
+            }
+
+            modifiers.push(CommandModifier { name, kind, bang });
+        }
+
+        Ok(modifiers)
+    }
+}
+
+/// A command run with modifiers, such as `silent! call Foo()`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModifiedCommand {
+    pub modifiers: Vec<CommandModifier>,
+    pub command: Box<ExCommand>,
+}
+
+impl ModifiedCommand {
+    pub fn has(&self, kind: ModifierKind) -> bool {
+        self.modifiers.iter().any(|m| m.kind == kind)
+    }
+}
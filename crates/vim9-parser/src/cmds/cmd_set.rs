@@ -0,0 +1,180 @@
+use std::{iter::Peekable, str::Chars};
+
+use anyhow::Result;
+use vim9_lexer::TokenKind;
+
+use crate::{ExCommand, Parser, SharedCommand, TokenMeta};
+
+/// `:set`, `:setlocal` and `:setglobal`, see `:help :set`
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetCommand {
+    set: TokenMeta,
+    pub scope: SetScope,
+    pub options: Vec<SetOption>,
+    eol: TokenMeta,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SetScope {
+    /// `:set`, which sets both the local and the global value
+    Both,
+    Local,
+    Global,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetOption {
+    /// The name of the option, without `no` or `inv`
+    pub name: String,
+    pub action: SetAction,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SetAction {
+    /// `opt`
+    Enable,
+    /// `noopt`
+    Disable,
+    /// `invopt` and `opt!`
+    Invert,
+    /// `opt&`
+    Default,
+    /// `opt=val` and `opt:val`
+    Assign(String),
+    /// `opt+=val`
+    Append(String),
+    /// `opt-=val`
+    Remove(String),
+    /// `opt^=val`
+    Prepend(String),
+}
+
+impl SetCommand {
+    pub fn matches(parser: &Parser) -> bool {
+        ["set", "setlocal", "setglobal"]
+            .iter()
+            .any(|name| parser.command_match(name))
+    }
+
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        // The lexer does not know about option values, so read them from
+        // the source
+        let scope = match &*parser.front_text() {
+            "setlocal" => SetScope::Local,
+            "setglobal" => SetScope::Global,
+            _ => SetScope::Both,
+        };
+
+        let options = {
+            let span = parser.front_ref().span.clone();
+            let line = parser.source_line(span.start_row);
+            let rest: String = line.chars().skip(span.end_col).collect();
+            parse_options(&rest)
+        };
+
+        // Listing options, `opt?`, `opt<` and the like are left to vim
+        let options = match options {
+            Some(options) if !options.is_empty() => options,
+            _ => return SharedCommand::parse(parser),
+        };
+
+        let set = parser.pop();
+        while !parser.front_kind().is_whitespace()
+            && parser.front_kind() != TokenKind::Comment
+        {
+            parser.pop();
+        }
+
+        Ok(ExCommand::Set(SetCommand {
+            set: set.into(),
+            scope,
+            options,
+            eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+/// Splits `text` into the options it sets, or `None` when one of them is
+/// not something that sets an option
+fn parse_options(text: &str) -> Option<Vec<SetOption>> {
+    let mut options = vec![];
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+
+        let mut name = String::new();
+        while let Some(ch) =
+            chars.next_if(|ch| ch.is_ascii_alphanumeric() || *ch == '_')
+        {
+            name.push(ch);
+        }
+
+        if name.is_empty() {
+            return match chars.peek() {
+                None | Some('#') | Some('"') => Some(options),
+                _ => None,
+            };
+        }
+
+        let operator: String = match chars.peek() {
+            Some('+' | '-' | '^') => chars.by_ref().take(2).collect(),
+            Some('!' | '&' | '=' | ':') => chars.next().into_iter().collect(),
+            _ => String::new(),
+        };
+
+        let action = match operator.as_str() {
+            "" => match name.strip_prefix("no") {
+                Some(rest) if !rest.is_empty() => {
+                    name = rest.to_string();
+                    SetAction::Disable
+                }
+                _ => match name.strip_prefix("inv") {
+                    Some(rest) if !rest.is_empty() => {
+                        name = rest.to_string();
+                        SetAction::Invert
+                    }
+                    _ => SetAction::Enable,
+                },
+            },
+            "!" => SetAction::Invert,
+            "&" => SetAction::Default,
+            "=" | ":" => SetAction::Assign(read_value(&mut chars)),
+            "+=" => SetAction::Append(read_value(&mut chars)),
+            "-=" => SetAction::Remove(read_value(&mut chars)),
+            "^=" => SetAction::Prepend(read_value(&mut chars)),
+            _ => return None,
+        };
+
+        // Anything else, such as `opt&vim` or `opt?`, is left to vim
+        match chars.peek() {
+            None => {}
+            Some(ch) if ch.is_whitespace() => {}
+            _ => return None,
+        }
+
+        // So are `all`, `termcap` and the terminal options, which are not
+        // options that can be set from lua
+        if name == "all" || name == "termcap" || name.starts_with("t_") {
+            return None;
+        }
+
+        options.push(SetOption { name, action });
+    }
+}
+
+/// Reads a value up to the next unescaped white space, see `:help
+/// option-backslash`
+fn read_value(chars: &mut Peekable<Chars>) -> String {
+    let mut value = String::new();
+    while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) => value.push(next),
+                None => value.push(ch),
+            },
+            ch => value.push(ch),
+        }
+    }
+
+    value
+}
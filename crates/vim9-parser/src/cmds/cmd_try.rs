@@ -1,15 +1,16 @@
 use std::collections::HashSet;
 
 use anyhow::Result;
+use vim9_lexer::TokenKind;
 
-use crate::{Body, ExCommand, Expression, Parser, TokenMeta};
+use crate::{Body, ExCommand, Expression, Parser, Precedence, TokenMeta};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct TryCommand {
     try_: TokenMeta,
     try_eol: TokenMeta,
     pub body: Body,
-    pub catch: Option<CatchCommand>,
+    pub catches: Vec<CatchCommand>,
     pub finally: Option<FinallyCommand>,
     endtry_: TokenMeta,
     endtry_eol: TokenMeta,
@@ -30,19 +31,25 @@ impl TryCommand {
             try_: parser.expect_identifier_with_text("try")?.into(),
             try_eol: parser.expect_eol()?,
             body: Body::parse_until_any(parser, &try_endings)?,
-            catch: {
-                if parser.front_text().eq("catch") {
-                    Some(CatchCommand {
-                        catch_: parser.pop().into(),
-                        expr: None,
-                        catch_eol: parser.expect_eol()?,
-                        body: Body::parse_until_any(parser, &try_endings)?,
+            catches: {
+                let mut catches = Vec::new();
+                while parser.command_match("catch") {
+                    catches.push(CatchCommand::parse(parser, &try_endings)?);
+                }
+
+                catches
+            },
+            finally: {
+                if parser.command_match("finally") {
+                    Some(FinallyCommand {
+                        finally_: parser.pop().into(),
+                        finally_eol: parser.expect_eol()?,
+                        body: Body::parse_until(parser, "endtry")?,
                     })
                 } else {
                     None
                 }
             },
-            finally: None,
             endtry_: parser.expect_identifier_with_text("endtry")?.into(),
             endtry_eol: parser.expect_eol()?,
         }))
@@ -52,10 +59,91 @@ impl TryCommand {
 #[derive(Debug, PartialEq, Clone)]
 pub struct CatchCommand {
     catch_: TokenMeta,
-    pub expr: Option<Expression>,
+    /// The pattern in `catch /pattern/`, matched against `v:exception`.
+    /// Without one, every exception is caught.
+    pub pattern: Option<String>,
     catch_eol: TokenMeta,
     pub body: Body,
 }
 
+impl CatchCommand {
+    fn parse(
+        parser: &Parser,
+        try_endings: &HashSet<String>,
+    ) -> Result<CatchCommand> {
+        let catch_: TokenMeta = parser.pop().into();
+
+        // The lexer does not know about patterns, so read it from the source
+        let line = parser.source_line(catch_.span.start_row);
+        let rest: String = line.chars().skip(catch_.span.end_col).collect();
+        let pattern = read_pattern(rest.trim_start());
+
+        while !parser.front_kind().is_whitespace()
+            && parser.front_kind() != TokenKind::Comment
+        {
+            parser.pop();
+        }
+
+        Ok(CatchCommand {
+            catch_,
+            pattern,
+            catch_eol: parser.expect_eol()?,
+            body: Body::parse_until_any(parser, try_endings)?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FinallyCommand {
+    finally_: TokenMeta,
+    finally_eol: TokenMeta,
+    pub body: Body,
+}
+
+/// See `:help :throw`
 #[derive(Debug, PartialEq, Clone)]
-pub struct FinallyCommand {}
+pub struct ThrowCommand {
+    throw_: TokenMeta,
+    pub expr: Expression,
+    eol: TokenMeta,
+}
+
+impl ThrowCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        Ok(ExCommand::Throw(ThrowCommand {
+            throw_: parser.expect_identifier_with_text("throw")?.into(),
+            expr: Expression::parse(parser, Precedence::Lowest)?,
+            eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+/// Reads `/pattern/`, where any character can be used in place of `/`
+fn read_pattern(text: &str) -> Option<String> {
+    let mut chars = text.chars();
+    let delimiter = match chars.next() {
+        None | Some('#') => return None,
+        Some(delimiter) => delimiter,
+    };
+
+    let mut pattern = String::new();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                // Only the escaped delimiter loses its backslash
+                match chars.next() {
+                    Some(next) if next == delimiter => pattern.push(next),
+                    Some(next) => {
+                        pattern.push(ch);
+                        pattern.push(next);
+                    }
+                    None => pattern.push(ch),
+                }
+            }
+            ch if ch == delimiter => break,
+            ch => pattern.push(ch),
+        }
+    }
+
+    Some(pattern)
+}
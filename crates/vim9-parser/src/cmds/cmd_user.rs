@@ -1,7 +1,7 @@
 use anyhow::Result;
 use vim9_lexer::TokenKind;
 
-use crate::{ExCommand, Parser, TokenMeta};
+use crate::{ExCommand, ParseError, Parser, TokenMeta};
 
 // TODO:
 // - consider how script vars are supposed to work in this context
@@ -37,7 +37,8 @@ impl UserCommand {
             parser.next_token();
             parser.ensure_token(TokenKind::Identifier)?;
 
-            match parser.pop().text.as_str() {
+            let attr = parser.pop();
+            match attr.text.as_str() {
                 "bar" => {
                     command_bar = true;
                 }
@@ -56,7 +57,13 @@ impl UserCommand {
                     parser.expect_token(TokenKind::Equal)?;
                     command_range = Some(parser.pop().text.to_string());
                 }
-                _ => panic!("OH NO"),
+                _ => {
+                    return Err(ParseError::UnsupportedCommand {
+                        command: format!("command -{}", attr.text.as_str()),
+                        span: attr.span,
+                    }
+                    .into())
+                }
             }
         }
 
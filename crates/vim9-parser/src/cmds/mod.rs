@@ -1,12 +1,18 @@
 use anyhow::Result;
-use vim9_lexer::TokenKind;
+use vim9_lexer::{Lexer, TokenKind};
 
 use crate::{
-    CallExpression, ExCommand, Expression, Parser, Precedence, TokenMeta,
+    CallExpression, ExCommand, Expression, Identifier, InnerType, ParseError,
+    Parser, Precedence, TokenMeta,
 };
 
 pub mod cmd_auto;
+pub mod cmd_class;
 pub mod cmd_if;
+pub mod cmd_legacy;
+pub mod cmd_map;
+pub mod cmd_modifier;
+pub mod cmd_set;
 pub mod cmd_try;
 pub mod cmd_user;
 
@@ -22,18 +28,13 @@ impl DeferCommand {
             defer_: parser.expect_identifier_with_text("defer")?.into(),
             call: {
                 // Parse up to the point it would be a call expr
-                let base = Expression::parse(parser, Precedence::Call)
-                    .expect("base")
-                    .into();
+                let base = Expression::parse(parser, Precedence::Call)?.into();
 
                 // Create the call expr from the first base expression
-                let right =
-                    CallExpression::parse(parser, base).expect("call").into();
+                let right = CallExpression::parse(parser, base)?;
 
                 // Closing on right paren, DO NOT advance
-                parser
-                    .expect_token(TokenKind::RightParen)
-                    .expect("rightparen");
+                parser.expect_token(TokenKind::RightParen)?;
 
                 right
             },
@@ -41,6 +42,152 @@ impl DeferCommand {
     }
 }
 
+/// See `:help :echoerr`
+#[derive(Debug, PartialEq, Clone)]
+pub struct EchoErrCommand {
+    echoerr: TokenMeta,
+    pub expr: Expression,
+    eol: TokenMeta,
+}
+
+impl EchoErrCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        Ok(ExCommand::EchoErr(EchoErrCommand {
+            echoerr: parser.expect_identifier_with_text("echoerr")?.into(),
+            expr: Expression::parse(parser, Precedence::Lowest)?,
+            eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+/// See `:help :unlet`
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnletCommand {
+    unlet: TokenMeta,
+    /// `unlet!`, which does not fail when a target does not exist
+    pub bang: bool,
+    /// Variables, such as `g:name` or `$ENV`, and items of lists and dicts,
+    /// such as `dict.key`, `list[i]` or `list[a : b]`
+    pub targets: Vec<Expression>,
+    eol: TokenMeta,
+}
+
+impl UnletCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let unlet = parser.expect_identifier_with_text("unlet")?.into();
+        let bang = match parser.front_kind() {
+            TokenKind::Bang => {
+                parser.next_token();
+                true
+            }
+            _ => false,
+        };
+
+        let mut targets = vec![];
+        while !parser.front_kind().is_whitespace()
+            && parser.front_kind() != TokenKind::Comment
+        {
+            targets.push(Expression::parse(parser, Precedence::Lowest)?);
+        }
+
+        Ok(ExCommand::Unlet(UnletCommand {
+            unlet,
+            bang,
+            targets,
+            eol: parser.expect_eol()?,
+        }))
+    }
+}
+
+/// `type Name = list<string>`, see `:help :type`
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeAliasCommand {
+    type_: TokenMeta,
+    pub name: Identifier,
+    equal: TokenMeta,
+    pub ty: InnerType,
+    eol: TokenMeta,
+}
+
+impl TypeAliasCommand {
+    pub fn parse(parser: &Parser) -> Result<ExCommand> {
+        let type_ = parser.expect_identifier_with_text("type")?.into();
+        let name_token = parser.front_owned();
+        let name = Identifier::parse(parser)?;
+        let alias = match &name {
+            Identifier::Raw(raw) => raw.name.clone(),
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![TokenKind::Identifier],
+                    span: name_token.span.clone(),
+                    found: name_token,
+                }
+                .into())
+            }
+        };
+
+        if !alias.starts_with(|ch: char| ch.is_ascii_uppercase()) {
+            return Err(ParseError::Invalid {
+                message: format!(
+                    "E1394: Type name must start with an uppercase letter: \
+                     {alias}"
+                ),
+                span: name_token.span,
+            }
+            .into());
+        }
+
+        let equal = parser.expect_token(TokenKind::Equal)?.into();
+        let ty = InnerType::parse(parser)?;
+        parser.type_aliases.borrow_mut().insert(alias, ty.clone());
+
+        Ok(ExCommand::TypeAlias(TypeAliasCommand {
+            type_,
+            name,
+            equal,
+            ty,
+            eol: parser.expect_eol()?,
+        }))
+    }
+
+    /// The aliases that are declared in `source`, along with whether they
+    /// are exported. Aliases are declared on a line of their own, so they
+    /// are found without parsing the rest of the script.
+    pub(crate) fn scan(source: &str) -> Vec<(bool, String, InnerType)> {
+        let mut aliases = vec![];
+        for line in source.lines() {
+            let line = line.trim_start();
+            let (exported, line) = match line.strip_prefix("export") {
+                Some(rest) if rest.starts_with(char::is_whitespace) => {
+                    (true, rest.trim_start())
+                }
+                _ => (false, line),
+            };
+
+            match line.strip_prefix("type") {
+                Some(rest) if rest.starts_with(char::is_whitespace) => {}
+                _ => continue,
+            }
+
+            let lexer = Lexer::new(line);
+            let parser = Parser::new(&lexer);
+            parser.type_aliases.borrow_mut().extend(aliases.iter().map(
+                |(_, name, ty): &(_, String, InnerType)| {
+                    (name.clone(), ty.clone())
+                },
+            ));
+
+            if let Ok(ExCommand::TypeAlias(alias)) = Self::parse(&parser) {
+                if let Identifier::Raw(raw) = alias.name {
+                    aliases.push((exported, raw.name, alias.ty));
+                }
+            }
+        }
+
+        aliases
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BreakCommand {
     pub br: TokenMeta,
@@ -0,0 +1,223 @@
+use std::fmt::Write;
+
+use vim9_lexer::Span;
+
+use crate::ParseError;
+
+/// A problem found while parsing.
+///
+/// Diagnostics are collected by [`crate::Parser::parse_program_with_diagnostics`]
+/// instead of aborting the whole parse on the first error.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+
+    /// Where the error is, or `None` for errors that do not point into the
+    /// source, like internal errors of the generator.
+    pub span: Option<Span>,
+
+    /// Secondary locations that help explain the error,
+    /// for example the `def` of a function that is missing its `enddef`.
+    pub labels: Vec<Label>,
+
+    /// The structured error, when the diagnostic came from a [`ParseError`].
+    pub error: Option<ParseError>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span: Some(span),
+            labels: vec![],
+            error: None,
+        }
+    }
+
+    /// An error for the whole file, reported without a location
+    pub fn without_location(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+            labels: vec![],
+            error: None,
+        }
+    }
+
+    pub fn with_label(
+        mut self,
+        span: Span,
+        message: impl Into<String>,
+    ) -> Self {
+        self.labels.push(Label {
+            message: message.into(),
+            span,
+        });
+        self
+    }
+
+    /// Render the diagnostic against `source`, the contents of `path`.
+    ///
+    /// ```text
+    /// error: unexpected end of file, expected `enddef`
+    ///  --> plugin/foo.vim:4:13
+    ///   |
+    /// 3 | def Foo()
+    ///   | --- unclosed block starts here
+    /// 4 |   echo "foo"
+    ///   |             ^
+    /// ```
+    ///
+    /// Without a span, only the message and the file are printed.
+    pub fn render(&self, path: &str, source: &str) -> String {
+        let span = match &self.span {
+            Some(span) => span,
+            None => return format!("error: {}\n --> {}\n", self.message, path),
+        };
+
+        let lines = source.lines().collect::<Vec<_>>();
+
+        // (span, label, is_primary), in source order
+        let mut marks = vec![(span, "", true)];
+        marks.extend(
+            self.labels
+                .iter()
+                .map(|l| (&l.span, l.message.as_str(), false)),
+        );
+        marks.sort_by_key(|(span, _, _)| (span.start_row, span.start_col));
+
+        let last_row = marks.iter().map(|(s, ..)| s.start_row).max().unwrap();
+        let width = (last_row + 1).to_string().len();
+        let gutter = " ".repeat(width);
+
+        let mut out = String::new();
+        writeln!(out, "error: {}", self.message).unwrap();
+        writeln!(
+            out,
+            "{}--> {}:{}:{}",
+            gutter,
+            path,
+            span.start_row + 1,
+            span.start_col + 1
+        )
+        .unwrap();
+        writeln!(out, "{} |", gutter).unwrap();
+
+        let mut prev_row: Option<usize> = None;
+        for (span, label, primary) in marks {
+            let row = span.start_row;
+            let line = lines.get(row).copied().unwrap_or("");
+
+            if prev_row != Some(row) {
+                if let Some(prev) = prev_row {
+                    if row > prev + 1 {
+                        writeln!(out, "...").unwrap();
+                    }
+                }
+
+                writeln!(out, "{:>width$} | {}", row + 1, line).unwrap();
+            }
+            prev_row = Some(row);
+
+            let len = if span.end_row == span.start_row {
+                span.end_col.saturating_sub(span.start_col)
+            } else {
+                line.chars().count().saturating_sub(span.start_col)
+            };
+
+            // Keep tabs so the underline lines up with the source
+            let indent = line
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .take(span.start_col)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+
+            let marker = if primary { "^" } else { "-" };
+            let mark =
+                format!("{}{} {}", indent, marker.repeat(len.max(1)), label);
+            writeln!(out, "{} | {}", gutter, mark.trim_end()).unwrap();
+        }
+
+        out
+    }
+
+    /// Render the diagnostic as a single line of JSON.
+    ///
+    /// Lines and columns are 1-based, like in the human readable output.
+    /// Diagnostics without a location have a `null` span.
+    pub fn to_json(&self, path: &str) -> String {
+        let labels = self
+            .labels
+            .iter()
+            .map(|l| {
+                format!(
+                    r#"{{"message":{},"span":{}}}"#,
+                    json_string(&l.message),
+                    json_span(&l.span)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            r#"{{"severity":"error","file":{},"message":{},"span":{},"labels":[{}]}}"#,
+            json_string(path),
+            json_string(&self.message),
+            self.span.as_ref().map_or("null".to_string(), json_span),
+            labels
+        )
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let diagnostic = Self::error(error.to_string(), error.span().clone());
+        let diagnostic = match &error {
+            ParseError::UnterminatedBlock { opener_span, .. } => diagnostic
+                .with_label(opener_span.clone(), "unclosed block starts here"),
+            _ => diagnostic,
+        };
+
+        Self {
+            error: Some(error),
+            ..diagnostic
+        }
+    }
+}
+
+fn json_span(span: &Span) -> String {
+    format!(
+        r#"{{"start":{{"line":{},"column":{}}},"end":{{"line":{},"column":{}}}}}"#,
+        span.start_row + 1,
+        span.start_col + 1,
+        span.end_row + 1,
+        span.end_col + 1
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).unwrap()
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use vim9_lexer::{Span, TokenKind};
+
+use crate::TokenOwned;
+
+/// Errors produced by the parser.
+///
+/// Every variant carries the [`Span`] of the offending source, so that it can
+/// be rendered against the original file.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    /// Found a token of a different kind than any of `expected`.
+    ///
+    /// `expected` is empty when the parser could not describe what it wanted.
+    UnexpectedToken {
+        expected: Vec<TokenKind>,
+        found: TokenOwned,
+        span: Span,
+    },
+
+    /// Found the wrong identifier, for example `endfor` when closing an `if`.
+    ExpectedKeyword {
+        expected: String,
+        found: TokenOwned,
+        span: Span,
+    },
+
+    /// Found a token that cannot start an expression.
+    ExpectedExpression { found: TokenOwned, span: Span },
+
+    /// Reached the end of the file before the block was closed.
+    UnterminatedBlock {
+        expected: Vec<String>,
+        opener_span: Span,
+        span: Span,
+    },
+
+    /// Valid vim syntax that we do not handle yet.
+    UnsupportedCommand { command: String, span: Span },
+
+    /// Syntax that vim rejects, with the message vim gives for it.
+    Invalid { message: String, span: Span },
+
+    /// Source that the lexer could not read, such as an unterminated
+    /// interpolated string.
+    InvalidToken { message: String, span: Span },
+}
+
+impl ParseError {
+    pub fn span(&self) -> &Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. } => span,
+            ParseError::ExpectedKeyword { span, .. } => span,
+            ParseError::ExpectedExpression { span, .. } => span,
+            ParseError::UnterminatedBlock { span, .. } => span,
+            ParseError::UnsupportedCommand { span, .. } => span,
+            ParseError::Invalid { span, .. } => span,
+            ParseError::InvalidToken { span, .. } => span,
+        }
+    }
+}
+
+fn describe(token: &TokenOwned) -> String {
+    if token.text.is_empty() || token.kind.is_whitespace() {
+        format!("{:?}", token.kind)
+    } else {
+        format!("{:?} `{}`", token.kind, token.text)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected, found, ..
+            } => match expected.as_slice() {
+                [] => write!(f, "unexpected {}", describe(found)),
+                [kind] => {
+                    write!(f, "expected {:?}, found {}", kind, describe(found))
+                }
+                kinds => write!(
+                    f,
+                    "expected one of {:?}, found {}",
+                    kinds,
+                    describe(found)
+                ),
+            },
+            ParseError::ExpectedKeyword {
+                expected, found, ..
+            } => {
+                write!(f, "expected `{}`, found {}", expected, describe(found))
+            }
+            ParseError::ExpectedExpression { found, .. } => {
+                write!(f, "expected an expression, found {}", describe(found))
+            }
+            ParseError::UnterminatedBlock { expected, .. } => {
+                let expected = expected
+                    .iter()
+                    .map(|e| format!("`{}`", e))
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+
+                write!(f, "unexpected end of file, expected {}", expected)
+            }
+            ParseError::UnsupportedCommand { command, .. } => {
+                write!(f, "unsupported command: {}", command)
+            }
+            ParseError::Invalid { message, .. } => write!(f, "{}", message),
+            ParseError::InvalidToken { message, .. } => {
+                write!(f, "invalid token: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,285 @@
+//! Decoding of the escapes allowed in double quoted strings, see `:help expr-quote`
+
+use std::fmt::Debug;
+
+/// The value of a string after decoding escapes.
+///
+/// Vim strings are bytes, not utf-8: `"\xff"` and `"\<Up>"` are both valid
+/// strings that are not valid utf-8.
+#[derive(Clone, PartialEq, Default)]
+pub struct StringBytes(pub Vec<u8>, pub Vec<TermKey>);
+
+/// A `"\<...>"` key that is only known to neovim, such as `<Cmd>` or
+/// `<F13>`, which is translated with `nvim_replace_termcodes` at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermKey {
+    /// The byte offset in the string that the key goes at
+    pub offset: usize,
+    /// The key notation, including the `<` and `>`
+    pub notation: String,
+}
+
+impl Debug for StringBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match std::str::from_utf8(&self.0) {
+            Ok(s) => write!(f, "{:?}", s)?,
+            Err(_) => write!(f, "b\"{}\"", self.0.escape_ascii())?,
+        }
+
+        for key in &self.1 {
+            write!(f, " + {}@{}", key.notation, key.offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Byte that starts the internal encoding of a special key
+const K_SPECIAL: u8 = 0x80;
+
+const MOD_MASK_SHIFT: u8 = 0x02;
+const MOD_MASK_CTRL: u8 = 0x04;
+const MOD_MASK_ALT: u8 = 0x08;
+const MOD_MASK_META: u8 = 0x10;
+const MOD_MASK_CMD: u8 = 0x80;
+
+enum Key {
+    Char(char),
+    /// Keys without a character, encoded as `K_SPECIAL` + two termcap bytes
+    Special([u8; 2]),
+}
+
+fn named_key(name: &str) -> Option<Key> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "nl" | "newline" | "linefeed" | "lf" => Key::Char('\n'),
+        "cr" | "return" | "enter" => Key::Char('\r'),
+        "tab" => Key::Char('\t'),
+        "esc" => Key::Char('\x1b'),
+        "space" => Key::Char(' '),
+        "lt" => Key::Char('<'),
+        "bslash" => Key::Char('\\'),
+        "bar" => Key::Char('|'),
+
+        "bs" | "backspace" => Key::Special(*b"kb"),
+        "del" | "delete" => Key::Special(*b"kD"),
+        "insert" => Key::Special(*b"kI"),
+        "up" => Key::Special(*b"ku"),
+        "down" => Key::Special(*b"kd"),
+        "left" => Key::Special(*b"kl"),
+        "right" => Key::Special(*b"kr"),
+        "home" => Key::Special(*b"kh"),
+        "end" => Key::Special(*b"@7"),
+        "pageup" => Key::Special(*b"kP"),
+        "pagedown" => Key::Special(*b"kN"),
+        "help" => Key::Special(*b"%1"),
+        "undo" => Key::Special(*b"&8"),
+        "f1" => Key::Special(*b"k1"),
+        "f2" => Key::Special(*b"k2"),
+        "f3" => Key::Special(*b"k3"),
+        "f4" => Key::Special(*b"k4"),
+        "f5" => Key::Special(*b"k5"),
+        "f6" => Key::Special(*b"k6"),
+        "f7" => Key::Special(*b"k7"),
+        "f8" => Key::Special(*b"k8"),
+        "f9" => Key::Special(*b"k9"),
+        "f10" => Key::Special(*b"k;"),
+        "f11" => Key::Special(*b"F1"),
+        "f12" => Key::Special(*b"F2"),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Key::Char(ch),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(key)
+}
+
+fn push_char(out: &mut Vec<u8>, ch: char) {
+    let mut buf = [0; 4];
+    out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+}
+
+/// Decodes the contents of `<...>` in `"\<...>"`, such as `C-x` or `S-Up`.
+fn decode_key(notation: &str) -> Option<Vec<u8>> {
+    let mut modifiers = 0;
+    let mut rest = notation;
+
+    // The key itself may be a `-`, as in `<C-->`
+    while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+        modifiers |= match rest.as_bytes()[0].to_ascii_lowercase() {
+            b's' => MOD_MASK_SHIFT,
+            b'c' => MOD_MASK_CTRL,
+            b'a' => MOD_MASK_ALT,
+            b'm' => MOD_MASK_META,
+            b'd' => MOD_MASK_CMD,
+            _ => return None,
+        };
+        rest = &rest[2..];
+    }
+
+    let mut key = named_key(rest)?;
+
+    // Ctrl is folded into the character when possible, `<C-a>` is just 0x01
+    if modifiers & MOD_MASK_CTRL != 0 {
+        if let Key::Char(ch) = key {
+            let ctrl = match ch {
+                'a'..='z' | 'A'..='Z' => {
+                    Some(ch.to_ascii_uppercase() as u8 & 0x1f)
+                }
+                '@' | '[' | '\\' | ']' | '^' | '_' => Some(ch as u8 & 0x1f),
+                '?' => Some(0x7f),
+                _ => None,
+            };
+
+            if let Some(ctrl) = ctrl {
+                key = Key::Char(ctrl as char);
+                modifiers &= !MOD_MASK_CTRL;
+            }
+        }
+    }
+
+    // Other modifiers have their own encodings for some keys, such as
+    // `<S-Up>`, which are left to neovim
+    let key = match (modifiers, key) {
+        (0, key) => key,
+        (MOD_MASK_SHIFT, Key::Char('\t')) => Key::Special(*b"kB"),
+        _ => return None,
+    };
+
+    let mut out = vec![];
+    match key {
+        Key::Char(ch) => push_char(&mut out, ch),
+        Key::Special(code) => {
+            out.push(K_SPECIAL);
+            out.extend_from_slice(&code);
+        }
+    }
+
+    Some(out)
+}
+
+/// Whether `<notation>` could be a key, which is left to neovim when it is
+/// not one that we know
+fn is_key_notation(notation: &str) -> bool {
+    !notation.is_empty()
+        && notation
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_'))
+}
+
+/// Reads up to `max` digits in `radix` from the front of `chars`
+fn take_digits(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    radix: u32,
+    max: usize,
+) -> Option<u32> {
+    let mut value = None;
+    for _ in 0..max {
+        match chars.peek().and_then(|ch| ch.to_digit(radix)) {
+            Some(digit) => {
+                value = Some(value.unwrap_or(0) * radix + digit);
+                chars.next();
+            }
+            None => break,
+        }
+    }
+
+    value
+}
+
+/// Decodes the text between the quotes of a double quoted string
+pub fn decode_double_quoted(raw: &str) -> StringBytes {
+    let mut out = vec![];
+    let mut keys = vec![];
+    let mut chars = raw.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            push_char(&mut out, ch);
+            continue;
+        }
+
+        let Some(escaped) = chars.next() else {
+            out.push(b'\\');
+            break;
+        };
+
+        match escaped {
+            'b' => out.push(0x08),
+            'e' => out.push(0x1b),
+            'f' => out.push(0x0c),
+            'n' => out.push(b'\n'),
+            'r' => out.push(b'\r'),
+            't' => out.push(b'\t'),
+            '0'..='7' => {
+                let mut value = escaped.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|ch| ch.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+
+                out.push(value as u8);
+            }
+            'x' | 'X' => match take_digits(&mut chars, 16, 2) {
+                Some(value) => out.push(value as u8),
+                None => push_char(&mut out, escaped),
+            },
+            'u' | 'U' => {
+                let max = if escaped == 'u' { 4 } else { 8 };
+                match take_digits(&mut chars, 16, max) {
+                    Some(value) => push_char(
+                        &mut out,
+                        char::from_u32(value).unwrap_or('\u{fffd}'),
+                    ),
+                    None => push_char(&mut out, escaped),
+                }
+            }
+            '<' => {
+                let mut notation = String::new();
+                let mut lookahead = chars.clone();
+                let closed = loop {
+                    match lookahead.next() {
+                        Some('>') => break true,
+                        Some(ch) => notation.push(ch),
+                        None => break false,
+                    }
+                };
+
+                match decode_key(&notation).filter(|_| closed) {
+                    Some(key) => {
+                        out.extend(key);
+                        chars = lookahead;
+                    }
+                    // Unknown keys are left alone by neovim, `"\<Nope>"`
+                    // is `<Nope>`
+                    None if closed && is_key_notation(&notation) => {
+                        keys.push(TermKey {
+                            offset: out.len(),
+                            notation: format!("<{notation}>"),
+                        });
+                        chars = lookahead;
+                    }
+                    None => out.push(b'<'),
+                }
+            }
+            // `\\`, `\"` and any other escaped character are just the character
+            _ => push_char(&mut out, escaped),
+        }
+    }
+
+    // A NUL byte ends the string, the same as in vim
+    if let Some(nul) = out.iter().position(|&b| b == 0) {
+        out.truncate(nul);
+        keys.retain(|key| key.offset <= nul);
+    }
+
+    StringBytes(out, keys)
+}
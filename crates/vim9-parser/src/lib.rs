@@ -2,9 +2,10 @@
 #![allow(dead_code)]
 
 use std::{
-    cell::{Ref, RefCell},
-    collections::{HashSet, VecDeque},
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
+    path::{Path, PathBuf},
 };
 
 use anyhow::Result;
@@ -14,11 +15,23 @@ use vim9_lexer::{Lexer, Span, Token, TokenKind};
 
 mod cmds;
 pub use cmds::{
-    cmd_auto::{AugroupCommand, AutocmdBlock, AutocmdCommand},
+    cmd_auto::{
+        AugroupCommand, AugroupDeleteCommand, AutocmdBlock, AutocmdBuffer,
+        AutocmdCommand, AutocmdPattern,
+    },
+    cmd_class::{
+        ClassCommand, ClassMember, ClassMethod, ClassVar, EnumCommand,
+        EnumValue, InterfaceCommand, MethodSignature,
+    },
     cmd_if::IfCommand,
-    cmd_try::TryCommand,
+    cmd_legacy::{FunctionAttributes, FunctionCommand, LetCommand},
+    cmd_map::{MapArguments, MapCommand, MapKind, MapRhs},
+    cmd_modifier::{CommandModifier, ModifiedCommand, ModifierKind},
+    cmd_set::{SetAction, SetCommand, SetOption, SetScope},
+    cmd_try::{CatchCommand, FinallyCommand, ThrowCommand, TryCommand},
     cmd_user::UserCommand,
-    BreakCommand, ContinueCommand, DeferCommand,
+    BreakCommand, ContinueCommand, DeferCommand, EchoErrCommand,
+    TypeAliasCommand, UnletCommand,
 };
 // pub use cmds::{
 //     cmd_auto::{AugroupCommand, AutocmdBlock, AutocmdCommand},
@@ -30,7 +43,19 @@ pub use cmds::{
 // };
 
 mod types;
-pub use types::{InnerType, Type};
+pub use types::{InnerFuncType, InnerType, Type, TypeArgs, TypeItem};
+
+mod range;
+pub use range::{Address, LineNumber, Range};
+
+mod diagnostic;
+pub use diagnostic::{Diagnostic, Label};
+
+mod error;
+pub use error::ParseError;
+
+mod escape;
+pub use escape::{decode_double_quoted, StringBytes, TermKey};
 
 #[derive(PartialEq, Clone)]
 pub struct TokenMeta {
@@ -139,26 +164,40 @@ pub enum ExCommand {
     Heredoc(Heredoc),
     Decl(DeclCommand),
     Echo(EchoCommand),
+    EchoErr(EchoErrCommand),
     Execute(ExecuteCommand),
     Return(ReturnCommand),
     Def(DefCommand),
+    Function(FunctionCommand),
+    Let(LetCommand),
+    Class(ClassCommand),
+    Interface(InterfaceCommand),
+    Enum(EnumCommand),
     If(IfCommand),
     For(ForCommand),
     While(WhileCommand),
     Try(TryCommand),
+    Throw(ThrowCommand),
     Call(CallCommand),
     Defer(DeferCommand),
+    Unlet(UnletCommand),
+    TypeAlias(TypeAliasCommand),
     Eval(EvalCommand),
     Finish(FinishCommand),
     Break(BreakCommand),
     Continue(ContinueCommand),
     Augroup(AugroupCommand),
+    AugroupDelete(AugroupDeleteCommand),
     Autocmd(AutocmdCommand),
     Statement(StatementCommand),
     UserCommand(UserCommand),
+    Map(MapCommand),
+    Set(SetCommand),
     SharedCommand(SharedCommand),
     ExportCommand(ExportCommand),
     ImportCommand(ImportCommand),
+    Modified(ModifiedCommand),
+    Error(ErrorCommand),
     Skip,
     EndOfFile,
 
@@ -192,6 +231,8 @@ impl Vim9ScriptCommand {
 pub struct DefCommand {
     def: TokenMeta,
     pub name: Identifier,
+    /// The type parameters of a generic function, `<T>` in `def Foo<T>()`
+    pub generics: Option<TypeArgs>,
     pub args: Signature,
     pub ret: Option<Type>,
     def_eol: TokenMeta,
@@ -202,9 +243,18 @@ pub struct DefCommand {
 
 impl DefCommand {
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
-        Ok(ExCommand::Def(DefCommand {
+        Ok(ExCommand::Def(Self::parse_def(parser)?))
+    }
+
+    /// Parses the `def`, without wrapping it in a command. Used for methods.
+    pub fn parse_def(parser: &Parser) -> Result<DefCommand> {
+        Ok(DefCommand {
             def: parser.expect_identifier_with_text("def")?.into(),
             name: Identifier::parse(parser)?,
+            generics: match parser.front_kind() {
+                TokenKind::AngleLeft => Some(TypeArgs::parse(parser)?),
+                _ => None,
+            },
             args: Signature::parse(parser)?,
             ret: {
                 if parser.front_kind() == TokenKind::SpacedColon {
@@ -217,7 +267,7 @@ impl DefCommand {
             body: Body::parse_until(parser, "enddef")?,
             enddef: parser.expect_identifier_with_text("enddef")?.into(),
             end_eol: parser.expect_eol()?,
-        }))
+        })
     }
 }
 
@@ -307,9 +357,7 @@ impl CallExpression {
             expr: left,
             open: parser.ensure_token(TokenKind::LeftParen)?,
             args: parser.parse_expression_list(TokenKind::RightParen)?,
-            close: parser
-                .ensure_token(TokenKind::RightParen)
-                .expect("[CallExpression::parse]"),
+            close: parser.ensure_token(TokenKind::RightParen)?,
         })
     }
 }
@@ -409,43 +457,122 @@ impl ImportCommand {
                     eol: parser.expect_eol()?,
                 }
             }
-            _ => unreachable!("{:?}", parser),
+            _ => {
+                return Err(parser
+                    .unexpected_token(vec![
+                        TokenKind::LeftBrace,
+                        TokenKind::Identifier,
+                        TokenKind::SingleQuoteString,
+                        TokenKind::DoubleQuoteString,
+                    ])
+                    .into())
+            }
         };
 
+        parser.import_type_aliases(&command);
         Ok(ExCommand::ImportCommand(command))
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct SharedCommand {
+    /// The lines the command works on, like the `'<,'>` in `'<,'>sort`
+    pub range: Option<Range>,
     pub contents: String,
     eol: TokenMeta,
 }
 
 impl SharedCommand {
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
-        let mut contents = String::new();
-        let mut prev_end = 0;
+        Self::parse_contents(parser, None)
+    }
+
+    /// Parses the rest of a command that started with `range`.
+    ///
+    /// The contents may be empty, `:5` on its own jumps to line 5.
+    pub fn parse_with_range(
+        parser: &Parser,
+        range: Range,
+    ) -> Result<ExCommand> {
+        Self::parse_contents(parser, Some(range))
+    }
+
+    fn parse_contents(
+        parser: &Parser,
+        range: Option<Range>,
+    ) -> Result<ExCommand> {
+        // The lexer does not know about the arguments of `:!` and the like,
+        // which go to the end of the line, so read them from the source
+        if let Some(range) = &range {
+            let span = range.span().clone();
+            let line = parser.source_line(span.end_row);
+            let rest: String = line.chars().skip(span.end_col).collect();
+            if rest.trim_start().starts_with(range::is_symbol_command) {
+                while !parser.front_kind().is_whitespace()
+                    && parser.front_kind() != TokenKind::Comment
+                {
+                    parser.pop();
+                }
+
+                return Ok(ExCommand::SharedCommand(SharedCommand {
+                    range: Some(range.clone()),
+                    contents: rest.trim().to_string(),
+                    eol: parser.expect_eol()?,
+                }));
+            }
+        }
+
+        let start = {
+            let span = &parser.front_ref().span;
+            (span.start_row, span.start_col)
+        };
+
+        let mut prev_end = match &range {
+            Some(range) => (range.span().end_row, range.span().end_col),
+            None => start,
+        };
         while !parser.front_kind().is_whitespace() {
             let tok = parser.pop();
 
-            if prev_end > tok.span.start_col {
-                panic!("failed to make shared command: {:#?}", parser);
+            if prev_end > (tok.span.start_row, tok.span.start_col) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![],
+                    span: tok.span.clone(),
+                    found: tok.into(),
+                }
+                .into());
             }
 
-            contents += " ".repeat(tok.span.start_col - prev_end).as_str();
-            contents += tok.text.as_str();
-
-            prev_end = tok.span.end_col;
+            prev_end = (tok.span.end_row, tok.span.end_col);
         }
 
+        // The tokens only tell where the command ends, its contents are the
+        // source with the blanks between the tokens kept as they are
+        let end = {
+            let span = &parser.front_ref().span;
+            (span.start_row, span.start_col)
+        };
+        let contents = parser.joined_source(start, end).trim_end().to_string();
+
         Ok(ExCommand::SharedCommand(SharedCommand {
+            range,
             contents,
             eol: parser.expect_eol()?,
         }))
     }
 }
 
+/// Placeholder for a command that failed to parse.
+///
+/// Only produced when parsing with diagnostics, see
+/// [`Parser::parse_program_with_diagnostics`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct ErrorCommand {
+    /// The source that was skipped while resyncing to the next line
+    pub span: Span,
+    pub diagnostic: Diagnostic,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct EchoCommand {
     echo: TokenMeta,
@@ -559,7 +686,14 @@ impl<'a> TryFrom<Token<'a>> for Literal {
         Ok(match token.kind {
             TokenKind::Identifier => Self { token },
             TokenKind::Mul => Self { token },
-            _ => unimplemented!("Not valid: {:#?}", token),
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![TokenKind::Identifier],
+                    span: token.span.clone(),
+                    found: token,
+                }
+                .into())
+            }
         })
     }
 }
@@ -572,7 +706,14 @@ impl<'a> TryFrom<TokenOwned> for Literal {
         Ok(match token.kind {
             TokenKind::Identifier => Self { token },
             TokenKind::Mul => Self { token },
-            _ => unimplemented!("Not valid: {:#?}", token),
+            _ => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![TokenKind::Identifier],
+                    span: token.span.clone(),
+                    found: token,
+                }
+                .into())
+            }
         })
     }
 }
@@ -619,7 +760,7 @@ impl StatementCommand {
     }
 
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
-        let expr = dbg!(Expression::parse(parser, Precedence::Lowest)?);
+        let expr = Expression::parse(parser, Precedence::Lowest)?;
         if parser.front_kind() == TokenKind::Equal {
             return Ok(ExCommand::Statement(StatementCommand::Assign(
                 AssignStatement {
@@ -640,7 +781,7 @@ impl StatementCommand {
         Ok(ExCommand::Statement(StatementCommand::Mutate(
             MutationStatement {
                 left: expr,
-                modifier: parser.pop(),
+                modifier: parser.pop().into(),
                 right: {
                     let right = parser.parse_expression(Precedence::Lowest)?;
                     parser.next_token();
@@ -678,22 +819,64 @@ impl Body {
         parser: &Parser,
         identifiers: &HashSet<String>,
     ) -> Result<Body> {
+        let opener = parser.command_start();
         let mut commands = vec![];
-        while !identifiers.contains(parser.front_text().as_str()) {
-            commands.push(parser.parse_command()?);
+        while !identifiers.contains(parser.front_ref().text.as_str()) {
+            if parser.front_kind().is_eof() {
+                let mut expected =
+                    identifiers.iter().cloned().collect::<Vec<_>>();
+                expected.sort();
+
+                return Err(parser.unterminated_block(opener, expected).into());
+            }
+
+            commands.push(parser.parse_command_recoverable()?);
         }
 
         Ok(Body { commands })
     }
 
     pub fn parse_until(parser: &Parser, identifier: &str) -> Result<Body> {
+        let opener = parser.command_start();
         let mut commands = vec![];
-        while parser.front_text().as_str() != identifier {
-            commands.push(parser.parse_command()?);
+        while !parser.command_match(identifier) {
+            if parser.front_kind().is_eof() {
+                return Err(parser
+                    .unterminated_block(opener, vec![identifier.to_string()])
+                    .into());
+            }
+
+            commands.push(parser.parse_command_recoverable()?);
         }
 
         Ok(Body { commands })
     }
+
+    /// Parses `{` and the commands on the following lines, up to the closing
+    /// `}`, for the body of a lambda
+    fn parse_block(parser: &Parser) -> Result<Body> {
+        let command_start = parser.command_start();
+        let opener = parser.expect_token(TokenKind::LeftBrace)?.span;
+        parser.expect_eol()?;
+
+        let mut commands = vec![];
+        while parser.front_kind() != TokenKind::RightBrace {
+            if parser.front_kind().is_eof() {
+                return Err(parser
+                    .unterminated_block(opener, vec!["}".to_string()])
+                    .into());
+            }
+
+            commands.push(parser.parse_command_recoverable()?);
+        }
+        // Like other expressions, this ends on its last token: `}`
+        parser.ensure_token(TokenKind::RightBrace)?;
+
+        // The lambda is still part of the command it started in
+        *parser.command_start.borrow_mut() = command_start;
+
+        Ok(Body { commands })
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -723,6 +906,40 @@ impl Signature {
             close: parser.expect_token(TokenKind::RightParen)?.into(),
         })
     }
+
+    /// Parses the arguments of a legacy function, which have no types and
+    /// may end with `...`
+    fn parse_legacy(parser: &Parser) -> Result<Signature> {
+        Ok(Self {
+            open: parser.expect_token(TokenKind::LeftParen)?.into(),
+            params: {
+                let mut params = Vec::new();
+                while parser.front_kind() != TokenKind::RightParen {
+                    params.push(match parser.front_kind() {
+                        TokenKind::Ellipsis => {
+                            parser.next_token();
+                            Parameter {
+                                this: None,
+                                name: Identifier::Ellipsis,
+                                ty: None,
+                                equal: None,
+                                default_val: None,
+                            }
+                        }
+                        _ => Parameter::parse(parser)?,
+                    });
+                    parser.skip_whitespace();
+                    if parser.front_kind() == TokenKind::Comma {
+                        parser.next_token();
+                        parser.skip_whitespace();
+                    }
+                }
+
+                params
+            },
+            close: parser.expect_token(TokenKind::RightParen)?.into(),
+        })
+    }
 }
 #[derive(Debug, PartialEq, Clone)]
 /// Parameter definitions
@@ -733,6 +950,9 @@ impl Signature {
 ///      {name} = {value}
 ///      {name}: {type} = {value}
 pub struct Parameter {
+    /// `this.` in `def new(this.name)`, which assigns the argument to
+    /// the object variable of the same name
+    this: Option<TokenMeta>,
     pub name: Identifier,
     pub ty: Option<Type>,
     equal: Option<TokenMeta>,
@@ -740,7 +960,23 @@ pub struct Parameter {
 }
 
 impl Parameter {
+    /// Whether this is a `this.name` parameter of a constructor
+    pub fn is_member(&self) -> bool {
+        self.this.is_some()
+    }
+
     fn parse(parser: &Parser) -> Result<Parameter> {
+        let this = match parser.command_match("this")
+            && parser.peek_kind() == TokenKind::Dot
+        {
+            true => {
+                let this = parser.pop().into();
+                parser.next_token();
+                Some(this)
+            }
+            false => None,
+        };
+
         let name = Identifier::parse(parser)?;
 
         let ty = if parser.front_kind() == TokenKind::SpacedColon {
@@ -759,6 +995,7 @@ impl Parameter {
         };
 
         Ok(Parameter {
+            this,
             name,
             ty,
             equal,
@@ -786,32 +1023,12 @@ impl Debug for Identifier {
     }
 }
 
-impl TryInto<VimScope> for Identifier {
-    type Error = anyhow::Error;
-
-    fn try_into(self) -> Result<VimScope, Self::Error> {
-        match self {
-            Identifier::Raw(raw) => Ok(match raw.name.as_str() {
-                "g" => VimScope::Global,
-                "v" => VimScope::VimVar,
-                "t" => VimScope::Tab,
-                "w" => VimScope::Window,
-                "b" => VimScope::Buffer,
-                "s" => VimScope::Script,
-                "l" => VimScope::Local,
-                _ => return Err(anyhow::anyhow!("invalid scope: {:?}", raw)),
-            }),
-            _ => Err(anyhow::anyhow!("must be a raw identifier")),
-        }
-    }
-}
-
 impl Identifier {
     pub fn is_valid_local(&self) -> bool {
         match self {
             Identifier::Raw(_) => true,
             Identifier::Scope(_) => false,
-            Identifier::Unpacked(_) => todo!(),
+            Identifier::Unpacked(_) => false,
             Identifier::Ellipsis => false,
         }
     }
@@ -827,34 +1044,42 @@ impl Identifier {
         }
 
         Ok(match parser.peek_kind() {
-            TokenKind::Colon => {
-                Identifier::Scope(ScopedIdentifier {
-                    scope: {
-                        // TODO: get the right scope
-                        parser.next_token();
-                        VimScope::Global
-                    },
-                    colon: parser.expect_token(TokenKind::Colon)?.into(),
-                    accessor: Identifier::parse_in_expression(parser)?.into(),
-                })
-            }
+            TokenKind::Colon => Identifier::Scope(ScopedIdentifier {
+                scope: {
+                    let name = parser.front_text().to_string();
+                    let scope =
+                        VimScope::from_name(&name).ok_or_else(|| {
+                            ParseError::Invalid {
+                                message: format!(
+                                    "E461: Illegal variable name: {name}:"
+                                ),
+                                span: parser.front_ref().span.clone(),
+                            }
+                        })?;
+                    parser.next_token();
+                    scope
+                },
+                colon: parser.expect_token(TokenKind::Colon)?.into(),
+                accessor: Identifier::parse_in_expression(parser)?.into(),
+            }),
             TokenKind::Ellipsis => Identifier::Ellipsis,
             _ => Identifier::Raw(RawIdentifier {
                 name: {
-                    let current = parser.front_owned();
-                    anyhow::ensure!(
-                        matches!(
-                            current.kind,
-                            TokenKind::Identifier
-                                | TokenKind::True
-                                | TokenKind::False
-                                | TokenKind::Null
-                        ),
-                        "{:#?}",
-                        current
-                    );
+                    if !matches!(
+                        parser.front_kind(),
+                        TokenKind::Identifier
+                            // `a:0`, `a:1`, ... in legacy functions
+                            | TokenKind::Integer
+                            | TokenKind::True
+                            | TokenKind::False
+                            | TokenKind::Null
+                    ) {
+                        return Err(parser
+                            .unexpected_token(vec![TokenKind::Identifier])
+                            .into());
+                    }
 
-                    current.text.to_string()
+                    parser.front_text().to_string()
                 },
             }),
         })
@@ -909,6 +1134,25 @@ pub enum VimScope {
     Script,
     Local,
     VimVar,
+    /// `a:`, the arguments of a legacy function
+    Argument,
+}
+
+impl VimScope {
+    /// The scope of `name:`, if `name` is one
+    pub fn from_name(name: &str) -> Option<VimScope> {
+        Some(match name {
+            "g" => VimScope::Global,
+            "v" => VimScope::VimVar,
+            "t" => VimScope::Tab,
+            "w" => VimScope::Window,
+            "b" => VimScope::Buffer,
+            "s" => VimScope::Script,
+            "l" => VimScope::Local,
+            "a" => VimScope::Argument,
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -938,25 +1182,166 @@ pub enum Expression {
 }
 
 #[derive(PartialEq, Clone)]
-pub struct VimNumber {
-    pub value: String,
+pub enum VimNumber {
+    Integer(i64),
+    Float(f64),
+    Blob(Vec<u8>),
 }
 
 impl Debug for VimNumber {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Number({})", self.value)
+        match self {
+            VimNumber::Integer(value) => write!(f, "Number({})", value),
+            VimNumber::Float(value) => write!(f, "Number({:?})", value),
+            VimNumber::Blob(bytes) => {
+                write!(f, "Blob(0z")?;
+                for byte in bytes {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl VimNumber {
+    /// Parses the text of a number token, with any `'` separators removed.
+    /// `span` is where the token is, for the errors.
+    pub fn parse(
+        kind: &TokenKind,
+        text: &str,
+        span: &Span,
+    ) -> Result<VimNumber, ParseError> {
+        let invalid = |message: String| ParseError::Invalid {
+            message,
+            span: span.clone(),
+        };
+
+        // Like vim, numbers that are too big are clamped instead of wrapping
+        let integer = |digits: &str, radix: u32| {
+            digits.chars().fold(0i64, |acc, ch| {
+                acc.saturating_mul(radix as i64)
+                    .saturating_add(ch.to_digit(radix).unwrap_or(0) as i64)
+            })
+        };
+
+        Ok(match kind {
+            TokenKind::Integer => VimNumber::Integer(integer(text, 10)),
+            TokenKind::HexInteger => {
+                VimNumber::Integer(integer(&text[2..], 16))
+            }
+            TokenKind::OctalInteger => {
+                VimNumber::Integer(integer(&text[2..], 8))
+            }
+            TokenKind::BinaryInteger => {
+                VimNumber::Integer(integer(&text[2..], 2))
+            }
+            TokenKind::Float => VimNumber::Float(
+                text.parse()
+                    .map_err(|_| invalid(format!("invalid float: {text}")))?,
+            ),
+            TokenKind::Blob => {
+                let digits = text[2..].replace('.', "");
+                if digits.len() % 2 != 0 {
+                    return Err(invalid(
+                        "E973: Blob literal should have an even number of \
+                         hex characters"
+                            .to_string(),
+                    ));
+                }
+
+                VimNumber::Blob(
+                    (0..digits.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| {
+                            invalid(format!("invalid blob: {text}"))
+                        })?,
+                )
+            }
+            _ => return Err(invalid(format!("not a number: {:?}", kind))),
+        })
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum VimString {
     SingleQuote(String),
-    DoubleQuote(String),
-    Interpolated(String),
-    InterpolatedLit(String),
+    DoubleQuote(StringBytes),
+    Interpolated(InterpolatedString),
     EnvironmentVariable(String),
 }
 
+/// `$"hello {name}"` or `$'hello {name}'`
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterpolatedString {
+    open: TokenMeta,
+    /// Whether this is a `$'...'` string, where escapes are not decoded
+    pub literal: bool,
+    pub parts: Vec<InterpolatedPart>,
+    close: TokenMeta,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum InterpolatedPart {
+    Literal(StringBytes),
+    Expression(Expression),
+}
+
+impl InterpolatedString {
+    pub fn parse(parser: &Parser) -> Result<InterpolatedString> {
+        let open = parser.front_owned();
+        let literal = open.kind == TokenKind::InterpolatedLiteralString;
+
+        let mut parts = vec![];
+        loop {
+            parser.next_token();
+            match parser.front_kind() {
+                TokenKind::InterpolatedChunk => {
+                    let text = parser
+                        .front_text()
+                        .replace("{{", "{")
+                        .replace("}}", "}");
+
+                    parts.push(InterpolatedPart::Literal(if literal {
+                        StringBytes(
+                            text.replace("''", "'").into_bytes(),
+                            vec![],
+                        )
+                    } else {
+                        decode_double_quoted(&text)
+                    }));
+                }
+                TokenKind::InterpolationStart => {
+                    parser.next_token();
+                    parts.push(InterpolatedPart::Expression(
+                        parser.parse_expression(Precedence::Lowest)?,
+                    ));
+                    parser.expect_peek(TokenKind::InterpolationEnd)?;
+                }
+                TokenKind::InterpolatedStringEnd => break,
+                _ => {
+                    return Err(parser
+                        .unexpected_token(vec![
+                            TokenKind::InterpolatedChunk,
+                            TokenKind::InterpolationStart,
+                            TokenKind::InterpolatedStringEnd,
+                        ])
+                        .into())
+                }
+            }
+        }
+
+        Ok(InterpolatedString {
+            open: open.into(),
+            literal,
+            parts,
+            close: parser.front_owned().into(),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Ternary {
     pub cond: Box<Expression>,
@@ -1009,7 +1394,7 @@ impl Lambda {
             arrow: parser.expect_token(TokenKind::Arrow)?.into(),
             body: {
                 if parser.front_kind() == TokenKind::LeftBrace {
-                    todo!("parse blocks correctly");
+                    Body::parse_block(parser)?
                 } else {
                     Body {
                         commands: {
@@ -1069,13 +1454,11 @@ impl IndexType {
                 // Negative { Slice ... }
                 parser.pop();
                 let colon = parser.front_owned();
-                anyhow::ensure!(
-                    colon.kind.is_colon(),
-                    "[IndexType] token: {:#?}, parser: {:#?}, slice: {:#?}",
-                    colon,
-                    parser,
-                    left
-                );
+                if !colon.kind.is_colon() {
+                    return Err(parser
+                        .unexpected_token(vec![TokenKind::Colon])
+                        .into());
+                }
 
                 // Move past the colon, so that we're on the expression to the right
                 parser.next_token();
@@ -1131,10 +1514,10 @@ impl KeyValue {
                     VimKey::Literal(parser.pop().try_into()?)
                 }
                 TokenKind::SingleQuoteString => VimKey::Literal(Literal {
-                    token: parser.pop(),
+                    token: parser.pop().into(),
                 }),
                 TokenKind::DoubleQuoteString => VimKey::Literal(Literal {
-                    token: parser.pop(),
+                    token: parser.pop().into(),
                 }),
                 TokenKind::LeftBracket => {
                     // Consume left bracket, we do not want this to parser
@@ -1151,7 +1534,16 @@ impl KeyValue {
 
                     expr
                 }
-                _ => unimplemented!("{:?}", parser),
+                _ => {
+                    return Err(parser
+                        .unexpected_token(vec![
+                            TokenKind::Identifier,
+                            TokenKind::SingleQuoteString,
+                            TokenKind::DoubleQuoteString,
+                            TokenKind::LeftBracket,
+                        ])
+                        .into())
+                }
             },
             colon: parser.expect_token(TokenKind::SpacedColon)?.into(),
             value: parser.parse_expression(Precedence::Lowest)?,
@@ -1330,10 +1722,14 @@ pub struct VarCommand {
 impl VarCommand {
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
         let var = parser.expect_token(TokenKind::Identifier)?;
-        anyhow::ensure!(matches!(
-            var.text.to_string().as_str(),
-            "var" | "const"
-        ));
+        if !matches!(var.text.as_str(), "var" | "const") {
+            return Err(ParseError::ExpectedKeyword {
+                expected: "var".to_string(),
+                span: var.span.clone(),
+                found: var,
+            }
+            .into());
+        }
 
         let var: TokenMeta = var.into();
 
@@ -1345,10 +1741,14 @@ impl VarCommand {
             TokenKind::EndOfFile => None,
             TokenKind::SpacedColon => Some(Type::parse(parser)?),
             _ => {
-                return Err(anyhow::anyhow!(
-                    "invalid type and/or equal for var: {:?}",
-                    parser
-                ))
+                return Err(parser
+                    .unexpected_token(vec![
+                        TokenKind::SpacedColon,
+                        TokenKind::Equal,
+                        TokenKind::HeredocOperator,
+                        TokenKind::EndOfLine,
+                    ])
+                    .into())
             }
         };
 
@@ -1399,7 +1799,12 @@ impl VarCommand {
                     }
 
                     if parser.front_kind() == TokenKind::EndOfFile {
-                        panic!("Failed to do the stuffs... {:?}", contents);
+                        return Err(parser
+                            .unterminated_block(
+                                parser.command_start(),
+                                vec![open.text],
+                            )
+                            .into());
                     }
 
                     parser.next_token();
@@ -1450,7 +1855,13 @@ impl VarCommand {
                     eol: parser.expect_eol()?,
                 }))
             }
-            _ => Err(anyhow::anyhow!("invalid next character: {:?}", parser)),
+            _ => Err(parser
+                .unexpected_token(vec![
+                    TokenKind::Equal,
+                    TokenKind::HeredocOperator,
+                    TokenKind::EndOfLine,
+                ])
+                .into()),
         }
     }
 }
@@ -1483,6 +1894,11 @@ pub struct CallCommand {
 }
 
 impl CallCommand {
+    /// Whether the function is called with `:call`, instead of on its own
+    pub fn has_call(&self) -> bool {
+        self.call.is_some()
+    }
+
     pub fn parse(parser: &Parser) -> Result<ExCommand> {
         Ok(ExCommand::Call(CallCommand {
             call: parser
@@ -1510,30 +1926,37 @@ mod prefix_expr {
     use super::*;
 
     pub fn parse_number(parser: &Parser) -> Result<Expression> {
-        anyhow::ensure!(matches!(
-            parser.front_kind(),
-            TokenKind::Integer | TokenKind::Float
-        ));
+        let number = VimNumber::parse(
+            &parser.front_kind(),
+            &parser.front_text(),
+            &parser.front_ref().span,
+        )?;
 
-        Ok(Expression::Number(VimNumber {
-            value: parser.front_text(),
-        }))
+        Ok(Expression::Number(number))
     }
 
     pub fn parse_identifier(parser: &Parser) -> Result<Expression> {
-        anyhow::ensure!(parser.front_kind() == TokenKind::Identifier);
+        if parser.front_kind() != TokenKind::Identifier {
+            return Err(parser
+                .unexpected_token(vec![TokenKind::Identifier])
+                .into());
+        }
 
         Ok(RawIdentifier {
-            name: parser.front_text(),
+            name: parser.front_text().to_string(),
         }
         .into())
     }
 
     pub fn parse_register(parser: &Parser) -> Result<Expression> {
-        anyhow::ensure!(parser.front_kind() == TokenKind::Register);
+        if parser.front_kind() != TokenKind::Register {
+            return Err(parser
+                .unexpected_token(vec![TokenKind::Register])
+                .into());
+        }
 
         Ok(Expression::Register(Register {
-            register: parser.front_text(),
+            register: parser.front_text().to_string(),
         }))
     }
 
@@ -1549,31 +1972,25 @@ mod prefix_expr {
 
     pub fn parse_single_string(parser: &Parser) -> Result<Expression> {
         Ok(Expression::String(VimString::SingleQuote(
-            parser.front_text(),
+            parser.front_text().to_string(),
         )))
     }
 
     pub fn parse_double_string(parser: &Parser) -> Result<Expression> {
         Ok(Expression::String(VimString::DoubleQuote(
-            parser.front_text(),
+            decode_double_quoted(&parser.front_text()),
         )))
     }
 
     pub fn parse_interpolated(parser: &Parser) -> Result<Expression> {
-        Ok(Expression::String(VimString::InterpolatedLit(
-            parser.front_text(),
-        )))
-    }
-
-    pub fn parse_interpolated_lit(parser: &Parser) -> Result<Expression> {
         Ok(Expression::String(VimString::Interpolated(
-            parser.front_text(),
+            InterpolatedString::parse(parser)?,
         )))
     }
 
     pub fn parse_env_var(parser: &Parser) -> Result<Expression> {
         Ok(Expression::String(VimString::EnvironmentVariable(
-            parser.front_text(),
+            parser.front_text().to_string(),
         )))
     }
 
@@ -1618,9 +2035,7 @@ mod prefix_expr {
         Ok(Expression::Dict(DictLiteral {
             open: parser.ensure_token(TokenKind::LeftBrace)?,
             elements: parser.parse_keyvalue_list(TokenKind::RightBrace)?,
-            close: parser
-                .ensure_token(TokenKind::RightBrace)
-                .expect("parse_dict_literal"),
+            close: parser.ensure_token(TokenKind::RightBrace)?,
         }))
     }
 
@@ -1631,15 +2046,18 @@ mod prefix_expr {
         }))
     }
 
+    /// A `:` can not start an expression, slices like `[:5]` are parsed
+    /// by [`IndexType`]
     pub fn parse_prefix_colon(parser: &Parser) -> Result<Expression> {
-        todo!(
-            "[Colon] I don't think we ever get this in reality? {:#?}",
-            parser
-        );
+        Err(ParseError::ExpectedExpression {
+            found: parser.front_owned(),
+            span: parser.error_span(),
+        }
+        .into())
     }
 
     pub fn parse_prefix_spaced_colon(parser: &Parser) -> Result<Expression> {
-        unreachable!("[SpacedColon] should not happen {:#?}", parser);
+        parse_prefix_colon(parser)
     }
 
     pub fn parse_expandable_sequence(parser: &Parser) -> Result<Expression> {
@@ -1718,7 +2136,7 @@ mod infix_expr {
         };
 
         Ok(Expression::Infix(InfixExpression {
-            token,
+            token: token.into(),
             operator,
             left,
             right: parser.parse_expression(prec)?.into(),
@@ -1750,18 +2168,13 @@ mod infix_expr {
             },
             right: {
                 // Parse up to the point it would be a call expr
-                let base = Expression::parse(parser, Precedence::Call)
-                    .expect("base")
-                    .into();
+                let base = Expression::parse(parser, Precedence::Call)?.into();
 
                 // Create the call expr from the first base expression
-                let right =
-                    CallExpression::parse(parser, base).expect("call").into();
+                let right = CallExpression::parse(parser, base)?.into();
 
                 // Closing on right paren, DO NOT advance
-                parser
-                    .ensure_token(TokenKind::RightParen)
-                    .expect("rightparen");
+                parser.ensure_token(TokenKind::RightParen)?;
 
                 right
             },
@@ -1789,10 +2202,8 @@ mod infix_expr {
         //  4. left open -> :5, : 5,            (illegal? g: var)
 
         // Attempt to determine if current val is => g:var, w:something, etc.
-        // TODO: Can i get rid of this clone?
-        if let Expression::Identifier(ident) = (*left).clone() {
-            let valid_scope: Result<VimScope> = ident.try_into();
-            if let Ok(scope) = valid_scope {
+        if let Expression::Identifier(Identifier::Raw(raw)) = left.as_ref() {
+            if let Some(scope) = VimScope::from_name(&raw.name) {
                 if parser.front_kind() == TokenKind::Colon {
                     return Ok(Expression::Identifier(Identifier::Scope(
                         ScopedIdentifier {
@@ -1809,28 +2220,29 @@ mod infix_expr {
         }
 
         let colon = parser.pop();
-        anyhow::ensure!(
-            matches!(colon.kind, TokenKind::Colon),
-            "token: {:?}, parser: {:?}",
-            colon,
-            parser
-        );
-
-        if parser.front_kind() == TokenKind::RightBracket {
-            return Ok(Expression::Slice(
-                VimSlice {
-                    start: Some(left),
-                    colon: colon.into(),
-                    finish: None,
-                }
-                .into(),
-            ));
+        if colon.kind != TokenKind::Colon {
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![TokenKind::Colon],
+                span: colon.span.clone(),
+                found: colon.into(),
+            }
+            .into());
         }
 
-        todo!(
-            "I don't think this should be possible anymore: {:?}",
-            parser
-        )
+        // Either way, ends on the closing `]`
+        let finish = match parser.front_kind() {
+            TokenKind::RightBracket => None,
+            _ => Some(Expression::parse(parser, Precedence::Lowest)?.into()),
+        };
+
+        Ok(Expression::Slice(
+            VimSlice {
+                start: Some(left),
+                colon: colon.into(),
+                finish,
+            }
+            .into(),
+        ))
     }
 
     pub fn parser_call_expr(
@@ -1868,31 +2280,146 @@ mod infix_expr {
 
 #[derive(Debug)]
 pub struct Parser<'a> {
-    lexer: &'a Lexer,
+    lexer: &'a Lexer<'a>,
     token_buffer: RefCell<VecDeque<Token<'a>>>,
+
+    /// Span of the last token that was consumed,
+    /// used to report errors that happen at the end of the file.
+    last_span: RefCell<Span>,
+
+    /// When set, commands that fail to parse are recorded in `diagnostics`
+    /// and replaced with an [`ErrorCommand`] instead of aborting the parse.
+    recover: Cell<bool>,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+
+    /// The errors of the lexer. The line of each one ends where the lexer
+    /// stopped, and lexing continues on the next line.
+    lexer_errors: RefCell<Vec<ParseError>>,
+
+    /// Span of the first token of the command that is currently being
+    /// parsed, used to point at the opener of unterminated blocks.
+    command_start: RefCell<Span>,
+
+    /// The types declared with `type Name = ...`, and those exported by
+    /// the imported scripts as `script.Name`, which types resolve to.
+    type_aliases: RefCell<HashMap<String, InnerType>>,
+
+    /// The path of the script, to find the scripts that it imports.
+    path: Option<PathBuf>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(lexer: &'a Lexer) -> Self {
-        let mut tokens = VecDeque::new();
-        tokens.push_back(lexer.next_token().unwrap());
-        tokens.push_back(lexer.next_token().unwrap());
-
-        Self {
-            token_buffer: RefCell::new(tokens),
+    pub fn new(lexer: &'a Lexer<'a>) -> Self {
+        let parser = Self {
+            token_buffer: RefCell::new(VecDeque::new()),
             lexer,
+            last_span: RefCell::new(Span::empty()),
+            recover: Cell::new(false),
+            diagnostics: RefCell::new(vec![]),
+            lexer_errors: RefCell::new(vec![]),
+            command_start: RefCell::new(Span::empty()),
+            type_aliases: RefCell::new(HashMap::new()),
+            path: None,
+        };
+
+        parser.fill_buffer(1);
+        parser
+    }
+
+    /// Parses the script at `path`, so that the types exported by the
+    /// scripts that it imports can be resolved
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Declares the type aliases of the script up front, so that they can be
+    /// used above their declaration, like in a `def` before the `type`
+    fn declare_type_aliases(&self) {
+        let aliases = TypeAliasCommand::scan(self.lexer.source());
+        self.type_aliases
+            .borrow_mut()
+            .extend(aliases.into_iter().map(|(_, name, ty)| (name, ty)));
+    }
+
+    /// Declares the type aliases that `import` makes available. Only
+    /// scripts with a path relative to this one can be found.
+    fn import_type_aliases(&self, import: &ImportCommand) {
+        let (file, prefix, names) = match import {
+            ImportCommand::ImportImplicit {
+                autoload: false,
+                file,
+                name,
+                ..
+            } => {
+                let prefix = match name {
+                    Some(Expression::Identifier(Identifier::Raw(raw))) => {
+                        raw.name.clone()
+                    }
+                    Some(_) => return,
+                    None => match Path::new(file).file_stem() {
+                        Some(stem) => stem.to_string_lossy().to_string(),
+                        None => return,
+                    },
+                };
+
+                (file, Some(prefix), vec![])
+            }
+            ImportCommand::ImportUnpacked { file, names, .. } => {
+                let names = names
+                    .iter()
+                    .filter_map(|name| match name {
+                        Identifier::Raw(raw) => Some(raw.name.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                (file, None, names)
+            }
+            _ => return,
+        };
+
+        let dir = match self.path.as_ref().and_then(|path| path.parent()) {
+            Some(dir) if file.starts_with("./") || file.starts_with("../") => {
+                dir
+            }
+            _ => return,
+        };
+
+        let source = match std::fs::read_to_string(dir.join(file)) {
+            Ok(source) => source,
+            Err(_) => return,
+        };
+
+        let mut aliases = self.type_aliases.borrow_mut();
+        for (exported, name, ty) in TypeAliasCommand::scan(&source) {
+            match &prefix {
+                _ if !exported => {}
+                Some(prefix) => {
+                    aliases.insert(format!("{prefix}.{name}"), ty);
+                }
+                None if names.contains(&name) => {
+                    aliases.insert(name, ty);
+                }
+                None => {}
+            }
         }
     }
 
-    pub fn pop(&self) -> TokenOwned {
+    pub fn pop(&self) -> Token<'a> {
         if self.token_buffer.borrow().len() <= 2 {
             self.fill_buffer(2);
         }
 
-        self.token_buffer.borrow_mut().pop_front().unwrap().into()
+        let token = self.token_buffer.borrow_mut().pop_front().unwrap();
+        if token.kind != TokenKind::EndOfFile {
+            *self.last_span.borrow_mut() = token.span.clone();
+        }
+
+        token
     }
 
-    fn front_ref(&self) -> Ref<'_, Token> {
+    fn front_ref(&self) -> Ref<'_, Token<'a>> {
         Ref::map(self.token_buffer.borrow(), |mi| &mi[0])
     }
 
@@ -1900,20 +2427,36 @@ impl<'a> Parser<'a> {
         (&self.token_buffer.borrow()[0]).into()
     }
 
-    fn front_text(&self) -> String {
-        self.token_buffer.borrow()[0].text.to_string()
+    /// The source of line `row`, for the parts of a command that the lexer
+    /// does not understand
+    fn source_line(&self, row: usize) -> &'a str {
+        self.lexer.line(row)
     }
 
-    fn front_kind(&self) -> TokenKind {
-        self.token_buffer.borrow()[0].kind.clone()
+    /// The source between two positions, with its line continuations joined
+    fn joined_source(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> String {
+        self.lexer.joined_source(start, end)
+    }
+
+    /// Continues parsing at column `col` of line `row`, for commands that
+    /// read their arguments from the source instead of from tokens
+    fn skip_to(&self, row: usize, col: usize) {
+        self.token_buffer.borrow_mut().clear();
+        self.lexer.skip_to(row, col);
+        self.fill_buffer(1);
     }
 
-    fn peek_text(&self) -> &str {
-        self.peek_ntext(1)
+    /// The text of the current token, without copying it
+    fn front_text(&self) -> Ref<'_, str> {
+        Ref::map(self.token_buffer.borrow(), |tokens| tokens[0].text.as_str())
     }
 
-    fn peek_ntext(&self, n: usize) -> &str {
-        todo!("peek_text")
+    fn front_kind(&self) -> TokenKind {
+        self.token_buffer.borrow()[0].kind.clone()
     }
 
     fn peek_kind(&self) -> TokenKind {
@@ -1921,7 +2464,7 @@ impl<'a> Parser<'a> {
     }
 
     fn peek_nkind(&self, n: usize) -> TokenKind {
-        self.peek_n(n).kind
+        self.peek_ref(n).kind.clone()
     }
 
     fn peek_real_kind(&self) -> TokenKind {
@@ -1934,11 +2477,11 @@ impl<'a> Parser<'a> {
 
     fn line_matches<F>(&self, f: F) -> bool
     where
-        F: Fn(&TokenOwned) -> bool,
+        F: Fn(&Token) -> bool,
     {
         let mut peek_index = 0;
         loop {
-            let tok = self.peek_n(peek_index);
+            let tok = self.peek_ref(peek_index);
             if tok.kind == TokenKind::EndOfLine
                 || tok.kind == TokenKind::EndOfFile
             {
@@ -1954,7 +2497,7 @@ impl<'a> Parser<'a> {
     fn line_contains_kind(&self, kind: TokenKind) -> bool {
         let mut peek_index = 0;
         loop {
-            let tok = self.peek_n(peek_index);
+            let tok = self.peek_ref(peek_index);
             if tok.kind == TokenKind::EndOfLine
                 || tok.kind == TokenKind::EndOfFile
             {
@@ -1969,11 +2512,11 @@ impl<'a> Parser<'a> {
 
     fn line_contains_any<F>(&self, f: F) -> bool
     where
-        F: Fn(&TokenOwned) -> bool,
+        F: Fn(&Token) -> bool,
     {
         let mut peek_index = 0;
         loop {
-            let tok = self.peek_n(peek_index);
+            let tok = self.peek_ref(peek_index);
             if tok.kind == TokenKind::EndOfLine
                 || tok.kind == TokenKind::EndOfFile
             {
@@ -1986,7 +2529,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn peek_non_whitespace(&self) -> (TokenOwned, bool) {
+    fn peek_non_whitespace(&self) -> (Token<'a>, bool) {
         // let mut peek_index = 1;
         // let mut peek_token = self.peek_token.clone();
         let mut peek_index = 1;
@@ -2016,14 +2559,16 @@ impl<'a> Parser<'a> {
         use TokenKind::*;
 
         Some(Box::new(match self.front_kind() {
-            Integer | Float => parse_number,
+            Integer | HexInteger | OctalInteger | BinaryInteger | Float
+            | Blob => parse_number,
             Identifier => parse_identifier,
             Register => parse_register,
             Ampersand => parse_vim_option,
             DoubleQuoteString => parse_double_string,
             SingleQuoteString => parse_single_string,
-            InterpolatedString => parse_interpolated,
-            InterpolatedLiteralString => parse_interpolated_lit,
+            InterpolatedString | InterpolatedLiteralString => {
+                parse_interpolated
+            }
             EnvironmentVariable => parse_env_var,
             LeftParen => parse_grouped_expr,
             LeftBracket => parse_array_literal,
@@ -2054,12 +2599,15 @@ impl<'a> Parser<'a> {
             t if t.is_comparison() => Precedence::LessGreater,
             TokenKind::RightBracket
             | TokenKind::RightBrace
-            | TokenKind::RightParen => Precedence::Lowest,
+            | TokenKind::RightParen
+            | TokenKind::InterpolationEnd => Precedence::Lowest,
             TokenKind::EndOfLine | TokenKind::EndOfFile => Precedence::Lowest,
 
             // We have to check new lines to see if we need to handle anything there.
             //  I'm not sure this is 100% great, but we'll leave it this way for now.
-            TokenKind::Identifier | TokenKind::Comment => Precedence::Lowest,
+            TokenKind::Identifier
+            | TokenKind::EnvironmentVariable
+            | TokenKind::Comment => Precedence::Lowest,
 
             // TODO: Not confident that this is the right level
             TokenKind::AngleLeft => Precedence::Lowest,
@@ -2076,9 +2624,9 @@ impl<'a> Parser<'a> {
                 Precedence::Lowest
             }
 
-            _ => {
-                panic!("Unexpected precendence kind: {:?} // {:#?}", kind, self)
-            }
+            // Not an operator, which ends the expression. The token is then
+            // reported by whatever expected something else.
+            _ => return None,
         })
     }
 
@@ -2127,8 +2675,7 @@ impl<'a> Parser<'a> {
             TokenKind::Dot => infix_expr::parse_dot_operator,
             TokenKind::QuestionMark => infix_expr::parse_ternary_expr,
             // TokenKind::SpacedColon => infix_expr::parser_index_type,
-            TokenKind::Identifier => return None,
-            _ => unimplemented!("get_infix_fn: {:#?}", self),
+            _ => return None,
         }))
     }
 
@@ -2143,10 +2690,11 @@ impl<'a> Parser<'a> {
         let mut left = match prefix {
             Some(prefix) => prefix(self)?,
             None => {
-                return Err(anyhow::anyhow!(
-                    "Failed to find prefix function for {:#?}",
-                    self
-                ));
+                return Err(ParseError::ExpectedExpression {
+                    found: self.front_owned(),
+                    span: self.error_span(),
+                }
+                .into());
             }
         };
 
@@ -2163,7 +2711,7 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
-    pub fn expect_eol(&self) -> Result<TokenMeta> {
+    pub fn expect_eol(&self) -> Result<TokenMeta, ParseError> {
         let curkind = self.front_kind();
         if curkind == TokenKind::EndOfLine
             || curkind == TokenKind::EndOfFile
@@ -2173,47 +2721,49 @@ impl<'a> Parser<'a> {
         {
             Ok(self.pop().into())
         } else {
-            Err(anyhow::anyhow!("expected eol or eof, got: {:?}", self))
+            Err(self.unexpected_token(vec![
+                TokenKind::EndOfLine,
+                TokenKind::EndOfFile,
+            ]))
         }
     }
 
-    pub fn ensure_token(&self, kind: TokenKind) -> Result<TokenMeta> {
-        let token = self.front_ref();
-        if token.kind != kind {
-            return Err(anyhow::anyhow!(
-                "[ensure_token] Got token: {:?}, Expected: {:?}",
-                token,
-                kind
-            ));
+    pub fn ensure_token(
+        &self,
+        kind: TokenKind,
+    ) -> Result<TokenMeta, ParseError> {
+        if self.front_kind() != kind {
+            return Err(self.unexpected_token(vec![kind]));
         }
 
-        Ok(token.into())
+        Ok(self.front_ref().into())
     }
 
-    pub fn ensure_peek(&self, kind: TokenKind) -> Result<TokenMeta> {
+    pub fn ensure_peek(
+        &self,
+        kind: TokenKind,
+    ) -> Result<TokenMeta, ParseError> {
         let tok = self.peek_n(1);
         if tok.kind != kind {
-            return Err(anyhow::anyhow!(
-                "[ensure_peek] Got token: {:?}, Expected: {:?}",
-                tok,
-                kind
-            ));
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![kind],
+                span: tok.span.clone(),
+                found: tok.into(),
+            });
         }
 
-        Ok(self.peek_n(1).into())
+        Ok(tok.into())
     }
 
     // TODO: Could possibly (if we care about the perf) write a copy of this function
     // that, instead of returning an owned, directly returns the metadata only
-    pub fn expect_token(&self, kind: TokenKind) -> Result<TokenOwned> {
+    pub fn expect_token(
+        &self,
+        kind: TokenKind,
+    ) -> Result<TokenOwned, ParseError> {
         let token = self.front_owned();
         if token.kind != kind {
-            return Err(anyhow::anyhow!(
-                "\n[expect_token] Got token: {:?}, Expected: {:?}\n{:#?}",
-                token,
-                kind,
-                self
-            ));
+            return Err(self.unexpected_token(vec![kind]));
         }
 
         self.next_token();
@@ -2221,37 +2771,47 @@ impl<'a> Parser<'a> {
     }
 
     /// Consumes the current
-    pub fn expect_peek(&self, kind: TokenKind) -> Result<TokenOwned> {
+    pub fn expect_peek(
+        &self,
+        kind: TokenKind,
+    ) -> Result<TokenOwned, ParseError> {
         let tok = self.peek();
         if tok.kind != kind {
-            return Err(anyhow::anyhow!(
-                "Got token: {:?}, Expected: {:?}",
-                tok,
-                kind
-            ));
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![kind],
+                span: tok.span.clone(),
+                found: tok.into(),
+            });
         }
 
         self.next_token();
         Ok(self.front_owned())
     }
 
-    pub fn expect_fn<F>(&self, f: F, consume: bool) -> Result<TokenOwned>
+    pub fn expect_fn<F>(
+        &self,
+        f: F,
+        consume: bool,
+    ) -> Result<TokenOwned, ParseError>
     where
         F: Fn(&TokenKind) -> bool,
     {
         self.expect_fn_token(|t| f(&t.kind), consume)
     }
 
-    pub fn expect_fn_token<F>(&self, f: F, consume: bool) -> Result<TokenOwned>
+    pub fn expect_fn_token<F>(
+        &self,
+        f: F,
+        consume: bool,
+    ) -> Result<TokenOwned, ParseError>
     where
         F: Fn(&Token) -> bool,
     {
-        let token = self.front_ref();
-        if !f(&token) {
-            return Err(anyhow::anyhow!("[expect_fn] Got token: {:?}", token));
+        if !f(&self.front_ref()) {
+            return Err(self.unexpected_token(vec![]));
         }
 
-        let token = token.into();
+        let token = self.front_owned();
         if consume {
             self.next_token();
         }
@@ -2263,36 +2823,38 @@ impl<'a> Parser<'a> {
         &self,
         kind: TokenKind,
         text: &str,
-    ) -> Result<TokenOwned> {
+    ) -> Result<TokenOwned, ParseError> {
         let token = self.front_owned();
-        if token.kind != kind {
-            return Err(anyhow::anyhow!(
-                "Got token: {:?}, Expected kind: {:?}",
-                token,
-                kind
-            ));
-        }
-
-        if !token.text.eq(text) {
-            return Err(anyhow::anyhow!(
-                "Got token: {:?}, Expected text: {:?}",
-                token,
-                text
-            ));
+        if token.kind != kind || !token.text.eq(text) {
+            return Err(ParseError::ExpectedKeyword {
+                expected: text.to_string(),
+                found: token,
+                span: self.error_span(),
+            });
         }
 
         self.next_token();
         Ok(token)
     }
 
+    /// Error for when the current token is not one of `expected`
+    fn unexpected_token(&self, expected: Vec<TokenKind>) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected,
+            found: self.front_owned(),
+            span: self.error_span(),
+        }
+    }
+
     pub fn peek_identifier_with_text(&self, text: &str) -> bool {
-        self.peek_nkind(1) == TokenKind::Identifier && self.peek_text().eq(text)
+        let token = self.peek_ref(1);
+        token.kind == TokenKind::Identifier && token.text.eq(text)
     }
 
     pub fn expect_identifier_with_text(
         &self,
         text: &str,
-    ) -> Result<TokenOwned> {
+    ) -> Result<TokenOwned, ParseError> {
         self.expect_token_with_text(TokenKind::Identifier, text)
     }
 
@@ -2305,23 +2867,21 @@ impl<'a> Parser<'a> {
         self.next_token();
     }
 
-    pub fn peek(&self) -> TokenOwned {
+    pub fn peek(&self) -> Token<'a> {
         self.peek_n(1)
     }
 
-    pub fn peek_n(&self, n: usize) -> TokenOwned {
-        let tokens = self.token_buffer.borrow();
-        match tokens.len() {
-            0 | 1 => panic!("Should always have a least 2 entries"),
-            len if len - 1 >= n => (&tokens[n]).into(),
-            len => {
-                drop(tokens);
-                self.fill_buffer(n);
+    pub fn peek_n(&self, n: usize) -> Token<'a> {
+        self.peek_ref(n).clone()
+    }
 
-                let tokens = self.token_buffer.borrow();
-                (&tokens[n]).into()
-            }
+    /// The token `n` ahead of the current one, without copying its text
+    fn peek_ref(&self, n: usize) -> Ref<'_, Token<'a>> {
+        if self.token_buffer.borrow().len() <= n {
+            self.fill_buffer(n);
         }
+
+        Ref::map(self.token_buffer.borrow(), |tokens| &tokens[n])
     }
 
     fn fill_buffer(&self, n: usize) {
@@ -2331,11 +2891,23 @@ impl<'a> Parser<'a> {
         for _ in 0..(n + 1 - len) {
             let tok = match self.lexer.next_token() {
                 Ok(tok) => tok,
-                Err(err) => Token {
-                    kind: TokenKind::EndOfFile,
-                    text: vim9_lexer::TokenText::Empty,
-                    span: Span::empty(),
-                },
+                // The line ends where the lexer stopped, and parsing
+                // continues on the next one
+                Err(err) => {
+                    let span = self.lexer.skip_line();
+                    self.lexer_errors.borrow_mut().push(
+                        ParseError::InvalidToken {
+                            message: err.to_string(),
+                            span: span.clone(),
+                        },
+                    );
+
+                    Token {
+                        kind: TokenKind::EndOfLine,
+                        text: vim9_lexer::TokenText::Empty,
+                        span,
+                    }
+                }
             };
 
             tokens.push_back(tok)
@@ -2352,17 +2924,24 @@ impl<'a> Parser<'a> {
             self.next_token();
         }
 
-        // TODO: Handle modifiers.
-        // Perhaps return as separate item/note for ExCommand, or return as tuple.
-        //  Could additionally be added as ExCommand parent type or something.
-        //  Will just be an annoying amount of writing :)
-        if self.front_ref().text.eq("silent") {
-            self.next_token();
-            if self.front_kind() == TokenKind::Bang {
-                self.next_token();
-            }
+        *self.command_start.borrow_mut() = self.front_ref().span.clone();
+
+        let modifiers = CommandModifier::parse_all(self)?;
+        let command = match Range::parse(self)? {
+            Some(range) => SharedCommand::parse_with_range(self, range)?,
+            None => self.parse_unmodified_command()?,
+        };
+        if modifiers.is_empty() {
+            return Ok(command);
         }
 
+        Ok(ExCommand::Modified(ModifiedCommand {
+            modifiers,
+            command: Box::new(command),
+        }))
+    }
+
+    fn parse_unmodified_command(&self) -> Result<ExCommand> {
         // For the following branches, you need to return early if it completely consumes
         // the last character and advances past.
         //
@@ -2390,6 +2969,19 @@ impl<'a> Parser<'a> {
                     CallCommand::parse(self)?
                 } else if self.command_match("def") {
                     DefCommand::parse(self)?
+                } else if FunctionCommand::matches(self) {
+                    FunctionCommand::parse(self)?
+                } else if LetCommand::matches(self) {
+                    LetCommand::parse(self)?
+                } else if self.command_match("class")
+                    || (self.command_match("abstract")
+                        && self.peek_ref(1).text.eq("class"))
+                {
+                    ClassCommand::parse(self)?
+                } else if self.command_match("interface") {
+                    InterfaceCommand::parse(self)?
+                } else if self.command_match("enum") {
+                    EnumCommand::parse(self)?
                 } else if self.command_match("if") {
                     return Ok(IfCommand::parse(self)?);
                 } else if self.command_match("return") {
@@ -2402,29 +2994,34 @@ impl<'a> Parser<'a> {
                     ImportCommand::parse(self)?
                 } else if self.command_match("augroup") {
                     AugroupCommand::parse(self)?
-                } else if self.command_match("autocmd") {
+                } else if AutocmdCommand::matches(self) {
                     AutocmdCommand::parse(self)?
                 } else if self.command_match("command") {
                     UserCommand::parse(self)?
-                } else if self.command_match("set")
-                    || self.command_match("setlocal")
-                {
-                    SharedCommand::parse(self)?
-                } else if self.command_match("nnoremap")
-                    || self.command_match("inoremap")
-                    || self.command_match("anoremenu")
+                } else if SetCommand::matches(self) {
+                    SetCommand::parse(self)?
+                } else if MapCommand::matches(self) {
+                    MapCommand::parse(self)?
+                } else if self.command_match("anoremenu")
                     || self.command_match("normal")
                 {
                     SharedCommand::parse(self)?
                 } else if self.command_match("unlet") {
-                    println!("TODO: UNLET");
-                    return Ok(SharedCommand::parse(self)?);
+                    UnletCommand::parse(self)?
+                } else if self.command_match("type")
+                    && self.peek_kind() == TokenKind::Identifier
+                {
+                    TypeAliasCommand::parse(self)?
                 } else if self.command_match("for") {
                     ForCommand::parse(self)?
                 } else if self.command_match("while") {
                     return Ok(WhileCommand::parse(self)?);
                 } else if self.command_match("try") {
                     TryCommand::parse(self)?
+                } else if self.command_match("throw") {
+                    ThrowCommand::parse(self)?
+                } else if self.command_match("echoerr") {
+                    EchoErrCommand::parse(self)?
                 } else if self.command_match("defer") {
                     DeferCommand::parse(self)?
                 } else if self.command_match("break") {
@@ -2446,7 +3043,6 @@ impl<'a> Parser<'a> {
                     //
                     // var sum = 1
                     // :sum = sum + 1
-                    println!("STATEMENT TIME");
                     StatementCommand::parse(self)?
                 } else if CallCommand::matches(self) {
                     CallCommand::parse(self)?
@@ -2461,25 +3057,151 @@ impl<'a> Parser<'a> {
         })
     }
 
-    pub fn parse_program(&self) -> Program {
+    /// Parses the whole program, stopping at the first error. An error of
+    /// the lexer is returned instead of the errors that it causes.
+    pub fn parse_program(&self) -> Result<Program> {
+        self.declare_type_aliases();
         let mut program = Program { commands: vec![] };
 
         while self.front_kind() != TokenKind::EndOfFile {
-            // let command = match self.parse_command() {
-            //     Ok(command) => command,
-            //     Err(err) => panic!(
-            //         "\nFailed to parse command.\nCurrent Commands:{:#?}.\nError: {}",
-            //         program, err
-            //     ),
-            // };
-
-            let command = self.parse_command().unwrap();
+            let command = self.parse_command();
+            if let Some(err) = self.lexer_errors.borrow().first() {
+                return Err(err.clone().into());
+            }
+
+            let command = command?;
+            if command != ExCommand::Skip {
+                program.commands.push(command);
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// Parses the whole program, without stopping at the first error.
+    ///
+    /// Every command that fails to parse is recorded as a [`Diagnostic`] and
+    /// replaced with an [`ExCommand::Error`], then parsing resumes at the
+    /// start of the next line.
+    pub fn parse_program_with_diagnostics(&self) -> (Program, Vec<Diagnostic>) {
+        self.recover.set(true);
+        self.declare_type_aliases();
+
+        let mut program = Program { commands: vec![] };
+        while self.front_kind() != TokenKind::EndOfFile {
+            let command = self
+                .parse_command_recoverable()
+                .expect("recoverable parsing does not return errors");
+
             if command != ExCommand::Skip {
                 program.commands.push(command);
             }
         }
 
-        program
+        self.recover.set(false);
+
+        // Errors of the lexer inside of commands that still parsed have not
+        // been reported yet
+        let mut diagnostics = self.diagnostics.take();
+        for err in self.lexer_errors.take() {
+            let diagnostic = err.into();
+            if !diagnostics.contains(&diagnostic) {
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        diagnostics.sort_by_key(|diagnostic| {
+            diagnostic
+                .span
+                .as_ref()
+                .map(|span| (span.start_row, span.start_col))
+        });
+
+        (program, diagnostics)
+    }
+
+    /// Parses a command, recovering from errors when the parser was started
+    /// with [`Parser::parse_program_with_diagnostics`].
+    ///
+    /// Nested bodies use this as well, so an error inside of a `def` does not
+    /// throw away the rest of the function.
+    pub fn parse_command_recoverable(&self) -> Result<ExCommand> {
+        if !self.recover.get() {
+            return self.parse_command();
+        }
+
+        let start = self.front_ref().span.clone();
+        match self.parse_command() {
+            Ok(command) => Ok(command),
+            Err(err) => Ok(ExCommand::Error(self.recover_from(start, err))),
+        }
+    }
+
+    fn recover_from(&self, start: Span, err: anyhow::Error) -> ErrorCommand {
+        let diagnostic = match err.downcast::<ParseError>() {
+            Ok(err) => err.into(),
+            Err(err) => Diagnostic::error(err.to_string(), self.error_span()),
+        };
+
+        // Resync at the next end of line
+        while !self.front_kind().is_whitespace() {
+            self.next_token();
+        }
+
+        let end = self.error_span();
+        if self.front_kind() == TokenKind::EndOfLine {
+            self.next_token();
+        }
+
+        let span = Span {
+            start_row: start.start_row,
+            start_col: start.start_col,
+            end_row: end.end_row,
+            end_col: end.end_col,
+        };
+
+        // A command that the lexer stopped in fails because of the error of
+        // the lexer, which is reported instead
+        let lexer_error = self
+            .lexer_errors
+            .borrow()
+            .iter()
+            .find(|err| span.contains(err.span()))
+            .cloned();
+        let diagnostic = match lexer_error {
+            Some(err) => err.into(),
+            None => diagnostic,
+        };
+
+        self.diagnostics.borrow_mut().push(diagnostic.clone());
+        ErrorCommand { span, diagnostic }
+    }
+
+    /// Span of the first token of the command that is currently being parsed
+    fn command_start(&self) -> Span {
+        self.command_start.borrow().clone()
+    }
+
+    fn unterminated_block(
+        &self,
+        opener_span: Span,
+        expected: Vec<String>,
+    ) -> ParseError {
+        ParseError::UnterminatedBlock {
+            expected,
+            opener_span,
+            span: self.error_span(),
+        }
+    }
+
+    /// Span of the current token, falling back to the last consumed token
+    /// at the end of the file (which does not have a real span).
+    fn error_span(&self) -> Span {
+        if self.front_kind().is_eof() {
+            self.last_span.borrow().clone()
+        } else {
+            self.front_ref().span.clone()
+        }
     }
 
     // Ends with the parser pointing to the close token as the current token
@@ -2511,8 +3233,7 @@ impl<'a> Parser<'a> {
             }
 
             // Must have comma
-            self.expect_token(TokenKind::Comma)
-                .expect("list_parser: comma");
+            self.expect_token(TokenKind::Comma)?;
 
             // Trailing commas are generally speaking accepted.
             //  I don't actually care that sometimes they aren't.
@@ -2533,7 +3254,7 @@ impl<'a> Parser<'a> {
             }
 
             if self.front_kind().is_eof() {
-                panic!("EOF");
+                return Err(self.unexpected_token(vec![close]).into());
             }
         }
 
@@ -2584,7 +3305,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn consume_if_kind(&self, kind: TokenKind) -> Option<TokenOwned> {
+    fn consume_if_kind(&self, kind: TokenKind) -> Option<Token<'a>> {
         if self.front_kind() == kind {
             Some(self.pop())
         } else {
@@ -2605,12 +3326,12 @@ impl<'a> Parser<'a> {
 fn snapshot_parsing(input: &str) -> String {
     let lexer = Lexer::new(input);
     let parser = Parser::new(&lexer);
-    let program = parser.parse_program();
+    let program = parser.parse_program().unwrap();
 
     format!("{:#?}", program.commands)
 }
 
-pub fn new_parser<'a>(lexer: &'a Lexer) -> Parser<'a> {
+pub fn new_parser<'a>(lexer: &'a Lexer<'a>) -> Parser<'a> {
     Parser::new(&lexer)
 }
 
@@ -2677,6 +3398,24 @@ mod test {
     snap!(test_import, "../testdata/snapshots/import.vim");
     snap!(test_autocmd, "../testdata/snapshots/autocmd.vim");
     snap!(test_unpack, "../testdata/snapshots/unpack.vim");
+    snap!(test_continuation, "../testdata/snapshots/continuation.vim");
+    snap!(test_modifiers, "../testdata/snapshots/modifiers.vim");
+    snap!(test_ranges, "../testdata/snapshots/ranges.vim");
+    snap!(test_types, "../testdata/snapshots/types.vim");
+    snap!(test_classes, "../testdata/snapshots/classes.vim");
+    snap!(test_block_lambda, "../testdata/snapshots/block_lambda.vim");
+    snap!(test_try, "../testdata/snapshots/try.vim");
+    snap!(test_throw, "../testdata/snapshots/throw.vim");
+    snap!(test_unlet, "../testdata/snapshots/unlet.vim");
+    snap!(test_legacy, "../testdata/snapshots/legacy.vim");
+    snap!(test_type_alias, "../testdata/snapshots/type_alias.vim");
+    snap!(test_map, "../testdata/snapshots/map.vim");
+    snap!(test_set, "../testdata/snapshots/set.vim");
+    snap!(
+        test_autocmd_options,
+        "../testdata/snapshots/autocmd_options.vim"
+    );
+    snap!(test_augroup, "../testdata/snapshots/augroup.vim");
 
     // https://github.com/yegappan/lsp test suite
     snap!(test_handlers, "../../shared/snapshots/lsp_handlers.vim");
@@ -2697,6 +3436,285 @@ mod test {
         assert_eq!(parser.peek_n(1).kind, TokenKind::EndOfLine);
     }
 
+    #[test]
+    fn test_parse_with_diagnostics() {
+        let input = r#"vim9script
+command -nosuchattr Foo echo "foo"
+def Bar()
+  import
+  echo "bar"
+enddef
+var x 5
+var y = 5
+"#;
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (program, diagnostics) = parser.parse_program_with_diagnostics();
+
+        assert_eq!(diagnostics.len(), 3, "{:#?}", diagnostics);
+        assert_eq!(diagnostics[0].span.as_ref().unwrap().start_row, 1);
+        assert_eq!(diagnostics[1].span.as_ref().unwrap().start_row, 3);
+        assert_eq!(diagnostics[2].span.as_ref().unwrap().start_row, 6);
+
+        // The def is still parsed, with the broken line replaced
+        let def = program
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                ExCommand::Def(def) => Some(def),
+                _ => None,
+            })
+            .expect("def is parsed");
+        assert!(matches!(def.body.commands[0], ExCommand::Error(_)));
+        assert!(matches!(def.body.commands[1], ExCommand::Echo(_)));
+
+        // Parsing continues after the last error
+        assert!(matches!(program.commands.last(), Some(ExCommand::Var(_))));
+    }
+
+    #[test]
+    fn test_parse_error_kinds() {
+        let input = r#"vim9script
+command -nosuchattr Foo echo "foo"
+var x 5
+def Bar()
+  echo "bar"
+"#;
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+
+        let errors = diagnostics
+            .into_iter()
+            .map(|d| d.error.expect("structured error"))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3, "{:#?}", errors);
+
+        assert!(matches!(
+            &errors[0],
+            ParseError::UnsupportedCommand { command, .. }
+                if command == "command -nosuchattr"
+        ));
+        assert!(matches!(
+            &errors[1],
+            ParseError::UnexpectedToken { found, .. }
+                if found.kind == TokenKind::Integer
+        ));
+        match &errors[2] {
+            ParseError::UnterminatedBlock {
+                expected,
+                opener_span,
+                ..
+            } => {
+                assert_eq!(expected, &vec!["enddef".to_string()]);
+                assert_eq!(opener_span.start_row, 3);
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_address() {
+        let input = "vim9script\n:\\&d\n:1,\\&d\n";
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+
+        let errors = diagnostics
+            .into_iter()
+            .map(|d| d.error.expect("structured error"))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        for (error, (row, col)) in errors.iter().zip([(1, 1), (2, 3)]) {
+            match error {
+                ParseError::UnsupportedCommand { command, span } => {
+                    assert_eq!(command, "\\&");
+                    assert_eq!((span.start_row, span.start_col), (row, col));
+                }
+                err => panic!("unexpected error: {:?}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_errors() {
+        let input = r#"vim9script
+type foo = number
+autocmd MyGroup NotAnEvent echo "x"
+autocmd BufEnter <buffer=x> echo "x"
+var b = 0zF
+for q:x in []
+endfor
+"#;
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+
+        let errors = diagnostics
+            .into_iter()
+            .map(|d| d.error.expect("structured error"))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 5, "{:#?}", errors);
+
+        let expected = [
+            ("E1394", (1, 5)),
+            ("E216", (2, 16)),
+            ("E680", (3, 17)),
+            ("E973", (4, 8)),
+            ("E461", (5, 4)),
+        ];
+        for (error, (code, (row, col))) in errors.iter().zip(expected) {
+            match error {
+                ParseError::Invalid { message, span } => {
+                    assert!(message.starts_with(code), "{}", message);
+                    assert_eq!((span.start_row, span.start_col), (row, col));
+                }
+                err => panic!("unexpected error: {:?}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        let input = r#"vim9script
+echo $"hello {x
+var x 5
+var y = 5
+"#;
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (program, diagnostics) = parser.parse_program_with_diagnostics();
+
+        let errors = diagnostics
+            .into_iter()
+            .map(|d| d.error.expect("structured error"))
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        match &errors[0] {
+            ParseError::InvalidToken { span, .. } => {
+                assert_eq!(span.start_row, 1)
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+        assert!(matches!(
+            &errors[1],
+            ParseError::UnexpectedToken { span, .. } if span.start_row == 2
+        ));
+
+        // The rest of the file is still parsed
+        assert!(matches!(program.commands.last(), Some(ExCommand::Var(_))));
+
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let err = parser.parse_program().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ParseError>(),
+            Some(ParseError::InvalidToken { .. })
+        ));
+
+        // The first token cannot be read
+        let lexer = Lexer::new("$\"unterminated {");
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+        assert_eq!(diagnostics.len(), 1, "{:#?}", diagnostics);
+    }
+
+    #[test]
+    fn test_errors_do_not_panic() {
+        let input = r#"vim9script
+if exists("a:x") | endif
+echo "x"->len()
+1->Foo()
+var e = x[1:2]
+"#;
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (program, _) = parser.parse_program_with_diagnostics();
+
+        assert!(matches!(program.commands.last(), Some(ExCommand::Var(_))));
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let input = "vim9script\nvar x: 5 = 5\nvar y: list<> = []\n";
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+
+        let found = diagnostics
+            .into_iter()
+            .map(|d| match d.error.expect("structured error") {
+                ParseError::UnexpectedToken { found, .. } => found.kind,
+                err => panic!("unexpected error: {:?}", err),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![TokenKind::Integer, TokenKind::GreaterThan]);
+    }
+
+    #[test]
+    fn test_render_diagnostic() {
+        let input = "vim9script\ndef Bar()\n  echo \"bar\"\n";
+        let lexer = Lexer::new(input);
+        let parser = Parser::new(&lexer);
+        let (_, diagnostics) = parser.parse_program_with_diagnostics();
+
+        assert_eq!(diagnostics.len(), 1, "{:#?}", diagnostics);
+        let mut settings = insta::Settings::clone_current();
+        settings.set_snapshot_path("../testdata/output/");
+        settings.bind(|| {
+            insta::assert_snapshot!(diagnostics[0].render("foo.vim", input));
+            insta::assert_snapshot!(diagnostics[0].to_json("foo.vim"));
+        });
+    }
+
+    #[test]
+    fn test_render_without_location() {
+        let diagnostic = Diagnostic::without_location("internal error: oops");
+
+        assert_eq!(
+            diagnostic.render("foo.vim", ""),
+            "error: internal error: oops\n --> foo.vim\n"
+        );
+        assert_eq!(
+            diagnostic.to_json("foo.vim"),
+            r#"{"severity":"error","file":"foo.vim","message":"internal error: oops","span":null,"labels":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_decode_double_quoted() {
+        let decode = |s: &str| decode_double_quoted(s).0;
+
+        assert_eq!(decode(r#"a\tb\n\"\\"#), b"a\tb\n\"\\");
+        assert_eq!(decode(r"\e\x41\x7\101\0"), b"\x1bA\x07A");
+        assert_eq!(decode(r"é\U0001F600"), "é😀".as_bytes());
+        assert_eq!(decode(r"\xg \q"), b"xg q");
+        assert_eq!(decode(r"\<C-a>\<c-[>\<Esc>\<lt>"), b"\x01\x1b\x1b<");
+        assert_eq!(decode(r"\<Up>\<S-Tab>"), b"\x80ku\x80kB");
+        assert_eq!(decode(r"\<lt>Cmd>"), b"<Cmd>");
+        assert_eq!(decode(r"a\x00b"), b"a");
+
+        // Keys that only neovim knows are left for nvim_replace_termcodes
+        let keys = |s: &str| decode_double_quoted(s).1;
+        let key = |offset, notation: &str| TermKey {
+            offset,
+            notation: notation.to_string(),
+        };
+        assert_eq!(keys(r"\<Cmd>call Foo()\<CR>"), vec![key(0, "<Cmd>")]);
+        assert_eq!(
+            keys(r"a\<Plug>\<F13>\<kEnter>\<Ignore>\<S-Up>"),
+            vec![
+                key(1, "<Plug>"),
+                key(1, "<F13>"),
+                key(1, "<kEnter>"),
+                key(1, "<Ignore>"),
+                key(1, "<S-Up>"),
+            ]
+        );
+        assert_eq!(decode(r"\<Nope> \<a b>"), b" <a b>");
+        assert_eq!(keys(r"\<Nope>"), vec![key(0, "<Nope>")]);
+    }
+
     // TODO: Slowly but surely, we can work towards this
     // snap!(test_matchparen, "../../shared/snapshots/matchparen.vim");
 }
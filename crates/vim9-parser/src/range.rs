@@ -0,0 +1,348 @@
+use anyhow::Result;
+use vim9_lexer::{Span, TokenKind};
+
+use crate::{ParseError, Parser};
+
+/// The lines an Ex command works on, such as `1,$` or `'<,'>`.
+///
+/// See `:help cmdline-ranges`
+#[derive(Debug, PartialEq, Clone)]
+pub enum Range {
+    /// `%`, every line in the buffer
+    Whole { span: Span },
+
+    Lines {
+        start: Address,
+
+        /// `None` for a single line or count, like `:5`
+        end: Option<Address>,
+
+        /// Separated by `;`, which moves the cursor to `start` before
+        /// `end` is found
+        relative: bool,
+
+        span: Span,
+    },
+}
+
+/// A line number, along with the offsets after it: `.+1`, `/foo/-2`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Address {
+    pub line: LineNumber,
+    pub offset: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LineNumber {
+    Number(usize),
+    /// `.`, also used when an address only has an offset
+    Current,
+    /// `$`
+    Last,
+    /// `'x`
+    Mark(char),
+    /// `/pattern/`
+    SearchForward(String),
+    /// `?pattern?`
+    SearchBackward(String),
+    /// `\/`, the last search pattern searched forward
+    LastSearchForward,
+    /// `\?`, the last search pattern searched backward
+    LastSearchBackward,
+}
+
+/// Reads a range from the front of `text`, returning it along with the
+/// number of chars it took up.
+fn read_range(text: &str) -> Option<(RangeText, usize)> {
+    let mut chars = text.chars().peekable();
+    let mut used = 0;
+
+    if chars.peek() == Some(&'%') {
+        return Some((RangeText::Whole, 1));
+    }
+
+    // `*` is the last visual selection, the same as `'<,'>`
+    if chars.peek() == Some(&'*') {
+        let mark = |mark| Address {
+            line: LineNumber::Mark(mark),
+            offset: 0,
+        };
+
+        return Some((RangeText::Lines(mark('<'), Some(mark('>')), false), 1));
+    }
+
+    let start = read_address(&mut chars, &mut used);
+    let separator = match chars.peek() {
+        Some(&sep @ (',' | ';')) => {
+            chars.next();
+            used += 1;
+            Some(sep)
+        }
+        _ => None,
+    };
+
+    let Some(separator) = separator else {
+        return start.map(|start| (RangeText::Lines(start, None, false), used));
+    };
+
+    // A missing address in `,5` or `5,` is the current line
+    let current = || Address {
+        line: LineNumber::Current,
+        offset: 0,
+    };
+
+    let end = read_address(&mut chars, &mut used).unwrap_or_else(current);
+    let start = start.unwrap_or_else(current);
+    Some((RangeText::Lines(start, Some(end), separator == ';'), used))
+}
+
+/// Whether `ch` is one of the commands that are a symbol instead of a name,
+/// such as `:!` in `:%!sort` or `:>` in `:.,.+3 >`
+pub(crate) fn is_symbol_command(ch: char) -> bool {
+    matches!(ch, '!' | '>' | '<' | '&' | '~')
+}
+
+/// Whether `text`, which starts with a name, assigns to it
+fn is_assignment(text: &str) -> bool {
+    let rest = text
+        .trim_start_matches(|c: char| c.is_alphanumeric() || c == '_')
+        .trim_start();
+
+    !rest.starts_with("==")
+        && ["=", "+=", "-=", "*=", "/=", "%=", ".=", "..="]
+            .iter()
+            .any(|op| rest.starts_with(op))
+}
+
+enum RangeText {
+    Whole,
+    Lines(Address, Option<Address>, bool),
+}
+
+fn read_address(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    used: &mut usize,
+) -> Option<Address> {
+    let mut next = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        *used += 1;
+        chars.next()
+    };
+
+    let line = match chars.peek().copied() {
+        Some('0'..='9') => {
+            let mut number = 0usize;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                number =
+                    number.saturating_mul(10).saturating_add(digit as usize);
+                next(chars);
+            }
+
+            Some(LineNumber::Number(number))
+        }
+        Some('.') => {
+            next(chars);
+            Some(LineNumber::Current)
+        }
+        Some('$') => {
+            next(chars);
+            Some(LineNumber::Last)
+        }
+        Some('\'') => {
+            next(chars);
+            Some(LineNumber::Mark(next(chars)?))
+        }
+        Some(delimiter @ ('/' | '?')) => {
+            next(chars);
+
+            let mut pattern = String::new();
+            loop {
+                match next(chars)? {
+                    ch if ch == delimiter => break,
+                    '\\' if chars.peek() == Some(&delimiter) => {
+                        pattern.push(next(chars)?)
+                    }
+                    '\\' => {
+                        pattern.push('\\');
+                        pattern.push(next(chars)?);
+                    }
+                    ch => pattern.push(ch),
+                }
+            }
+
+            Some(match delimiter {
+                '/' => LineNumber::SearchForward(pattern),
+                _ => LineNumber::SearchBackward(pattern),
+            })
+        }
+        Some('\\') => {
+            let mut ahead = chars.clone();
+            ahead.next();
+
+            let line = match ahead.next() {
+                Some('/') => LineNumber::LastSearchForward,
+                Some('?') => LineNumber::LastSearchBackward,
+                _ => return None,
+            };
+
+            next(chars);
+            next(chars);
+            Some(line)
+        }
+        _ => None,
+    };
+
+    let mut offset = 0i64;
+    let mut has_offset = false;
+    while let Some(&sign @ ('+' | '-')) = chars.peek() {
+        next(chars);
+        has_offset = true;
+
+        let mut amount = None;
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            let value = amount.unwrap_or(0i64);
+            amount =
+                Some(value.saturating_mul(10).saturating_add(digit as i64));
+            next(chars);
+        }
+
+        // A `+` or `-` on its own is one line
+        let amount = amount.unwrap_or(1);
+        offset += if sign == '+' { amount } else { -amount };
+    }
+
+    match line {
+        Some(line) => Some(Address { line, offset }),
+        None if has_offset => Some(Address {
+            line: LineNumber::Current,
+            offset,
+        }),
+        None => None,
+    }
+}
+
+impl Range {
+    pub fn span(&self) -> &Span {
+        match self {
+            Range::Whole { span } => span,
+            Range::Lines { span, .. } => span,
+        }
+    }
+
+    /// Parses the range at the start of a command, if there is one.
+    ///
+    /// The lexer does not know about ranges (`'<,'>` looks like a string),
+    /// so this reads the source of the line and then skips the tokens that
+    /// the range covers.
+    pub fn parse(parser: &Parser) -> Result<Option<Range>> {
+        if parser.front_kind().is_whitespace() {
+            return Ok(None);
+        }
+
+        // Token spans can start after the text of the token (strings skip
+        // their quote), so start from the end of the previous token instead
+        let row = parser.front_ref().span.start_row;
+        let last = parser.last_span.borrow().clone();
+        let after = if last.end_row == row { last.end_col } else { 0 };
+
+        let line = parser.source_line(row);
+        let Some((offset, _)) = line.char_indices().nth(after) else {
+            return Ok(None);
+        };
+
+        let text = &line[offset..];
+        let blanks = text.chars().take_while(|c| c.is_whitespace()).count();
+        let start_col = after + blanks;
+        let text = text.trim_start();
+
+        // `\&` searches for the last substitute pattern, which lua cannot
+        // get at
+        let range = read_range(text);
+        let used = range.as_ref().map_or(0, |(_, used)| *used);
+        if text
+            .chars()
+            .skip(used)
+            .collect::<String>()
+            .starts_with("\\&")
+        {
+            let start_col = start_col + used;
+            return Err(ParseError::UnsupportedCommand {
+                command: "\\&".to_string(),
+                span: Span {
+                    start_row: row,
+                    start_col,
+                    end_row: row,
+                    end_col: start_col + 2,
+                },
+            }
+            .into());
+        }
+
+        let Some((range, used)) = range else {
+            return Ok(None);
+        };
+
+        let end_col = start_col + used;
+        let rest = text.chars().skip(used).collect::<String>();
+        let rest = rest.trim_start();
+
+        // Only a range when followed by a command name or the end of the
+        // line, so that `$FOO = 1` and `'str'->Func()` are not ranges
+        let is_command = match rest.chars().next() {
+            None => true,
+            Some(ch) if ch.is_alphabetic() => {
+                if text.chars().nth(used - 1) == Some('$') {
+                    // `$name` is lexed as an environment variable
+                    !is_assignment(rest)
+                } else {
+                    Self::identifier_at(parser, row, end_col)
+                }
+            }
+            Some(ch) => is_symbol_command(ch),
+        };
+
+        if !is_command {
+            return Ok(None);
+        }
+
+        while !parser.front_kind().is_whitespace()
+            && parser.front_ref().span.start_row == row
+            && parser.front_ref().span.start_col < end_col
+        {
+            parser.next_token();
+        }
+
+        let span = Span {
+            start_row: row,
+            start_col,
+            end_row: row,
+            end_col,
+        };
+
+        Ok(Some(match range {
+            RangeText::Whole => Range::Whole { span },
+            RangeText::Lines(start, end, relative) => Range::Lines {
+                start,
+                end,
+                relative,
+                span,
+            },
+        }))
+    }
+
+    /// Whether the first token at or after `col` is an identifier
+    fn identifier_at(parser: &Parser, row: usize, col: usize) -> bool {
+        let mut n = 0;
+        loop {
+            let tok = parser.peek_n(n);
+            if tok.kind.is_whitespace() || tok.span.start_row != row {
+                return false;
+            }
+
+            if tok.span.start_col >= col {
+                return tok.kind == TokenKind::Identifier;
+            }
+
+            n += 1;
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use vim9_lexer::TokenKind;
+use vim9_lexer::{Span, TokenKind};
 
 use crate::{Literal, Parser, TokenMeta};
 
@@ -7,8 +7,12 @@ use crate::{Literal, Parser, TokenMeta};
 pub struct Type {
     colon: TokenMeta,
     pub inner: InnerType,
+
+    /// The source of `inner`, without the colon
+    pub span: Span,
 }
 
+/// See `:help vim9-types`
 #[derive(Debug, PartialEq, Clone)]
 pub enum InnerType {
     Any,
@@ -27,22 +31,82 @@ pub enum InnerType {
         inner: Box<InnerType>,
         close: TokenMeta,
     },
+    /// `tuple<number, string>`, where the last item may be variadic:
+    /// `tuple<number, ...list<string>>`
+    Tuple {
+        open: TokenMeta,
+        items: Vec<TypeItem>,
+        close: TokenMeta,
+    },
+    /// `object<Foo>`
+    Object {
+        open: TokenMeta,
+        inner: Box<InnerType>,
+        close: TokenMeta,
+    },
     Job,
     Channel,
     Func(InnerFuncType),
     Void,
+    /// A class, interface, enum, type alias or type parameter, along with
+    /// its type arguments: `Foo`, `T` or `Container<number>`
+    Named {
+        name: Literal,
+        args: Option<TypeArgs>,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum InnerFuncType {
+    /// `func`, any arguments and any return value
     Naked,
+    /// `func: number`, any arguments
+    Returns(Box<InnerType>),
+    /// `func(number, ?string, ...list<any>): bool`
+    Signature {
+        open: TokenMeta,
+        params: Vec<TypeItem>,
+        close: TokenMeta,
+        /// `None` when nothing is returned
+        ret: Option<Box<InnerType>>,
+    },
+}
+
+/// A type in a list of types, such as the arguments of a `func` type
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeItem {
+    Required(InnerType),
+    /// `?number`, an argument that can be left out
+    Optional(InnerType),
+    /// `...list<any>`, any number of trailing items
+    Variadic(InnerType),
+}
+
+/// `<number, string>` in `Foo<number, string>` or `<T>` in `def Foo<T>()`
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeArgs {
+    open: TokenMeta,
+    pub types: Vec<InnerType>,
+    close: TokenMeta,
 }
 
 impl Type {
     pub fn parse(parser: &Parser) -> Result<Type> {
+        let colon = parser.expect_token(TokenKind::SpacedColon)?.into();
+
+        let start = parser.front_ref().span.clone();
+        let inner = InnerType::parse(parser)?;
+        let end = parser.last_span.borrow().clone();
+
         Ok(Type {
-            colon: parser.expect_token(TokenKind::SpacedColon)?.into(),
-            inner: InnerType::parse(parser)?,
+            colon,
+            inner,
+            span: Span {
+                start_row: start.start_row,
+                start_col: start.start_col,
+                end_row: end.end_row,
+                end_col: end.end_col,
+            },
         })
     }
 }
@@ -56,34 +120,160 @@ impl InnerType {
         matches!(k, TokenKind::GreaterThan | TokenKind::AngleRight)
     }
 
+    /// Parses `<inner>`, for types like `list<string>`
+    fn parse_wrapped(
+        parser: &Parser,
+    ) -> Result<(TokenMeta, Box<InnerType>, TokenMeta)> {
+        Ok((
+            parser.expect_fn(Self::open, true)?.into(),
+            InnerType::parse(parser)?.into(),
+            parser.expect_fn(Self::close, true)?.into(),
+        ))
+    }
+
     pub fn parse(parser: &Parser) -> Result<InnerType> {
-        match parser.front_kind() {
-            TokenKind::Identifier => {
-                let literal: Literal = parser.pop().try_into()?;
-                Ok(match literal.token.text.as_str() {
-                    "any" => InnerType::Any,
-                    "bool" => InnerType::Bool,
-                    "number" => InnerType::Number,
-                    "void" => InnerType::Void,
-                    "string" => InnerType::String,
-                    "float" => InnerType::Float,
-                    "list" => InnerType::List {
-                        open: parser.expect_fn(Self::open, true)?.into(),
-                        inner: InnerType::parse(parser)?.into(),
-                        close: parser.expect_fn(Self::close, true)?.into(),
-                    },
-                    "dict" => InnerType::Dict {
-                        open: parser.expect_fn(Self::open, true)?.into(),
-                        inner: InnerType::parse(parser)?.into(),
-                        close: parser.expect_fn(Self::close, true)?.into(),
-                    },
-                    "func" => InnerType::Func(InnerFuncType::Naked),
-                    "job" => InnerType::Job,
-                    "channel" => InnerType::Channel,
-                    _ => todo!("{:?}", literal.token),
-                })
+        if parser.front_kind() != TokenKind::Identifier {
+            return Err(parser
+                .unexpected_token(vec![TokenKind::Identifier])
+                .into());
+        }
+
+        let literal: Literal = parser.pop().try_into()?;
+        Ok(match literal.token.text.as_str() {
+            "any" => InnerType::Any,
+            "bool" => InnerType::Bool,
+            "number" => InnerType::Number,
+            "void" => InnerType::Void,
+            "string" => InnerType::String,
+            "float" => InnerType::Float,
+            "blob" => InnerType::Blob,
+            "list" => {
+                let (open, inner, close) = Self::parse_wrapped(parser)?;
+                InnerType::List { open, inner, close }
+            }
+            "dict" => {
+                let (open, inner, close) = Self::parse_wrapped(parser)?;
+                InnerType::Dict { open, inner, close }
+            }
+            "object" => {
+                let (open, inner, close) = Self::parse_wrapped(parser)?;
+                InnerType::Object { open, inner, close }
+            }
+            "tuple" => InnerType::Tuple {
+                open: parser.expect_fn(Self::open, true)?.into(),
+                items: TypeItem::parse_list(parser, Self::close)?,
+                close: parser.expect_fn(Self::close, true)?.into(),
+            },
+            "func" => InnerType::Func(InnerFuncType::parse(parser)?),
+            "job" => InnerType::Job,
+            "channel" => InnerType::Channel,
+            _ => {
+                let mut name = literal;
+
+                // `script.Foo`, a type exported by an imported script
+                if parser.front_kind() == TokenKind::Dot {
+                    parser.next_token();
+                    let member = parser.pop();
+                    name.token.text =
+                        format!("{}.{}", name.token.text, member.text.as_str());
+                    name.token.span.end_row = member.span.end_row;
+                    name.token.span.end_col = member.span.end_col;
+                }
+
+                let args = match parser.front_kind() {
+                    TokenKind::AngleLeft => Some(TypeArgs::parse(parser)?),
+                    _ => None,
+                };
+
+                // Aliases are replaced by the type they stand for
+                match parser.type_aliases.borrow().get(&name.token.text) {
+                    Some(aliased) if args.is_none() => aliased.clone(),
+                    _ => InnerType::Named { name, args },
+                }
+            }
+        })
+    }
+}
+
+impl InnerFuncType {
+    fn parse(parser: &Parser) -> Result<InnerFuncType> {
+        let is_paren = |k: &TokenKind| *k == TokenKind::RightParen;
+
+        Ok(match parser.front_kind() {
+            TokenKind::LeftParen => InnerFuncType::Signature {
+                open: parser.expect_token(TokenKind::LeftParen)?.into(),
+                params: TypeItem::parse_list(parser, is_paren)?,
+                close: parser.expect_token(TokenKind::RightParen)?.into(),
+                ret: match parser.front_kind() {
+                    TokenKind::SpacedColon => {
+                        parser.next_token();
+                        Some(InnerType::parse(parser)?.into())
+                    }
+                    _ => None,
+                },
+            },
+            TokenKind::SpacedColon => {
+                parser.next_token();
+                InnerFuncType::Returns(InnerType::parse(parser)?.into())
+            }
+            _ => InnerFuncType::Naked,
+        })
+    }
+}
+
+impl TypeItem {
+    fn parse(parser: &Parser) -> Result<TypeItem> {
+        Ok(match parser.front_kind() {
+            TokenKind::QuestionMark => {
+                parser.next_token();
+                TypeItem::Optional(InnerType::parse(parser)?)
+            }
+            TokenKind::Ellipsis => {
+                parser.next_token();
+                TypeItem::Variadic(InnerType::parse(parser)?)
             }
-            _ => unreachable!("should probably return an error"),
+            _ => TypeItem::Required(InnerType::parse(parser)?),
+        })
+    }
+
+    /// Parses comma separated items, up to but not including the token
+    /// that matches `close`
+    fn parse_list<F>(parser: &Parser, close: F) -> Result<Vec<TypeItem>>
+    where
+        F: Fn(&TokenKind) -> bool,
+    {
+        let mut items = vec![];
+        while !close(&parser.front_kind()) {
+            let item = TypeItem::parse(parser)?;
+            let variadic = matches!(item, TypeItem::Variadic(_));
+            items.push(item);
+
+            // Nothing can come after a variadic item
+            if variadic || parser.front_kind() != TokenKind::Comma {
+                break;
+            }
+
+            parser.next_token();
+        }
+
+        Ok(items)
+    }
+}
+
+impl TypeArgs {
+    pub fn parse(parser: &Parser) -> Result<TypeArgs> {
+        let open = parser.expect_fn(InnerType::open, true)?.into();
+
+        let mut types = vec![InnerType::parse(parser)?];
+        while parser.front_kind() == TokenKind::Comma {
+            parser.next_token();
+            types.push(InnerType::parse(parser)?);
         }
+
+        Ok(TypeArgs {
+            open,
+            types,
+            close: parser.expect_fn(InnerType::close, true)?.into(),
+        })
     }
 }
@@ -0,0 +1,16 @@
+vim9script
+
+augroup first
+  autocmd!
+  autocmd BufEnter * echo "first"
+augroup second
+  autocmd BufLeave * echo "second"
+  autocmd!
+augroup END
+
+augroup third
+  au! BufRead *.txt echo "third"
+augroup end
+
+augroup! second
+augroup END
@@ -0,0 +1,22 @@
+vim9script
+
+var Add = (x, y) => {
+  var sum = x + y
+  return sum
+}
+
+timer_start(100, (_) => {
+  echo 'done'
+})
+
+def Evens(items: list<number>): list<number>
+  return items->filter((_, v) => {
+    return v % 2 == 0
+  })
+enddef
+
+var MakeAdder = (x: number): func => {
+  return (y) => {
+    return x + y
+  }
+}
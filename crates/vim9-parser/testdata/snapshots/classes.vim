@@ -0,0 +1,75 @@
+vim9script
+
+interface Named
+  var name: string
+endinterface
+
+interface HasArea extends Named
+  def Area(): number
+endinterface
+
+interface Scalable
+  def Scale(factor: number)
+endinterface
+
+abstract class Shape implements HasArea
+  var name: string = 'shape'
+  static var count: number = 0
+  abstract def Area(): number
+  def Describe(): string
+    return this.name .. ' ' .. string(this.Area())
+  enddef
+endclass
+
+export class Rect extends Shape implements Scalable
+  public var width: number
+  var height: number
+
+  def new(this.width, this.height)
+    this.name = 'rect'
+    Shape.count += 1
+  enddef
+
+  def Area(): number
+    var area = this.width
+    area *= this.height
+    return area
+  enddef
+
+  def Describe(): string
+    return 'rect: ' .. super.Describe()
+  enddef
+
+  def Scale(factor: number)
+    this.width *= factor
+    this.height *= factor
+  enddef
+
+  static def Square(size: number): Rect
+    return Rect.new(size, size)
+  enddef
+endclass
+
+enum Color
+  Red,
+  Green,
+  Blue
+endenum
+
+enum Size
+  Small(1), Large(10)
+
+  final value: number
+
+  def new(this.value)
+  enddef
+
+  def Area(): number
+    return this.value
+  enddef
+endenum
+
+var r = Rect.Square(2)
+echo r.Area()
+echo Color.Red.name
+echo Size.Large.ordinal
@@ -0,0 +1,14 @@
+vim9script
+
+var x = 1
+      \ + 2
+      \ + 3
+
+var y = x
+  ->abs()
+  #\ a comment in between
+  ->string()
+
+var z = x == 6
+  ? "six"
+  : "other"
@@ -23,3 +23,7 @@ def Test_right()
   assert_equal([1, 2], l[: 1])
 enddef
 
+
+def Test_unspaced()
+  assert_equal([2, 3], l[1:2])
+enddef
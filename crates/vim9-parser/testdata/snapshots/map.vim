@@ -0,0 +1,29 @@
+vim9script
+
+def Toggle()
+  echo 'toggle'
+enddef
+
+def Complete(): string
+  return pumvisible() ? "\<C-n>" : "\<Tab>"
+enddef
+
+nnoremap <silent> <buffer> <leader>x <ScriptCmd>Toggle()<CR>
+nnoremap <leader>t :call <SID>Toggle()<CR>
+nnoremap <leader>c <Cmd>nohlsearch<CR>
+inoremap <expr> <Tab> Complete()
+nmap <unique> <nowait> gx <Plug>(Thing)
+map! <script> jk <Esc>
+xnoremap <silent> J :m '>+1<CR>gv
+nunmap <buffer> <leader>x
+unmap! jk
+mapclear <buffer>
+imapclear
+
+var mapped = map([1, 2], (_, v) => v + 1)
+nnoremap <leader>b <ScriptCmd>Toggle()<CR>|echo 2
+nnoremap <leader>p :echo 1 \| echo 2<CR>
+nmap <leader>a <Cmd>echo 1<CR>
+nmap <buffer> x
+nnoremap
+nunmap
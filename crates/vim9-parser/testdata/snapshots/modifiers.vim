@@ -0,0 +1,8 @@
+vim9script
+
+silent! call Foo()
+noautocmd keepjumps normal! gg
+vertical botright split
+sil! noa :echo "quiet"
+var silent = 1
+silent = 2
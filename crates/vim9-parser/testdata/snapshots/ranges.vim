@@ -0,0 +1,20 @@
+vim9script
+
+:%s/foo/bar/g
+:1,$delete
+:'<,'>normal! gv
+:.+1
+:.;/end/d
+:?start?,.yank
+:3,5
+:%!sort -u
+:.,.+3 > 2
+:*
+:*<
+:'<,'>&&
+silent :1,3d
+var line = 1
+:$
+:1,2s/a/b/ | echo 1
+:\/d
+:\?-1,.yank
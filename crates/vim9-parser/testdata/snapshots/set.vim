@@ -0,0 +1,22 @@
+vim9script
+
+set number
+set nowrap invlist hlsearch!
+setlocal tabstop=4 shiftwidth=2
+setlocal colorcolumn=80
+setglobal path+=src/**
+set wildignore-=*.o
+set runtimepath^=~/.vim/pack
+set shortmess+=c
+set matchpairs+=<:>
+set statusline=%f\ %m
+set filetype:vim
+set textwidth&
+setlocal foldmethod=marker # a comment
+:setlocal foldcolumn=2
+set sw? ts?
+set all&
+set termcap
+set t_Co=256
+set tabstop=0x8
+set
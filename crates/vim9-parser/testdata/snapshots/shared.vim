@@ -2,3 +2,6 @@ vim9script
 
 syn keyword contextConstants zerocount minusone minustwo plusone plustwo contained
 syn keyword contextConstants plusthree plusfour plusfive plussix plusseven contained
+runtime! plugin/foo.vim
+      \ plugin/bar.vim
+syn match	contextComment	"%.*"   contained
@@ -0,0 +1,26 @@
+vim9script
+
+def Read(path: string): list<string>
+  try
+    return readfile(path)
+  catch /^Vim\%((\a\+)\)\=:E484/
+    echo 'cannot read: ' .. v:exception
+  catch # anything else
+    echo 'other error'
+  finally
+    echo 'done'
+  endtry
+
+  return []
+enddef
+
+for name in ['a', 'b']
+  try
+    if name == 'a'
+      continue
+    endif
+    Read(name)
+  catch ,E\,121,
+    break
+  endtry
+endfor
@@ -0,0 +1,29 @@
+vim9script
+
+import './types.vim'
+
+type Strings = list<string>
+export type Flag = bool
+type Lookup = dict<Strings>
+type Callback = func(Strings): Flag
+
+var names: Strings = ['a', 'b']
+var enabled: Flag = 1
+var table: Lookup = {}
+var other: types.Names = []
+
+def Apply(Fn: Callback, items: Strings): Flag
+  return Fn(items)
+enddef
+
+class Config
+  var names: Strings
+  var verbose: Flag
+endclass
+
+def Enabled(): Switch
+  var on: Switch = 1
+  return on
+enddef
+
+type Switch = bool
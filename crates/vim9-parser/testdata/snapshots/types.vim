@@ -0,0 +1,18 @@
+vim9script
+
+var F: func(number, string): bool = (n, s) => true
+var G: func(...list<any>) = Foo
+var H: func: number = () => 1
+var I: func(?number, ...list<string>): void = Foo
+var nested: list<list<string>> = []
+var deep: dict<list<dict<number>>> = {}
+var t: tuple<number, string> = x
+var v: tuple<...list<number>> = x
+var o: object<Foo> = x
+var c: Foo = Foo.new()
+var g: Container<number, string> = x
+var b: blob = x
+
+def Id<T>(x: T): T
+  return x
+enddef
@@ -0,0 +1,279 @@
+-- Runtime for vim9 classes, interfaces and enums
+--
+-- A class is a table holding its static variables, static methods and
+-- constructors. Objects are tables holding their object variables, and find
+-- their methods through the metatable of their class.
+local class = {}
+
+-- Finds `name` in the methods of `cls` or the classes it extends
+local find_method = function(cls, name)
+  while cls do
+    local method = cls.__vim9_class.methods[name]
+    if method then
+      return method
+    end
+
+    cls = cls.__vim9_class.extends
+  end
+end
+
+-- Sets the object variables of `cls` and the classes it extends on `this`,
+-- starting from the base class
+local function init_vars(cls, this)
+  local info = cls.__vim9_class
+  if info.extends then
+    init_vars(info.extends, this)
+  end
+
+  if info.init then
+    info.init(this)
+  end
+end
+
+-- All the names of the object variables of `cls`, in the order they were
+-- declared, starting from the base class
+local var_names = function(cls)
+  local names = {}
+  while cls do
+    names = vim.list_extend(vim.deepcopy(cls.__vim9_class.vars), names)
+    cls = cls.__vim9_class.extends
+  end
+
+  return names
+end
+
+local object = function(cls)
+  local this = setmetatable({}, cls.__vim9_class.object_mt)
+  init_vars(cls, this)
+  return this
+end
+
+-- The constructor used when a class does not define `new()`, which takes
+-- the object variables in order
+local default_new = function(cls)
+  return function(this, ...)
+    local args = { ... }
+    for i, name in ipairs(var_names(cls)) do
+      if args[i] ~= nil then
+        this[name] = args[i]
+      end
+    end
+  end
+end
+
+-- Whether `interface` is `target` or extends it
+local function extends_interface(interface, target)
+  if interface == target then
+    return true
+  end
+
+  for _, parent in ipairs(interface.__vim9_interface.extends) do
+    if extends_interface(parent, target) then
+      return true
+    end
+  end
+
+  return false
+end
+
+local implements = function(cls, interface)
+  local info = cls.__vim9_class
+  while info do
+    for _, implemented in ipairs(info.implements) do
+      if extends_interface(implemented, interface) then
+        return true
+      end
+    end
+
+    info = info.extends and info.extends.__vim9_class
+  end
+
+  return false
+end
+
+local function check_interface(cls, interface)
+  local spec = interface.__vim9_interface
+  for _, parent in ipairs(spec.extends) do
+    check_interface(cls, parent)
+  end
+
+  local vars = var_names(cls)
+  for _, name in ipairs(spec.vars) do
+    if not vim.tbl_contains(vars, name) then
+      error(string.format('E1348: Variable "%s" of interface "%s" is not implemented', name, spec.name), 0)
+    end
+  end
+
+  for _, name in ipairs(spec.methods) do
+    if not find_method(cls, name) then
+      error(string.format('E1349: Method "%s" of interface "%s" is not implemented', name, spec.name), 0)
+    end
+  end
+end
+
+local abstract_methods = function(cls)
+  local missing = {}
+  local info = cls.__vim9_class
+  while info do
+    for _, name in ipairs(info.abstract_methods) do
+      if not find_method(cls, name) then
+        table.insert(missing, name)
+      end
+    end
+
+    info = info.extends and info.extends.__vim9_class
+  end
+
+  return missing
+end
+
+-- Creates a class from the table that the generated code passes in:
+--
+--  name, abstract, extends, implements
+--  vars: the names of the object variables, in order
+--  init: function(this) that sets the object variables to their defaults
+--  methods: the object methods, called with `this` first
+--  abstract_methods: the names of the methods without a body
+--  constructors: `new` and the other `new*` methods, called with `this`
+--  static: the static variables and methods
+class.new = function(def)
+  local cls = def.static or {}
+
+  local info = {
+    name = def.name,
+    extends = def.extends,
+    implements = def.implements or {},
+    vars = def.vars or {},
+    init = def.init,
+    methods = def.methods or {},
+    abstract_methods = def.abstract_methods or {},
+  }
+
+  info.object_mt = {
+    __vim9_object = cls,
+    __index = function(this, key)
+      local method = find_method(cls, key)
+      if method then
+        return function(...)
+          return method(this, ...)
+        end
+      end
+    end,
+  }
+
+  rawset(cls, "__vim9_class", info)
+
+  for _, interface in ipairs(info.implements) do
+    check_interface(cls, interface)
+  end
+
+  if not def.abstract then
+    local missing = abstract_methods(cls)
+    if #missing > 0 then
+      error(string.format('E1373: Abstract method "%s" is not implemented', missing[1]), 0)
+    end
+  end
+
+  local constructors = def.constructors or {}
+  if not constructors.new and not def.is_enum then
+    constructors.new = default_new(cls)
+  end
+
+  for name, constructor in pairs(constructors) do
+    cls[name] = function(...)
+      if def.abstract then
+        error(string.format('Cannot instantiate abstract class "%s"', def.name), 0)
+      end
+
+      local this = object(cls)
+      constructor(this, ...)
+      return this
+    end
+  end
+
+  return cls
+end
+
+-- Creates an enum, which is a class whose only objects are its `values`.
+-- Each value is a list of its name followed by the arguments for `new()`.
+class.enum = function(def)
+  def.is_enum = true
+  local constructor = (def.constructors or {}).new or function() end
+  def.constructors = {}
+
+  local cls = class.new(def)
+  cls.values = {}
+  for ordinal, value in ipairs(def.values) do
+    local this = object(cls)
+    rawset(this, "name", value[1])
+    rawset(this, "ordinal", ordinal - 1)
+    constructor(this, unpack(value, 2))
+
+    cls[value[1]] = this
+    table.insert(cls.values, this)
+  end
+
+  return cls
+end
+
+-- Creates an interface. It only exists to be checked against classes that
+-- implement it.
+class.interface = function(def)
+  return {
+    __vim9_interface = {
+      name = def.name,
+      extends = def.extends or {},
+      vars = def.vars or {},
+      methods = def.methods or {},
+    },
+  }
+end
+
+-- The methods of the class that `cls` extends, called on `this`. This is
+-- what `super` refers to inside of a method.
+class.super = function(cls, this)
+  local parent = cls.__vim9_class.extends
+  return setmetatable({}, {
+    __index = function(_, key)
+      local method = find_method(parent, key)
+      if method then
+        return function(...)
+          return method(this, ...)
+        end
+      end
+
+      return this[key]
+    end,
+  })
+end
+
+-- Whether `value` is an object of `cls`, or of a class that extends or
+-- implements it. See |instanceof()|.
+class.instanceof = function(value, ...)
+  local mt = getmetatable(value)
+  local cls = mt and mt.__vim9_object
+  if not cls then
+    return false
+  end
+
+  for _, target in ipairs { ... } do
+    if type(target) == "table" and target.__vim9_interface then
+      if implements(cls, target) then
+        return true
+      end
+    else
+      local current = cls
+      while current do
+        if current == target then
+          return true
+        end
+
+        current = current.__vim9_class.extends
+      end
+    end
+  end
+
+  return false
+end
+
+return class
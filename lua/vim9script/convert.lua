@@ -32,4 +32,18 @@ convert.to_vim_bool = function(val)
   error("unhandled type: " .. vim.inspect(val))
 end
 
+--- Converts a value to a string the way vim does in `$"{expr}"`,
+--- which is like `string()` except that strings are left as is.
+convert.to_vim_string = function(val)
+  if type(val) == "string" then
+    return val
+  elseif type(val) == "boolean" then
+    return val and "true" or "false"
+  elseif val == nil then
+    return "null"
+  end
+
+  return vim.fn.string(val)
+end
+
 return convert
@@ -171,6 +171,10 @@ fn.popup_setoptions = function(id, options)
   print("setting options...", id)
 end
 
+fn.instanceof = function(object, ...)
+  return require("vim9script.class").instanceof(object, ...)
+end
+
 fn = setmetatable(fn, {
   __index = vim.fn,
 })
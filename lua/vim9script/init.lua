@@ -11,9 +11,23 @@ M.convert = require "vim9script.convert"
 M.heredoc = require "vim9script.heredoc"
 M.fn = require "vim9script.fn"
 M.import = require "vim9script.import"
+M.range = require "vim9script.range"
+M.class = require "vim9script.class"
+M.try = require("vim9script.try").run
+M.throw = require("vim9script.try").throw
+M.echoerr = require("vim9script.try").echoerr
+M.try_at = require("vim9script.try").at
 
 M.bool = M.convert.to_vim_bool
 
+-- Makes the blob `0z{hex}`. Blobs are strings of bytes on the lua side,
+-- which is also what vim gives lua for them.
+M.blob = function(hex)
+  return (hex:gsub("%x%x", function(byte)
+    return string.char(tonumber(byte, 16))
+  end))
+end
+
 M.ternary = function(cond, if_true, if_false)
   if cond then
     if type(if_true) == "function" then
@@ -107,6 +121,85 @@ M.slice = function(obj, start, finish)
   return slicer(obj, start + 1, finish + 1)
 end
 
+-- Removes the variable `name` from `scope`, which is one of `vim.g`,
+-- `vim.env` and the like. See |:unlet|.
+M.unlet_var = function(scope, name, display)
+  if scope[name] == nil then
+    error(string.format('E108: No such variable: "%s"', display), 0)
+  end
+
+  scope[name] = nil
+end
+
+-- Removes the item at `idx` from a list, or the key `idx` from a dict. It
+-- is an error when it does not exist, unless `force` is set.
+M.unlet_item = function(obj, idx, force)
+  if vim.tbl_islist(obj) and type(idx) == "number" then
+    local i = idx < 0 and #obj + idx or idx
+    if i < 0 or i >= #obj then
+      if force then
+        return
+      end
+
+      error(string.format("E684: List index out of range: %d", idx), 0)
+    end
+
+    table.remove(obj, i + 1)
+  elseif type(obj) == "table" then
+    if obj[idx] == nil then
+      if force then
+        return
+      end
+
+      error(string.format('E716: Key not present in Dictionary: "%s"', idx), 0)
+    end
+
+    obj[idx] = nil
+  else
+    error("invalid type for unlet: " .. vim.inspect(obj))
+  end
+end
+
+-- Removes the items from `start` to `finish` from a list, including both
+M.unlet_slice = function(list, start, finish)
+  start = start or 0
+  if start < 0 then
+    start = #list + start
+  end
+
+  finish = finish or #list - 1
+  if finish < 0 then
+    finish = #list + finish
+  end
+
+  if start < 0 or start >= #list then
+    error(string.format("E684: List index out of range: %d", start), 0)
+  end
+
+  for _ = start, math.min(finish, #list - 1) do
+    table.remove(list, start + 1)
+  end
+end
+
+-- The functions of legacy script that were defined with the `dict`
+-- attribute, which are called with the dict they are found in as `self`
+local dict_functions = setmetatable({}, { __mode = "k" })
+
+M.dict_function = function(fn)
+  dict_functions[fn] = true
+  return fn
+end
+
+-- Calls `dict[key]`, passing `dict` along as `self` to dict functions
+M.dict_call = function(dict, key, ...)
+  local fn = dict[key]
+  if dict_functions[fn] then
+    return fn(dict, ...)
+  end
+
+  return fn(...)
+end
+
 M.make_source_cmd = function()
   local group = vim.api.nvim_create_augroup("vim9script-source", {})
   vim.api.nvim_create_autocmd("SourceCmd", {
@@ -130,6 +223,75 @@ M.make_source_cmd = function()
   })
 end
 
+-- Runs an ex command with the `range` and `mods` in `opts`, in the format
+-- of |nvim_cmd()|. The range and mods are only for the first command, the
+-- ones after a `|` run on their own.
+M.cmd = function(command, opts)
+  local parsed = vim.api.nvim_parse_cmd(command, {})
+  parsed.mods = vim.tbl_extend("force", parsed.mods or {}, opts.mods or {})
+  parsed.range = opts.range or parsed.range
+
+  -- nvim_cmd() ignores everything after the `|`
+  local output = vim.api.nvim_cmd(parsed, {})
+  if parsed.nextcmd and parsed.nextcmd ~= "" then
+    vim.cmd(parsed.nextcmd)
+  end
+
+  return output
+end
+
+-- The value of `:set {name}={value}`, which is a number for options that are
+-- numbers, like 'tabstop' and unlike 'colorcolumn', and a string otherwise
+M.option_value = function(name, value)
+  if vim.api.nvim_get_option_info2(name, {}).type == "number" then
+    return tonumber(value)
+  end
+
+  return value
+end
+
+-- The default value of an option, for `:set {name}&`
+M.option_default = function(name)
+  return vim.api.nvim_get_option_info2(name, {}).default
+end
+
+-- Runs `f` without triggering any autocmds, like |:noautocmd|, and returns
+-- what it returns
+M.noautocmd = function(f)
+  local eventignore = vim.o.eventignore
+  vim.o.eventignore = "all"
+  local ok, value = pcall(f)
+  vim.o.eventignore = eventignore
+
+  if not ok then
+    error(value, 0)
+  end
+
+  return value
+end
+
+-- Runs `f` like |:silent!|, without showing the messages that it gives.
+-- Returns what `f` returns, or nil when it raises an error.
+M.silent = function(f)
+  local ok, value
+  local outer = M.silent_call
+  M.silent_call = function()
+    ok, value = pcall(f)
+  end
+
+  -- Only an ex command can be silent, so go through `:lua`
+  pcall(
+    vim.api.nvim_exec2,
+    "silent! lua require('vim9script').silent_call()",
+    { output = true }
+  )
+  M.silent_call = outer
+
+  if ok then
+    return value
+  end
+end
+
 M.iter = function(expr)
   if vim.tbl_islist(expr) then
     return ipairs(expr)
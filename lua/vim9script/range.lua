@@ -0,0 +1,23 @@
+local range = {}
+
+-- Finds the line for a `/pattern/` or `?pattern?` address, searching from
+-- `line` the same way |:range| does
+range.search = function(pattern, backward, line)
+  local view = vim.fn.winsaveview()
+  if backward then
+    vim.fn.cursor(line, 1)
+  else
+    vim.fn.cursor(line, vim.fn.col { line, "$" })
+  end
+
+  local found = vim.fn.search(pattern, backward and "bn" or "n")
+  vim.fn.winrestview(view)
+
+  if found == 0 then
+    error("E486: Pattern not found: " .. pattern, 0)
+  end
+
+  return found
+end
+
+return range
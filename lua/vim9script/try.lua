@@ -0,0 +1,141 @@
+-- Runtime for try, catch and finally
+--
+-- The body of a try, each catch and the finally are generated as functions.
+-- They return `NVIM9.ITER_*` and a value when they are left with `return`,
+-- `break` or `continue`, so that the generated code can do the same once
+-- the finally has run.
+local try = {}
+
+-- The directory of the vim9script runtime, such as `@/path/lua/vim9script/`
+local runtime = debug.getinfo(1, "S").source:match("^(@.*[/\\])[^/\\]*$")
+
+-- Where the error that is being handled was raised, see |v:throwpoint|
+local throwpoint = function()
+  -- Skip this function, the handler, any functions written in C, such as
+  -- `error` itself, and the runtime, such as `try.throw`, to get to the
+  -- generated code that raised the error
+  local level = 3
+  while true do
+    local info = debug.getinfo(level, "Sl")
+    if not info then
+      return ""
+    end
+
+    local in_runtime = runtime and vim.startswith(info.source, runtime)
+    if info.what ~= "C" and not in_runtime then
+      return string.format("%s, line %d", info.short_src, info.currentline)
+    end
+
+    level = level + 1
+  end
+end
+
+-- The command that is running in a try, see `try.at`
+local command = nil
+
+-- The exception for `err`, in the form that vim uses for |v:exception|
+local exception = function(err)
+  if type(err) ~= "string" then
+    return vim.inspect(err)
+  end
+
+  -- Lua prefixes errors with where they were raised, like `file.lua:12: `
+  err = err:gsub("^[^%s:]+%.lua:%d+: ", ""):gsub('^%[string ".-"%]:%d+: ', "")
+
+  -- Errors from vim functions do not know the command, `Vim:E716: ...`
+  if command then
+    err = err:gsub("^Vim:", "Vim(" .. command .. "):")
+  end
+
+  return err
+end
+
+local handler = function(err)
+  return {
+    exception = exception(err),
+    throwpoint = throwpoint(),
+  }
+end
+
+-- Runs `fn`, setting |v:exception| and |v:throwpoint| to those of `caught`
+-- while it runs
+local with_exception = function(caught, fn)
+  local exception = rawget(vim.v, "exception")
+  local point = rawget(vim.v, "throwpoint")
+
+  rawset(vim.v, "exception", caught.exception)
+  rawset(vim.v, "throwpoint", caught.throwpoint)
+  local results = { xpcall(fn, handler) }
+  rawset(vim.v, "exception", exception)
+  rawset(vim.v, "throwpoint", point)
+
+  return unpack(results, 1, 3)
+end
+
+-- Runs `body`. When it raises an error, runs the first of `catches` whose
+-- pattern matches the error, and raises the error again when none do.
+-- `finally` always runs, and overrides how the others were left when it
+-- is left with `return`, `break` or `continue`.
+try.run = function(body, catches, finally)
+  local outer_command = command
+  local ok, status, ret = xpcall(body, handler)
+  command = outer_command
+  if not ok then
+    local caught = status
+    for _, catch in ipairs(catches) do
+      if not catch.pattern or vim.fn.match(caught.exception, catch.pattern) >= 0 then
+        ok, status, ret = with_exception(caught, catch.body)
+        command = outer_command
+        break
+      end
+    end
+  end
+
+  if finally then
+    local finally_status, finally_ret = finally()
+    if finally_status then
+      return finally_status, finally_ret
+    end
+  end
+
+  if not ok then
+    error(status.exception, 0)
+  end
+
+  return status, ret
+end
+
+-- Sets the command that is running in a try, so that errors from vim can
+-- be turned into `Vim(command):E123: ...`. Errors in functions that are
+-- called from the try are reported with the command that called them.
+try.at = function(name)
+  command = name
+end
+
+-- Raises `value` the way |:throw| does, so that |v:exception| is `value`
+try.throw = function(value)
+  if type(value) == "number" then
+    value = tostring(value)
+  elseif type(value) ~= "string" then
+    error("Vim(throw):E1012: Type mismatch; expected string but got " .. vim.fn.typename(value), 0)
+  end
+
+  if value == "" then
+    error("Vim(throw):E1129: Throw with empty string", 0)
+  elseif vim.startswith(value, "Vim") then
+    error("Vim(throw):E608: Cannot :throw exceptions with 'Vim' prefix", 0)
+  end
+
+  error(value, 0)
+end
+
+-- Raises `value` as an error message, see |:echoerr|
+try.echoerr = function(value)
+  if type(value) ~= "string" then
+    value = vim.fn.string(value)
+  end
+
+  error("Vim(echoerr):" .. value, 0)
+end
+
+return try
@@ -1,7 +1,11 @@
-use std::path::Path;
+use std::{
+    panic,
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -9,9 +13,109 @@ struct Args {
     /// The directory for generating paths
     #[arg(short, long)]
     dir: String,
+
+    /// How errors in the vim9script sources are reported
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ErrorFormat {
+    /// Source snippets with the error location underlined, on stderr
+    Human,
+    /// One JSON object per error, on stdout
+    Json,
+}
+
+enum GenError {
+    Diagnostics(Vec<parser::Diagnostic>),
+    Panic(String),
 }
 
-fn gen_directory(src: &Path, gen: &Path, subdir: &str) -> Result<()> {
+fn generate_file(path: &Path, contents: &str) -> Result<String, GenError> {
+    // The generator still has some `todo!()`s in it,
+    // report those as errors for the file instead of a backtrace.
+    // The hook is only replaced while generating, so any other panic is
+    // still reported as usual.
+    let location = Arc::new(Mutex::new(None));
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new({
+        let location = location.clone();
+        move |info| {
+            *location.lock().unwrap() = info.location().map(|l| l.to_string())
+        }
+    }));
+
+    let result = panic::catch_unwind(|| {
+        gen::generate_with_diagnostics(contents, Some(path), false)
+    });
+    panic::set_hook(previous_hook);
+
+    match result {
+        Ok(generated) => generated.map_err(GenError::Diagnostics),
+        Err(payload) => {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(s) => s.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(s) => s.clone(),
+                    None => "unknown error".to_string(),
+                },
+            };
+
+            let message = match location.lock().unwrap().take() {
+                Some(location) => format!("{} (at {})", message, location),
+                None => message,
+            };
+
+            Err(GenError::Panic(message))
+        }
+    }
+}
+
+fn report_error(
+    path: &Path,
+    contents: &str,
+    err: GenError,
+    format: ErrorFormat,
+) {
+    let path = path.display().to_string();
+    match err {
+        GenError::Diagnostics(diagnostics) => {
+            for diagnostic in diagnostics {
+                match format {
+                    ErrorFormat::Human => {
+                        eprintln!("{}", diagnostic.render(&path, contents))
+                    }
+                    ErrorFormat::Json => {
+                        println!("{}", diagnostic.to_json(&path))
+                    }
+                }
+            }
+        }
+        GenError::Panic(message) => {
+            let diagnostic = parser::Diagnostic::without_location(format!(
+                "internal error: {}",
+                message
+            ));
+            match format {
+                ErrorFormat::Human => {
+                    eprintln!("{}", diagnostic.render(&path, contents))
+                }
+                ErrorFormat::Json => {
+                    println!("{}", diagnostic.to_json(&path))
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of files that could not be generated
+fn gen_directory(
+    src: &Path,
+    gen: &Path,
+    subdir: &str,
+    format: ErrorFormat,
+) -> Result<usize> {
     // base: /path/src/
     // subdir: autoload,
     // subdir: plugin
@@ -24,10 +128,12 @@ fn gen_directory(src: &Path, gen: &Path, subdir: &str) -> Result<()> {
         std::fs::create_dir(&gen_subdir)?;
     }
 
+    let mut failed = 0;
     for f in src_subdir.read_dir()? {
         let f = f?;
         if f.file_type()?.is_dir() {
-            gen_directory(src, gen, f.path().to_str().unwrap())?
+            failed +=
+                gen_directory(src, gen, f.path().to_str().unwrap(), format)?
         }
 
         match f.path().extension() {
@@ -45,22 +151,31 @@ fn gen_directory(src: &Path, gen: &Path, subdir: &str) -> Result<()> {
         let stem = Path::with_extension(&Path::new(stem), "lua");
         let generated_file = gen_subdir.join(stem);
 
-        println!("plugin: {:?}", f);
-        println!("  filename: {:?}", generated_file);
+        if format == ErrorFormat::Human {
+            println!("plugin: {:?}", f);
+            println!("  filename: {:?}", generated_file);
+        }
 
-        let generated = gen::generate(&contents, false);
-        std::fs::write(generated_file, generated)?;
+        match generate_file(&path, &contents) {
+            Ok(generated) => std::fs::write(generated_file, generated)?,
+            Err(err) => {
+                report_error(&path, &contents, err, format);
+                failed += 1;
+            }
+        }
 
         // TODO: Go DEEPER
     }
 
-    Ok(())
+    Ok(failed)
 }
 
 fn main() -> Result<()> {
     parser::setup_trace();
     let args = Args::parse();
-    println!("dir: {}", args.dir);
+    if args.error_format == ErrorFormat::Human {
+        println!("dir: {}", args.dir);
+    }
 
     let dir_base = Path::new(&args.dir);
     let dir_src = dir_base.join("src");
@@ -70,8 +185,17 @@ fn main() -> Result<()> {
         std::fs::create_dir(dir_gen.clone())?;
     }
 
-    gen_directory(&dir_src, &dir_gen, "plugin")?;
-    gen_directory(&dir_src, &dir_gen, "autoload")?;
+    let failed =
+        gen_directory(&dir_src, &dir_gen, "plugin", args.error_format)?
+            + gen_directory(&dir_src, &dir_gen, "autoload", args.error_format)?;
+
+    if failed > 0 {
+        if args.error_format == ErrorFormat::Human {
+            eprintln!("error: could not generate {} file(s)", failed);
+        }
+
+        std::process::exit(1);
+    }
 
     Ok(())
 }